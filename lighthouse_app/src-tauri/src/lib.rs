@@ -1,16 +1,114 @@
-use lighthouse_core::models::DeviceInfo;
+use lighthouse_core::cancel::{run_cancellable, CancellationToken};
+use lighthouse_core::models::{to_camel_case_json, DeviceInfo, PowerState};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
 use std::env;
 use std::fs;
+use std::future::Future;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
 
 #[cfg(all(windows, feature = "installer"))]
 use std::os::windows::process::CommandExt;
 #[cfg(all(windows, feature = "installer"))]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
+/// Convert a result to camelCase JSON for the frontend.
+/// The CLI keeps the snake_case field names for backward compatibility.
+fn camel_case<T: Serialize>(value: T) -> Result<Value, String> {
+    to_camel_case_json(&value).map_err(|e| e.to_string())
+}
+
+/// Cancellation tokens of the long-running operations that are still in progress
+#[derive(Default)]
+struct Operations {
+    next_id: AtomicU64,
+    tokens: Mutex<HashMap<u64, CancellationToken>>,
+}
+
+/// Sent with the `operation-finished` event when an operation completes or fails
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OperationFinished {
+    id: u64,
+    result: Option<Value>,
+    error: Option<String>,
+}
+
+/// Sent with the `scan-progress` event while `scan_for_devices` runs
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScanProgress {
+    id: u64,
+    found: usize,
+}
+
+/// Sent with the `device-found` event for each base station `scan_for_devices` hears
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeviceFound {
+    id: u64,
+    device: Value,
+}
+
+/// Run the future made by `operation` in the background and return its id straight
+/// away. The future gets the id to tag any progress events it sends. The outcome is
+/// reported with an `operation-finished` event, or `operation-canceled` if
+/// `cancel_operation` is called first.
+fn start_operation<O, F>(app: AppHandle, operation: O) -> u64
+where
+    O: FnOnce(u64) -> F,
+    F: Future<Output = Result<Value, String>> + Send + 'static,
+{
+    let operations = app.state::<Operations>();
+    let id = operations.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    let token = CancellationToken::new();
+    operations.tokens.lock().unwrap().insert(id, token.clone());
+    let operation = operation(id);
+
+    tauri::async_runtime::spawn(async move {
+        let outcome = run_cancellable(&token, operation).await;
+        app.state::<Operations>().tokens.lock().unwrap().remove(&id);
+
+        let emitted = match outcome {
+            Some(result) => {
+                let (result, error) = match result {
+                    Ok(value) => (Some(value), None),
+                    Err(error) => (None, Some(error)),
+                };
+                app.emit(
+                    "operation-finished",
+                    OperationFinished { id, result, error },
+                )
+            }
+            None => app.emit("operation-canceled", id),
+        };
+        if let Err(e) = emitted {
+            eprintln!("Failed to report operation {}: {}", id, e);
+        }
+    });
+
+    id
+}
+
+/// Cancel a running operation. Returns false if it already finished.
 #[tauri::command]
-fn get_devices() -> Result<Vec<DeviceInfo>, String> {
-    lighthouse_core::config::load_devices().map_err(|e| e.to_string())
+fn cancel_operation(operations: State<'_, Operations>, id: u64) -> bool {
+    match operations.tokens.lock().unwrap().get(&id) {
+        Some(token) => {
+            token.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+#[tauri::command]
+fn get_devices() -> Result<Value, String> {
+    camel_case(lighthouse_core::config::load_devices().map_err(|e| e.to_string())?)
 }
 
 /// Clear all saved devices from the configuration file
@@ -19,26 +117,126 @@ fn clear_saved_devices() -> Result<(), String> {
     lighthouse_core::config::save_devices(&Vec::new()).map_err(|e| e.to_string())
 }
 
+/// Scan for base stations, sending each one with a `device-found` event as soon as
+/// it's heard and the number found so far with `scan-progress`, so the list can fill
+/// in during the scan. The result is the saved devices once the scan is over.
 #[tauri::command]
-async fn scan_for_devices() -> Result<Vec<DeviceInfo>, String> {
-    lighthouse_core::bluetooth::scan_process_and_save_with_json(0xFF, false)
+fn scan_for_devices(app: AppHandle) -> u64 {
+    let events = app.clone();
+    start_operation(app, move |id| async move {
+        // A lost progress event only delays a device, the result still lists it
+        events
+            .emit("scan-progress", ScanProgress { id, found: 0 })
+            .ok();
+        let mut found = 0;
+        lighthouse_core::bluetooth::stream_lighthouses(
+            &lighthouse_core::bluetooth::ScanOptions::default(),
+            |device| {
+                found += 1;
+                if let Ok(device) = camel_case(device) {
+                    events.emit("device-found", DeviceFound { id, device }).ok();
+                }
+                events
+                    .emit("scan-progress", ScanProgress { id, found })
+                    .ok();
+            },
+            false,
+        )
         .await
         .map_err(|e| e.to_string())?;
-    lighthouse_core::config::load_devices().map_err(|e| e.to_string())
+        camel_case(lighthouse_core::config::load_devices().map_err(|e| e.to_string())?)
+    })
 }
 
+/// Scan and report how many BLE devices were seen alongside the base stations found
 #[tauri::command]
-async fn power_on_all() -> Result<Vec<DeviceInfo>, String> {
-    lighthouse_core::bluetooth::power_on_lighthouses_with_json(false)
+fn scan_with_report(app: AppHandle) -> u64 {
+    start_operation(app, |_| async {
+        let report = lighthouse_core::bluetooth::scan_with_report(
+            &lighthouse_core::bluetooth::ScanOptions::default(),
+            false,
+        )
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| e.to_string())?;
+        camel_case(report)
+    })
 }
 
+/// Probe and cache which operations each nearby base station supports
 #[tauri::command]
-async fn standby_all() -> Result<Vec<DeviceInfo>, String> {
-    lighthouse_core::bluetooth::standby_lighthouses_with_json(false)
-        .await
-        .map_err(|e| e.to_string())
+async fn probe_devices(force: bool) -> Result<Value, String> {
+    let devices = lighthouse_core::bluetooth::probe_lighthouses(
+        &lighthouse_core::bluetooth::ScanOptions::default(),
+        force,
+        false,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    camel_case(devices)
+}
+
+/// Power state of a cached base station, or the reason it couldn't be read
+#[derive(Serialize)]
+struct DeviceState {
+    device: DeviceInfo,
+    state: Option<PowerState>,
+    error: Option<String>,
+}
+
+/// Read the state of every cached base station for a quick dashboard refresh
+#[tauri::command]
+async fn read_all_states() -> Result<Value, String> {
+    let states = lighthouse_core::bluetooth::read_all_states(
+        &lighthouse_core::bluetooth::ScanOptions::default(),
+        false,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    camel_case(
+        states
+            .into_iter()
+            .map(|(device, state)| match state {
+                Ok(state) => DeviceState {
+                    device,
+                    state: Some(state),
+                    error: None,
+                },
+                Err(error) => DeviceState {
+                    device,
+                    state: None,
+                    error: Some(error),
+                },
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Recent scans, commands and errors from the in-memory event log
+#[tauri::command]
+fn get_events() -> Result<Value, String> {
+    camel_case(lighthouse_core::events::recent_events())
+}
+
+#[tauri::command]
+fn power_on_all(app: AppHandle) -> u64 {
+    start_operation(app, |_| async {
+        camel_case(
+            lighthouse_core::bluetooth::power_on_lighthouses_with_json(false)
+                .await
+                .map_err(|e| e.to_string())?,
+        )
+    })
+}
+
+#[tauri::command]
+fn standby_all(app: AppHandle) -> u64 {
+    start_operation(app, |_| async {
+        camel_case(
+            lighthouse_core::bluetooth::standby_lighthouses_with_json(false)
+                .await
+                .map_err(|e| e.to_string())?,
+        )
+    })
 }
 
 #[tauri::command]
@@ -91,6 +289,36 @@ fn is_installer_supported() -> bool {
     }
 }
 
+/// Run a PowerShell script without showing a window and return what it printed
+#[cfg(all(windows, feature = "installer"))]
+fn run_powershell(script: &str) -> Result<String, String> {
+    use std::process::Command;
+
+    let output = Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-WindowStyle",
+            "Hidden",
+            "-Command",
+            script,
+        ])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .map_err(|e| format!("Failed to run PowerShell: {}", e))?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Quote a string for a single-quoted PowerShell literal
+#[cfg(all(windows, feature = "installer"))]
+fn powershell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
 /// Create a Windows shortcut (.lnk file) using PowerShell
 #[cfg(all(windows, feature = "installer"))]
 fn create_shortcut(
@@ -98,8 +326,6 @@ fn create_shortcut(
     target_path: &PathBuf,
     description: &str,
 ) -> Result<(), String> {
-    use std::process::Command;
-
     let ps_script = format!(
         r#"
         $WshShell = New-Object -comObject WScript.Shell
@@ -113,24 +339,7 @@ fn create_shortcut(
         description
     );
 
-    let output = Command::new("powershell")
-        .args([
-            "-NoProfile",
-            "-NonInteractive",
-            "-WindowStyle",
-            "Hidden",
-            "-Command",
-            &ps_script,
-        ])
-        .creation_flags(CREATE_NO_WINDOW)
-        .output()
-        .map_err(|e| format!("Failed to run PowerShell: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Failed to create shortcut: {}", stderr));
-    }
-
+    run_powershell(&ps_script).map_err(|e| format!("Failed to create shortcut: {}", e))?;
     Ok(())
 }
 
@@ -243,11 +452,174 @@ fn uninstall_application() -> Result<(), String> {
     }
 }
 
+/// Release feed checked for updates unless `update_url` is set in the app config
+#[cfg(all(windows, feature = "installer"))]
+const DEFAULT_UPDATE_URL: &str = "https://api.github.com/repos/matty/lighthouse-rs/releases/latest";
+
+/// Result of checking the release feed for a newer version
+#[cfg(all(windows, feature = "installer"))]
+#[derive(Serialize)]
+struct UpdateInfo {
+    current_version: String,
+    latest_version: String,
+    update_available: bool,
+    /// The release's exe asset, which `apply_update` installs
+    download_url: Option<String>,
+    /// Release page, for showing the release notes
+    release_url: Option<String>,
+}
+
+/// Split a version such as `v1.2.3` into its numbers, ignoring trailing zeros
+/// so that `1.2` and `1.2.0` compare equal
+#[cfg(all(windows, feature = "installer"))]
+fn version_numbers(version: &str) -> Vec<u64> {
+    let mut numbers: Vec<u64> = version
+        .trim()
+        .trim_start_matches(['v', 'V'])
+        .split(['.', '-', '+'])
+        .map_while(|part| part.parse().ok())
+        .collect();
+    while numbers.last() == Some(&0) {
+        numbers.pop();
+    }
+    numbers
+}
+
+/// Compare the latest release in the release feed (GitHub releases JSON) with this version
+#[tauri::command]
+async fn check_for_update() -> Result<Value, String> {
+    #[cfg(all(windows, feature = "installer"))]
+    {
+        let url = get_app_config()?
+            .update_url
+            .unwrap_or_else(|| DEFAULT_UPDATE_URL.to_string());
+        let script = format!(
+            "[Net.ServicePointManager]::SecurityProtocol = 'Tls12'; \
+             (Invoke-WebRequest -UseBasicParsing -Headers @{{'User-Agent'='lighthouse-manager'}} -Uri {}).Content",
+            powershell_quote(&url)
+        );
+        let content = tauri::async_runtime::spawn_blocking(move || run_powershell(&script))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Failed to check for updates: {}", e))?;
+        let release: Value = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse the release feed: {}", e))?;
+
+        let latest_version = release["tag_name"]
+            .as_str()
+            .ok_or("The release feed has no tag_name")?
+            .to_string();
+        let current_version = env!("CARGO_PKG_VERSION").to_string();
+        let download_url = release["assets"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|asset| {
+                asset["name"]
+                    .as_str()
+                    .is_some_and(|name| name.to_ascii_lowercase().ends_with(".exe"))
+            })
+            .and_then(|asset| asset["browser_download_url"].as_str())
+            .map(String::from);
+
+        camel_case(UpdateInfo {
+            update_available: version_numbers(&latest_version) > version_numbers(&current_version),
+            current_version,
+            latest_version,
+            download_url,
+            release_url: release["html_url"].as_str().map(String::from),
+        })
+    }
+    #[cfg(all(not(windows), feature = "installer"))]
+    {
+        Err("Updates are only supported on Windows.".to_string())
+    }
+    #[cfg(not(feature = "installer"))]
+    {
+        Err("Updates are not supported in this build.".to_string())
+    }
+}
+
+/// Download the new exe, then exit and let a script swap it in and restart the app.
+/// Like the uninstaller, the script waits for this process to exit first, since a
+/// running exe can't be replaced.
+#[tauri::command]
+async fn apply_update(download_url: String) -> Result<(), String> {
+    #[cfg(all(windows, feature = "installer"))]
+    {
+        use std::process::Command;
+
+        let install_exe = get_install_path()?.join("Lighthouse Manager.exe");
+        if !install_exe.exists() {
+            return Err("Lighthouse Manager is not installed.".to_string());
+        }
+
+        let temp_dir =
+            env::var("TEMP").map_err(|_| "Failed to get TEMP environment variable".to_string())?;
+        let update_exe = PathBuf::from(&temp_dir).join("lighthouse_update.exe");
+
+        let script = format!(
+            "[Net.ServicePointManager]::SecurityProtocol = 'Tls12'; \
+             Invoke-WebRequest -UseBasicParsing -Headers @{{'User-Agent'='lighthouse-manager'}} -Uri {} -OutFile {}",
+            powershell_quote(&download_url),
+            powershell_quote(&update_exe.to_string_lossy())
+        );
+        tauri::async_runtime::spawn_blocking(move || run_powershell(&script))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Failed to download the update: {}", e))?;
+
+        // Wait for the app to exit, swap in the new exe, start it and delete the script
+        let mut script = String::new();
+        script.push_str("@echo off\n");
+        script.push_str(":waitloop\n");
+        script.push_str("tasklist /FI \"IMAGENAME eq Lighthouse Manager.exe\" /NH 2>nul | find /i \"Lighthouse Manager.exe\" >nul\n");
+        script.push_str("if %errorlevel%==0 (\n");
+        script.push_str("    timeout /t 1 /nobreak >nul\n");
+        script.push_str("    goto waitloop\n");
+        script.push_str(")\n");
+        script.push_str(&format!(
+            "copy /y \"{}\" \"{}\" >nul\n",
+            update_exe.display(),
+            install_exe.display()
+        ));
+        script.push_str(&format!("del /f /q \"{}\"\n", update_exe.display()));
+        script.push_str(&format!("start \"\" \"{}\"\n", install_exe.display()));
+        script.push_str("(goto) 2>nul & del \"%~f0\"\n");
+
+        let script_path = PathBuf::from(&temp_dir).join("lighthouse_update.bat");
+        fs::write(&script_path, &script)
+            .map_err(|e| format!("Failed to write the update script: {}", e))?;
+        Command::new("cmd")
+            .args(["/C", &script_path.to_string_lossy()])
+            .creation_flags(CREATE_NO_WINDOW)
+            .spawn()
+            .map_err(|e| format!("Failed to start the update: {}", e))?;
+
+        // Exit so the script can replace the exe
+        std::process::exit(0);
+    }
+    #[cfg(all(not(windows), feature = "installer"))]
+    {
+        let _ = download_url;
+        Err("Updates are only supported on Windows.".to_string())
+    }
+    #[cfg(not(feature = "installer"))]
+    {
+        let _ = download_url;
+        Err("Updates are not supported in this build.".to_string())
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(default)]
 pub struct AppConfig {
     pub do_not_show_install_prompt: bool,
     pub theme: String,
+    /// Release feed to check for updates instead of the project's GitHub releases
+    pub update_url: Option<String>,
+    /// Register with SteamVR at startup, waiting for it to be installed if it isn't yet
+    pub register_steamvr_on_startup: bool,
 }
 
 impl Default for AppConfig {
@@ -255,6 +627,8 @@ impl Default for AppConfig {
         Self {
             do_not_show_install_prompt: false,
             theme: "dark".to_string(),
+            update_url: None,
+            register_steamvr_on_startup: false,
         }
     }
 }
@@ -337,31 +711,68 @@ fn restart_application(app: tauri::AppHandle) {
     app.restart();
 }
 
+/// How often and for how long `register_steamvr_on_startup` checks for SteamVR
+const STEAMVR_REGISTER_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+const STEAMVR_REGISTER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    if let Some(capacity) = lighthouse_core::config::load_settings()
+        .ok()
+        .and_then(|settings| settings.event_log_capacity)
+    {
+        lighthouse_core::events::set_event_log_capacity(capacity);
+    }
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .manage(Operations::default())
         .setup(|app| {
-            use tauri::Manager;
             let window = app.get_webview_window("main").unwrap();
             let _ = window.set_min_size(Some(tauri::Size::Logical(tauri::LogicalSize {
                 width: 600.0,
                 height: 600.0,
             })));
+
+            // On a first run SteamVR may not be installed yet, so keep checking for it
+            // in the background rather than failing
+            let register = get_app_config().is_ok_and(|config| config.register_steamvr_on_startup);
+            if register && !lighthouse_core::steamvr_integration::is_registered().unwrap_or(false) {
+                tauri::async_runtime::spawn(async {
+                    let result =
+                        lighthouse_core::steamvr_integration::register_when_steamvr_available(
+                            false,
+                            STEAMVR_REGISTER_INTERVAL,
+                            STEAMVR_REGISTER_TIMEOUT,
+                            false,
+                        )
+                        .await;
+                    if let Err(e) = result {
+                        eprintln!("Failed to register with SteamVR: {}", e);
+                    }
+                });
+            }
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             get_devices,
             clear_saved_devices,
             scan_for_devices,
+            scan_with_report,
+            probe_devices,
+            read_all_states,
+            get_events,
             power_on_all,
             standby_all,
+            cancel_operation,
             get_steamvr_status,
             set_steamvr_registration,
             check_installation_status,
             is_installer_supported,
             install_application,
             uninstall_application,
+            check_for_update,
+            apply_update,
             get_app_config,
             save_app_config,
             get_app_data_dir,
@@ -4,6 +4,7 @@
 use std::env;
 use std::fs;
 use std::path::PathBuf;
+use std::time::Instant;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -25,23 +26,46 @@ fn main() {
 }
 
 fn run_headless(power_on: bool) {
-    // Create a runtime for async execution
-    let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+    let started = Instant::now();
 
-    rt.block_on(async {
+    // A single-threaded runtime is enough for one command and avoids
+    // spinning up a worker thread per core before anything happens
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to create Tokio runtime");
+
+    let result = rt.block_on(async {
         if power_on {
             println!("Headless: Powering on lighthouses...");
-            if let Err(e) = lighthouse_core::bluetooth::power_on_lighthouses_with_json(false).await
-            {
+            let result = lighthouse_core::bluetooth::power_on_lighthouses_with_json(false).await;
+            if let Err(e) = &result {
                 eprintln!("Failed to power on lighthouses: {}", e);
             }
+            result
         } else {
             println!("Headless: Setting lighthouses to standby...");
-            if let Err(e) = lighthouse_core::bluetooth::standby_lighthouses_with_json(false).await {
+            // Automatic standby never touches excluded devices
+            let options = lighthouse_core::bluetooth::ScanOptions {
+                respect_exclusions: true,
+                ..Default::default()
+            };
+            let result =
+                lighthouse_core::bluetooth::standby_lighthouses_with_opts(&options, false).await;
+            if let Err(e) = &result {
                 eprintln!("Failed to set lighthouses to standby: {}", e);
             }
+            result
         }
     });
+
+    // There's no console in release builds, so this is the only sign the handler ran
+    let result = result.as_deref().map_err(|e| e.to_string());
+    if let Err(e) = lighthouse_core::notifications::notify_power_result(power_on, result) {
+        eprintln!("Failed to show a desktop notification: {}", e);
+    }
+
+    println!("Headless: Done in {} ms", started.elapsed().as_millis());
 }
 
 /// Run the uninstall process
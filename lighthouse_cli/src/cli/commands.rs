@@ -5,17 +5,191 @@ pub fn print_help() {
     println!("Options:");
     println!("  --poweron             Power on all detected Lighthouse devices");
     println!("  --standby             Put all detected Lighthouse devices in standby mode");
+    println!(
+        "  --sleep               Put all detected Lighthouse devices in deep sleep, which uses"
+    );
+    println!("                        less power than standby but takes longer to wake");
+    println!("  --resolve-conflict <poweron|standby|error>");
+    println!("                        Which command to send when both --poweron and --standby are given.");
+    println!("                        The default, error, refuses to send either");
     println!("  --scan                Scan for devices");
     println!("  --devices             Return a list of known devices");
+    println!("  --status              Read and print the power state of each known device");
     println!("  --tui                 Start interactive terminal UI (TUI)");
+    println!("  --tray                Show base station status in the system tray (`tray` builds)");
+    println!(
+        "  --setup, --first-run  Guided setup: scan, nickname base stations, register with SteamVR"
+    );
+    println!(
+        "  --batch               Scan and print all devices with their status as one JSON document"
+    );
+    println!(
+        "  --stdin               Run commands read from stdin, one per line, with a JSON result for each"
+    );
+    println!("  --list-commands       List the supported device operations and exit");
+    println!("  --explain-exit [CODE] Print what an exit code means, or all of them");
+    println!("  --check               Exit 0 if a Bluetooth adapter is available and turned on");
+    println!(
+        "  --check-reachable     Like --check, and also scan for at least one cached base station"
+    );
+    println!("  --repeat <N>          Re-send --poweron/--standby up to N times until the state is confirmed");
+    println!("  --interval <SECS>     Seconds to wait between repeated commands (default: 2)");
+    println!(
+        "  --watch <SECS>        With --poweron, power on again every SECS seconds until Ctrl-C"
+    );
+    println!("  --scan-passes <N>     Run N short scans and combine the results (default: 1)");
+    println!("  --adaptive-scan       Retry with longer scans (up to 15s) if no devices are found");
+    println!("  --scan-timeout <SECS> Seconds each scan runs (default: 5, 3 per pass and before commands)");
+    println!(
+        "  --direct-fallback     If the scan fails, connect to known devices by address instead"
+    );
+    println!(
+        "  --discovery-retries <N> Re-discover services up to N times when no writable characteristic is found (default: 2)"
+    );
+    println!(
+        "  --connect-attempts <N> Try each device up to N times, backing off between attempts (default: 3)"
+    );
+    println!("  --no-save             Never write to the device config file");
+    println!(
+        "  --replace             Overwrite the device config file with only the devices found, instead of adding them to it"
+    );
+    println!(
+        "  --json-stream         With --scan, print each base station as a JSON line as soon as it's found"
+    );
+    println!("  --diff                With --scan, show how the scan differs from the cache without saving");
+    println!(
+        "  --save-raw-scan       With --scan, save everything the scan saw to a diagnostic file"
+    );
+    println!("  --only-changed        Skip devices already in the requested power state");
+    println!("  --device <ADDRESS>    Only command this cached device (repeatable)");
+    println!("  --verify              After --poweron/--standby, wait for each device to settle in the new state");
+    println!(
+        "  --verify-timeout <SECS> Seconds each device gets to settle with --verify (default: 15)"
+    );
+    println!(
+        "  --timeout-overall <SECS> Give up and exit with code 7 if the whole command takes longer"
+    );
+    println!(
+        "  --skip-weak           Don't command devices whose signal is below the weak threshold"
+    );
+    println!("  --respect-exclusions  Leave devices in standby_exclusions out of --standby");
+    println!("  --confirm-accepted    Check a command's status characteristic, where it has one");
+    println!(
+        "  --weak-threshold <DBM> Warn about devices with a weaker signal than this (default: -90)"
+    );
+    println!("  --on-success <CMD>    Run CMD after a successful --poweron/--standby");
+    println!("  --on-failure <CMD>    Run CMD after a failed --poweron/--standby");
+    println!("                        Hooks receive LIGHTHOUSE_OPERATION, LIGHTHOUSE_RESULT,");
+    println!("                        LIGHTHOUSE_EXIT_CODE, LIGHTHOUSE_DEVICES and");
+    println!("                        LIGHTHOUSE_SUCCESS_COUNT environment variables");
+    println!("  --event-log           Report --poweron/--standby results to the Windows Event Log");
+    println!(
+        "  --lang <CODE>         Language for log messages, e.g. en or de (default: from locale)"
+    );
+    println!("  --config-path <PATH>  Use PATH as the device config file; the settings, log and");
+    println!("                        history files are kept next to it. Takes precedence over");
+    println!("                        the LIGHTHOUSE_CONFIG environment variable, which takes");
+    println!("                        precedence over the default location");
+    println!("  --print-config-path   Print the path of the device config file and exit");
+    println!(
+        "  --log-file            Also write log messages to lighthouse.log next to the config file"
+    );
     println!("  --json                Output known devices in JSON format");
     println!("  --help                Display help information");
     println!();
+    println!("Capabilities:");
+    println!(
+        "  --probe               Report and cache which operations each base station supports"
+    );
+    println!("  --force-probe         With --probe, re-probe devices that were already probed");
+    println!();
+    println!("Nicknames:");
+    println!("  --set-alias <ADDR> <NAME>");
+    println!("                        Give a cached base station a nickname; `none` removes it");
+    println!();
+    println!("Reliability:");
+    println!("  --reset-reliability [ADDR]");
+    println!(
+        "                        Forget recorded command results for one or all base stations"
+    );
+    println!();
+    println!("Identify:");
+    println!("  --blink <ADDR> [N]    Flash the LED of one base station N times (default: 3)");
+    println!("  --identify <ADDR> [N] Same as --blink");
+    println!();
+    println!("Channels:");
+    println!("  --set-channel <ADDR> <CHANNEL>");
+    println!(
+        "                        Switch a base station to CHANNEL (1-16) and confirm it took it"
+    );
+    println!();
+    println!("Inspect:");
+    println!(
+        "  --inspect <ADDR>      Connect, print every service and characteristic with its value,"
+    );
+    println!("                        and stay connected until Ctrl-C. Sends no commands.");
+    println!();
+    println!("Recovery:");
+    println!("  --recover             Standby all base stations, power them back on and verify they're on");
+    println!(
+        "  --recover-delay <SECS> Seconds to wait in standby before powering on (default: 10)"
+    );
+    println!(
+        "  --recover-attempts <N> Power on attempts in total for each base station (default: 3)"
+    );
+    println!();
+    println!("Benchmark:");
+    println!("  --benchmark [N]       Time N scan, connect and command rounds (default: 5)");
+    println!("                        Sends each base station the state it's already in");
+    println!();
+    println!("History:");
+    println!("  --history             Print past --poweron/--standby commands, oldest first");
+    println!("  --since <DURATION>    Only commands within this long ago, e.g. 30m, 12h or 7d");
+    println!("  --operation <OP>      Only poweron, standby or sleep commands");
+    println!("  --failed-only         Only commands that failed");
+    println!("  --limit <N>           Only the N most recent matching commands");
+    println!("  --recover-cache       Rebuild the device cache from the devices in the history");
+    println!(
+        "  --yes                 With --recover-cache, replace an existing cache without asking"
+    );
+    println!();
+    println!("Settings:");
+    println!("  --config-list         Print every setting and its value");
+    println!("  --config-get <KEY>    Print the value of one setting");
+    println!("  --config-set <KEY> <VALUE>");
+    println!(
+        "                        Change a setting; `none` resets an optional one to its default"
+    );
+    println!();
+    println!("Device Filters:");
+    println!("  --allow <ADDR>        Only ever treat allowlisted addresses as Lighthouse devices");
+    println!("  --deny <ADDR>         Never treat this address as a Lighthouse device");
+    println!("  --clear-filter <ADDR> Remove an address from the allowlist and denylist");
+    println!();
+    println!("Bluetooth Adapters:");
+    println!("  --list-adapters       List available Bluetooth adapters");
+    println!("  --adapter <ADAPTER>   Use the adapter with this index or name for this run");
+    println!("  --set-default-adapter <ADAPTER>");
+    println!("                        Save the adapter with this index or name as the default");
+    println!();
     println!("SteamVR Integration:");
     println!("  --register-steamvr    Register lighthouse-rs with SteamVR for automatic power management");
     println!("  --unregister-steamvr  Unregister from SteamVR");
+    println!("  --wait-for-steamvr [SECS]");
+    println!("                        With --register-steamvr, wait up to SECS for SteamVR to be");
+    println!("                        installed (default: 600)");
+    println!("  --wait-for-steamvr-interval <SECS>");
+    println!("                        Seconds between checks for SteamVR (default: 10)");
     println!("  --steamvr-started     Called by SteamVR when it starts (powers on lighthouses)");
     println!(
         "  --steamvr-stopped     Called by SteamVR when it exits (puts lighthouses in standby)"
     );
+    println!(
+        "  --launch-steamvr      Power on, wait until every base station is on, then start SteamVR"
+    );
+    println!("  --import-playspaces   Group base stations by the SteamVR playspace they belong to");
+    println!("  --simulate-steamvr-started");
+    println!("                        Run the --steamvr-started handler with verbose logging");
+    println!("  --simulate-steamvr-stopped");
+    println!("                        Run the --steamvr-stopped handler with verbose logging");
 }
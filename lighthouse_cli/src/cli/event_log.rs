@@ -0,0 +1,68 @@
+// Reporting of command results to the Windows Event Log, for machines that are
+// monitored centrally. Only active in Windows builds with the `winlog` feature.
+use lighthouse_core::events::EventKind;
+
+/// Whether this build can write to the Windows Event Log
+pub const EVENT_LOG_SUPPORTED: bool = cfg!(all(windows, feature = "winlog"));
+
+/// Event source name shown in Event Viewer
+#[cfg(all(windows, feature = "winlog"))]
+const EVENT_SOURCE: &str = "lighthouse-rs";
+
+/// Write an entry to the Application event log. Errors become error entries
+/// with event ID 2, commands and scans information entries with IDs 1 and 3.
+#[cfg(all(windows, feature = "winlog"))]
+pub fn report_event(kind: EventKind, message: &str) -> Result<(), String> {
+    use std::io;
+    use std::ptr;
+    use windows_sys::Win32::System::EventLog::{
+        DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+        EVENTLOG_INFORMATION_TYPE,
+    };
+
+    let (event_type, event_id) = match kind {
+        EventKind::Command => (EVENTLOG_INFORMATION_TYPE, 1),
+        EventKind::Error => (EVENTLOG_ERROR_TYPE, 2),
+        EventKind::Scan => (EVENTLOG_INFORMATION_TYPE, 3),
+    };
+    let source = to_wide(EVENT_SOURCE);
+    let message = to_wide(message);
+    let strings = [message.as_ptr()];
+
+    // SAFETY: `source` and `message` are NUL-terminated UTF-16 buffers that outlive
+    // the calls, and the handle isn't used after it's deregistered
+    unsafe {
+        let handle = RegisterEventSourceW(ptr::null(), source.as_ptr());
+        if handle.is_null() {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        let reported = ReportEventW(
+            handle,
+            event_type,
+            0,
+            event_id,
+            ptr::null_mut(),
+            1,
+            0,
+            strings.as_ptr(),
+            ptr::null(),
+        );
+        let error = io::Error::last_os_error();
+        DeregisterEventSource(handle);
+        if reported == 0 {
+            return Err(error.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Without Windows Event Log support this does nothing
+#[cfg(not(all(windows, feature = "winlog")))]
+pub fn report_event(_kind: EventKind, _message: &str) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(all(windows, feature = "winlog"))]
+fn to_wide(value: &str) -> Vec<u16> {
+    value.encode_utf16().chain(std::iter::once(0)).collect()
+}
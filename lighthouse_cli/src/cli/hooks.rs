@@ -0,0 +1,141 @@
+use super::{error_log, log, report_event, CommandResponse, EXIT_SUCCESS};
+use lighthouse_core::events::EventKind;
+use lighthouse_core::history::{append_history, HistoryEntry};
+use lighthouse_core::models::DeviceInfo;
+use std::process::{self, Command};
+
+/// External programs to run after a device command completes.
+///
+/// Hooks run through the system shell with these environment variables set:
+/// - `LIGHTHOUSE_OPERATION`: `poweron`, `standby` or `sleep`
+/// - `LIGHTHOUSE_RESULT`: `success` or `failure`
+/// - `LIGHTHOUSE_EXIT_CODE`: the exit code the CLI is about to return
+/// - `LIGHTHOUSE_DEVICES`: comma-separated addresses of the devices that were commanded
+/// - `LIGHTHOUSE_SUCCESS_COUNT`: number of devices that were commanded
+pub struct CommandHooks {
+    pub operation: &'static str,
+    pub on_success: Option<String>,
+    pub on_failure: Option<String>,
+    /// Also report the outcome to the Windows Event Log
+    pub event_log: bool,
+}
+
+impl CommandHooks {
+    /// Run the success hook, if any, for the devices that were commanded
+    pub fn succeeded(&self, devices: &[DeviceInfo], json_output: bool) {
+        self.record(true, EXIT_SUCCESS, devices, json_output);
+        if let Some(command) = &self.on_success {
+            self.run(command, "success", EXIT_SUCCESS, devices, json_output);
+        }
+    }
+
+    /// Run the failure hook, if any, for a command that failed with `exit_code`
+    pub fn failed(&self, exit_code: i32, json_output: bool) {
+        self.record(false, exit_code, &[], json_output);
+        if let Some(command) = &self.on_failure {
+            self.run(command, "failure", exit_code, &[], json_output);
+        }
+    }
+
+    /// Run the failure hook, if any, then exit with `exit_code`.
+    /// The hook's own outcome never changes the exit code.
+    pub fn exit(&self, exit_code: i32, json_output: bool) -> ! {
+        self.failed(exit_code, json_output);
+        process::exit(exit_code);
+    }
+
+    /// Report an error response, then run the failure hook and exit with its code
+    pub fn fail(&self, response: CommandResponse, json_output: bool) -> ! {
+        response.report(json_output);
+        self.exit(response.error_code, json_output);
+    }
+
+    /// Add the outcome to the history log shown by --history, and to the
+    /// Windows Event Log if requested
+    fn record(&self, success: bool, exit_code: i32, devices: &[DeviceInfo], json_output: bool) {
+        let entry = HistoryEntry::now(self.operation, success, exit_code, devices);
+        if let Err(e) = append_history(&entry) {
+            error_log(
+                &format!("Warning: Failed to record command history: {}", e),
+                json_output,
+            );
+        }
+
+        if self.event_log {
+            let (kind, message) = if success {
+                let names: Vec<String> = devices
+                    .iter()
+                    .map(|device| format!("{} ({})", device.name, device.address))
+                    .collect();
+                (
+                    EventKind::Command,
+                    format!(
+                        "{} succeeded for {} devices: {}",
+                        self.operation,
+                        devices.len(),
+                        names.join(", ")
+                    ),
+                )
+            } else {
+                (
+                    EventKind::Error,
+                    format!("{} failed with exit code {}", self.operation, exit_code),
+                )
+            };
+            if let Err(e) = report_event(kind, &message) {
+                error_log(
+                    &format!("Warning: Failed to write to the Windows Event Log: {}", e),
+                    json_output,
+                );
+            }
+        }
+    }
+
+    fn run(
+        &self,
+        command: &str,
+        result: &str,
+        exit_code: i32,
+        devices: &[DeviceInfo],
+        json_output: bool,
+    ) {
+        let addresses: Vec<&str> = devices.iter().map(|d| d.address.as_str()).collect();
+
+        #[cfg(windows)]
+        let mut shell = {
+            let mut c = Command::new("cmd");
+            c.arg("/C").arg(command);
+            c
+        };
+        #[cfg(not(windows))]
+        let mut shell = {
+            let mut c = Command::new("sh");
+            c.arg("-c").arg(command);
+            c
+        };
+
+        log(
+            &format!("Running {} hook: {}", result, command),
+            json_output,
+        );
+        let status = shell
+            .env("LIGHTHOUSE_OPERATION", self.operation)
+            .env("LIGHTHOUSE_RESULT", result)
+            .env("LIGHTHOUSE_EXIT_CODE", exit_code.to_string())
+            .env("LIGHTHOUSE_DEVICES", addresses.join(","))
+            .env("LIGHTHOUSE_SUCCESS_COUNT", devices.len().to_string())
+            .status();
+
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => error_log(
+                &format!("Warning: {} hook exited with {}", result, status),
+                json_output,
+            ),
+            Err(e) => error_log(
+                &format!("Warning: Failed to run {} hook: {}", result, e),
+                json_output,
+            ),
+        }
+    }
+}
@@ -1,35 +1,158 @@
 // CLI module for command handling
 mod commands;
+mod event_log;
+mod hooks;
 mod response;
 
 pub use commands::*;
+pub use event_log::*;
+pub use hooks::*;
 pub use response::*;
 
+// Shared with the core so messages logged here also reach the --log-file
+pub use lighthouse_core::logging::{error_log, log};
+
 // Command-line argument constants
 pub const STANDBY_ARG: &str = "--standby";
 pub const POWERON_ARG: &str = "--poweron";
+pub const SLEEP_ARG: &str = "--sleep";
+pub const RESOLVE_CONFLICT_ARG: &str = "--resolve-conflict";
 pub const SCAN_ARG: &str = "--scan";
 pub const DEVICES_ARG: &str = "--devices";
+pub const STATUS_ARG: &str = "--status";
+pub const DEVICE_ARG: &str = "--device";
 pub const JSON_OUTPUT_ARG: &str = "--json";
+pub const JSON_STREAM_ARG: &str = "--json-stream";
+pub const DIFF_ARG: &str = "--diff";
 pub const HELP_ARG: &str = "--help";
 pub const TUI_ARG: &str = "--tui";
+pub const TRAY_ARG: &str = "--tray";
+pub const SETUP_ARG: &str = "--setup";
+pub const FIRST_RUN_ARG: &str = "--first-run";
+pub const PRINT_CONFIG_PATH_ARG: &str = "--print-config-path";
+pub const CONFIG_PATH_ARG: &str = "--config-path";
+pub const LIST_COMMANDS_ARG: &str = "--list-commands";
+pub const CONFIG_GET_ARG: &str = "--config-get";
+pub const CONFIG_SET_ARG: &str = "--config-set";
+pub const CONFIG_LIST_ARG: &str = "--config-list";
+pub const EXPLAIN_EXIT_ARG: &str = "--explain-exit";
+pub const BATCH_ARG: &str = "--batch";
+pub const STDIN_ARG: &str = "--stdin";
+pub const CHECK_ARG: &str = "--check";
+pub const CHECK_REACHABLE_ARG: &str = "--check-reachable";
+pub const REPEAT_ARG: &str = "--repeat";
+pub const INTERVAL_ARG: &str = "--interval";
+pub const WATCH_ARG: &str = "--watch";
+pub const SCAN_PASSES_ARG: &str = "--scan-passes";
+pub const ON_SUCCESS_ARG: &str = "--on-success";
+pub const ON_FAILURE_ARG: &str = "--on-failure";
+pub const EVENT_LOG_ARG: &str = "--event-log";
+pub const NO_SAVE_ARG: &str = "--no-save";
+pub const REPLACE_ARG: &str = "--replace";
+pub const SAVE_RAW_SCAN_ARG: &str = "--save-raw-scan";
+pub const ADAPTIVE_SCAN_ARG: &str = "--adaptive-scan";
+pub const DIRECT_FALLBACK_ARG: &str = "--direct-fallback";
+pub const DISCOVERY_RETRIES_ARG: &str = "--discovery-retries";
+pub const CONNECT_ATTEMPTS_ARG: &str = "--connect-attempts";
+pub const SCAN_TIMEOUT_ARG: &str = "--scan-timeout";
+pub const LANG_ARG: &str = "--lang";
+pub const LOG_FILE_ARG: &str = "--log-file";
+pub const ONLY_CHANGED_ARG: &str = "--only-changed";
+pub const VERIFY_ARG: &str = "--verify";
+pub const VERIFY_TIMEOUT_ARG: &str = "--verify-timeout";
+pub const SKIP_WEAK_ARG: &str = "--skip-weak";
+pub const RESPECT_EXCLUSIONS_ARG: &str = "--respect-exclusions";
+pub const CONFIRM_ACCEPTED_ARG: &str = "--confirm-accepted";
+pub const WEAK_THRESHOLD_ARG: &str = "--weak-threshold";
+pub const DEFAULT_VERIFY_TIMEOUT_SECS: u64 = 15;
+pub const TIMEOUT_OVERALL_ARG: &str = "--timeout-overall";
+/// How long a --timeout-overall abort waits for scans to stop and connections to close
+pub const CLEANUP_GRACE_SECS: u64 = 3;
+
+// Capability probe arguments
+pub const PROBE_ARG: &str = "--probe";
+pub const FORCE_PROBE_ARG: &str = "--force-probe";
+
+// Nickname arguments
+pub const SET_ALIAS_ARG: &str = "--set-alias";
+
+// Reliability arguments
+pub const RESET_RELIABILITY_ARG: &str = "--reset-reliability";
+
+// Device cache recovery arguments
+pub const RECOVER_CACHE_ARG: &str = "--recover-cache";
+pub const YES_ARG: &str = "--yes";
+
+// Identify arguments
+pub const BLINK_ARG: &str = "--blink";
+pub const IDENTIFY_ARG: &str = "--identify";
+pub const DEFAULT_BLINK_COUNT: u32 = 3;
+
+// Channel arguments
+pub const SET_CHANNEL_ARG: &str = "--set-channel";
+
+// GATT inspection arguments
+pub const INSPECT_ARG: &str = "--inspect";
+/// How often --inspect checks that the device is still connected
+pub const INSPECT_KEEPALIVE_SECS: u64 = 5;
+
+// Recovery arguments
+pub const RECOVER_ARG: &str = "--recover";
+pub const RECOVER_DELAY_ARG: &str = "--recover-delay";
+pub const RECOVER_ATTEMPTS_ARG: &str = "--recover-attempts";
+pub const DEFAULT_RECOVER_DELAY_SECS: u64 = 10;
+pub const DEFAULT_RECOVER_ATTEMPTS: u32 = 3;
+
+// Benchmark arguments
+pub const BENCHMARK_ARG: &str = "--benchmark";
+pub const DEFAULT_BENCHMARK_ITERATIONS: u32 = 5;
+
+// History arguments
+pub const HISTORY_ARG: &str = "--history";
+pub const SINCE_ARG: &str = "--since";
+pub const OPERATION_ARG: &str = "--operation";
+pub const FAILED_ONLY_ARG: &str = "--failed-only";
+pub const LIMIT_ARG: &str = "--limit";
+
+// Device address filter arguments
+pub const ALLOW_ARG: &str = "--allow";
+pub const DENY_ARG: &str = "--deny";
+pub const CLEAR_FILTER_ARG: &str = "--clear-filter";
+
+// Bluetooth adapter selection arguments
+pub const ADAPTER_ARG: &str = "--adapter";
+pub const LIST_ADAPTERS_ARG: &str = "--list-adapters";
+pub const SET_DEFAULT_ADAPTER_ARG: &str = "--set-default-adapter";
 
 // SteamVR integration command-line arguments
 pub const REGISTER_STEAMVR_ARG: &str = "--register-steamvr";
 pub const UNREGISTER_STEAMVR_ARG: &str = "--unregister-steamvr";
+pub const WAIT_FOR_STEAMVR_ARG: &str = "--wait-for-steamvr";
+/// How long --wait-for-steamvr waits for SteamVR to be installed without a value
+pub const DEFAULT_WAIT_FOR_STEAMVR_SECS: u64 = 600;
+pub const WAIT_FOR_STEAMVR_INTERVAL_ARG: &str = "--wait-for-steamvr-interval";
+/// Seconds between checks for SteamVR with --wait-for-steamvr, unless
+/// --wait-for-steamvr-interval is given
+pub const DEFAULT_WAIT_FOR_STEAMVR_INTERVAL_SECS: u64 = 10;
 pub const STEAMVR_STARTED_ARG: &str = "--steamvr-started";
 pub const STEAMVR_STOPPED_ARG: &str = "--steamvr-stopped";
+pub const IMPORT_PLAYSPACES_ARG: &str = "--import-playspaces";
+pub const SIMULATE_STEAMVR_STARTED_ARG: &str = "--simulate-steamvr-started";
+pub const LAUNCH_STEAMVR_ARG: &str = "--launch-steamvr";
+pub const SIMULATE_STEAMVR_STOPPED_ARG: &str = "--simulate-steamvr-stopped";
 
-/// Conditionally print messages when not in JSON mode
-pub fn log(message: &str, json_output: bool) {
-    if !json_output {
-        println!("{}", message);
-    }
+/// Get the value following a flag, e.g. `--repeat 3` returns `Some("3")`
+pub fn get_arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .map(|value| value.as_str())
 }
 
-/// Conditionally print error messages when not in JSON mode
-pub fn error_log(message: &str, json_output: bool) {
-    if !json_output {
-        eprintln!("{}", message);
-    }
+/// Values of every occurrence of a repeatable argument, e.g. `--device A --device B`
+pub fn get_arg_values<'a>(args: &'a [String], name: &str) -> Vec<&'a str> {
+    args.windows(2)
+        .filter(|pair| pair[0] == name)
+        .map(|pair| pair[1].as_str())
+        .collect()
 }
@@ -1,5 +1,8 @@
-use lighthouse_core::models::DeviceInfo;
+use lighthouse_core::error::LighthouseError;
+use lighthouse_core::models::{DeviceInfo, PowerState};
 use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::process;
 
 // Exit codes for command line interface
 pub const EXIT_SUCCESS: i32 = 0;
@@ -8,34 +11,277 @@ pub const EXIT_BLUETOOTH_ERROR: i32 = 2;
 pub const EXIT_NO_DEVICES_FOUND: i32 = 3;
 pub const EXIT_COMMAND_FAILED: i32 = 4;
 pub const EXIT_STEAMVR_ERROR: i32 = 5;
+pub const EXIT_PARTIAL_SUCCESS: i32 = 6;
+pub const EXIT_TIMEOUT: i32 = 7;
+pub const EXIT_BLUETOOTH_OFF: i32 = 8;
+
+/// Name and meaning of an exit code, as printed by --explain-exit
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct ExitCodeInfo {
+    pub code: i32,
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+/// Every exit code the CLI can return
+pub const EXIT_CODES: &[ExitCodeInfo] = &[
+    ExitCodeInfo {
+        code: EXIT_SUCCESS,
+        name: "EXIT_SUCCESS",
+        description: "The command succeeded",
+    },
+    ExitCodeInfo {
+        code: EXIT_GENERAL_ERROR,
+        name: "EXIT_GENERAL_ERROR",
+        description: "Invalid arguments, or an error reading or writing the config files",
+    },
+    ExitCodeInfo {
+        code: EXIT_BLUETOOTH_ERROR,
+        name: "EXIT_BLUETOOTH_ERROR",
+        description: "No Bluetooth adapter is available, or scanning failed",
+    },
+    ExitCodeInfo {
+        code: EXIT_NO_DEVICES_FOUND,
+        name: "EXIT_NO_DEVICES_FOUND",
+        description: "No base stations were found or reachable",
+    },
+    ExitCodeInfo {
+        code: EXIT_COMMAND_FAILED,
+        name: "EXIT_COMMAND_FAILED",
+        description: "A command couldn't be sent to a base station, or it didn't change state",
+    },
+    ExitCodeInfo {
+        code: EXIT_STEAMVR_ERROR,
+        name: "EXIT_STEAMVR_ERROR",
+        description: "SteamVR couldn't be found, registered with or launched",
+    },
+    ExitCodeInfo {
+        code: EXIT_PARTIAL_SUCCESS,
+        name: "EXIT_PARTIAL_SUCCESS",
+        description: "The command worked on some base stations but not all of them",
+    },
+    ExitCodeInfo {
+        code: EXIT_TIMEOUT,
+        name: "EXIT_TIMEOUT",
+        description: "The command did not finish within --timeout-overall",
+    },
+    ExitCodeInfo {
+        code: EXIT_BLUETOOTH_OFF,
+        name: "EXIT_BLUETOOTH_OFF",
+        description: "A Bluetooth adapter is present but Bluetooth is turned off",
+    },
+];
+
+/// Look up the name and meaning of an exit code
+pub fn explain_exit_code(code: i32) -> Option<&'static ExitCodeInfo> {
+    EXIT_CODES.iter().find(|info| info.code == code)
+}
+
+/// Version of the JSON response shape.
+/// Bump this whenever a field is added, removed or changes meaning so consumers can branch on it.
+///
+/// - 1: initial shape
+/// - 2: optional `data` payload for command-specific results
+/// - 3: optional `capabilities` on each device
+/// - 4: optional `reliability` and `rssi` on each device
+/// - 5: optional `reason` on errors
+/// - 6: optional `channel` on each device
+/// - 7: optional `state` or `state_error` on each device, from commands that read it
+pub const RESPONSE_SCHEMA_VERSION: u32 = 7;
+
+/// A device in a response, with its power state when the command read it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResponseDevice {
+    #[serde(flatten)]
+    pub device: DeviceInfo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<PowerState>,
+    /// Why the power state couldn't be read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_error: Option<String>,
+}
+
+impl From<DeviceInfo> for ResponseDevice {
+    fn from(device: DeviceInfo) -> Self {
+        ResponseDevice {
+            device,
+            state: None,
+            state_error: None,
+        }
+    }
+}
+
+/// Status of a single device as reported by --batch
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeviceStatus {
+    pub name: String,
+    pub address: String,
+    /// Whether the device was seen during this run's scan
+    pub reachable: bool,
+    /// Whether the device was already in the config file before this run
+    pub known: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rssi: Option<i16>,
+}
 
 /// Response structure for JSON output
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CommandResponse {
+    pub schema_version: u32,
     pub success: bool,
     pub message: String,
-    pub devices: Vec<DeviceInfo>,
+    pub devices: Vec<ResponseDevice>,
     pub error_code: i32,
+    /// Stable identifier for the error, e.g. `no_adapter`, when it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Command-specific payload, e.g. the adapter list for --list-adapters
+    /// or the device statuses for --batch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
 }
 
 impl CommandResponse {
     /// Create a success response with message and devices
     pub fn success(message: &str, devices: Vec<DeviceInfo>) -> Self {
         CommandResponse {
+            schema_version: RESPONSE_SCHEMA_VERSION,
             success: true,
             message: message.to_string(),
-            devices,
+            devices: devices.into_iter().map(ResponseDevice::from).collect(),
             error_code: EXIT_SUCCESS,
+            reason: None,
+            data: None,
         }
     }
 
+    /// Create a success response listing each device with the power state read from it.
+    /// The states are also in `data.states`, where they were before devices carried them.
+    pub fn with_states(
+        message: &str,
+        states: Vec<(DeviceInfo, Result<PowerState, String>)>,
+    ) -> Self {
+        let data: Vec<serde_json::Value> = states
+            .iter()
+            .map(|(device, state)| match state {
+                Ok(state) => serde_json::json!({ "address": device.address, "state": state }),
+                Err(e) => serde_json::json!({ "address": device.address, "error": e }),
+            })
+            .collect();
+        let devices = states
+            .into_iter()
+            .map(|(device, state)| {
+                let (state, state_error) = match state {
+                    Ok(state) => (Some(state), None),
+                    Err(e) => (None, Some(e)),
+                };
+                ResponseDevice {
+                    device,
+                    state,
+                    state_error,
+                }
+            })
+            .collect();
+        CommandResponse {
+            devices,
+            ..Self::success(message, Vec::new())
+        }
+        .with_data(serde_json::json!({ "states": data }))
+    }
+
     /// Create an error response with message and error code
     pub fn error(message: &str, error_code: i32) -> Self {
         CommandResponse {
+            schema_version: RESPONSE_SCHEMA_VERSION,
             success: false,
             message: message.to_string(),
             devices: Vec::new(),
             error_code,
+            reason: None,
+            data: None,
+        }
+    }
+
+    /// Create an error response for `error` with the given message. Our own errors
+    /// bring their exit code, reason and payload; any other error gets `fallback_code`.
+    pub fn from_error(message: &str, error: &(dyn Error + 'static), fallback_code: i32) -> Self {
+        match error.downcast_ref::<LighthouseError>() {
+            Some(e) => CommandResponse {
+                message: message.to_string(),
+                ..Self::from(e)
+            },
+            None => Self::error(message, fallback_code),
         }
     }
+
+    /// Create an error response for a device command that failed. If it only failed on
+    /// some devices the code is EXIT_PARTIAL_SUCCESS and `data.failed` lists their addresses.
+    pub fn command_failed(message: &str, error: &(dyn Error + 'static)) -> Self {
+        Self::from_error(message, error, EXIT_COMMAND_FAILED)
+    }
+
+    /// Attach the devices the command got through to, e.g. to a partial failure
+    pub fn with_devices(mut self, devices: Vec<DeviceInfo>) -> Self {
+        self.devices = devices.into_iter().map(ResponseDevice::from).collect();
+        self
+    }
+
+    /// Attach a command-specific payload to the response
+    pub fn with_data(mut self, data: serde_json::Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    /// Print an error response as JSON, or just its message to stderr
+    pub fn report(&self, json_output: bool) {
+        if json_output {
+            match serde_json::to_string(self) {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("Failed to serialize the response: {}", e),
+            }
+        } else {
+            eprintln!("{}", self.message);
+        }
+    }
+
+    /// Report an error response and exit with its code
+    pub fn print_and_exit(self, json_output: bool) -> ! {
+        self.report(json_output);
+        process::exit(self.error_code);
+    }
+}
+
+/// Exit code that goes with one of our errors
+fn exit_code_for(error: &LighthouseError) -> i32 {
+    match error {
+        LighthouseError::NoAdapter
+        | LighthouseError::AdapterNotFound(_)
+        | LighthouseError::AdapterNotLeCapable(_)
+        | LighthouseError::AdapterLost => EXIT_BLUETOOTH_ERROR,
+        LighthouseError::BluetoothOff => EXIT_BLUETOOTH_OFF,
+        LighthouseError::DevicesFailed { .. } if error.is_partial_failure() => EXIT_PARTIAL_SUCCESS,
+        LighthouseError::DevicesFailed { .. } | LighthouseError::CommandRejected { .. } => {
+            EXIT_COMMAND_FAILED
+        }
+        LighthouseError::SteamVrNotFound | LighthouseError::VrPathRegMissing(_) => {
+            EXIT_STEAMVR_ERROR
+        }
+    }
+}
+
+impl From<&LighthouseError> for CommandResponse {
+    fn from(error: &LighthouseError) -> Self {
+        let mut response = Self::error(&error.to_string(), exit_code_for(error));
+        response.reason = Some(error.reason().to_string());
+        if let LighthouseError::DevicesFailed { failed, .. } = error {
+            response = response.with_data(serde_json::json!({ "failed": failed }));
+        }
+        response
+    }
+}
+
+impl From<LighthouseError> for CommandResponse {
+    fn from(error: LighthouseError) -> Self {
+        Self::from(&error)
+    }
 }
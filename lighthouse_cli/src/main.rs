@@ -4,46 +4,183 @@
 // Base Stations via Bluetooth. It allows scanning for devices, turning them on,
 // putting them in standby mode, and can be called by external applications to toggle them.
 
-use lighthouse_core::btleplug::api::{Central, Manager as _, Peripheral as _, ScanFilter};
-use lighthouse_core::btleplug::platform::Manager;
+use lighthouse_core::btleplug::api::{Central, CentralState, Peripheral as _};
+use lighthouse_core::btleplug::platform::{Manager, Peripheral};
 use std::env;
 use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
 use std::process;
 use std::time::Duration;
-use tokio::time;
 
 mod cli;
+mod pipe;
+mod setup;
+mod tray;
 mod tui;
 
 use cli::{
-    error_log, log, print_help, CommandResponse, DEVICES_ARG, EXIT_BLUETOOTH_ERROR,
-    EXIT_COMMAND_FAILED, EXIT_GENERAL_ERROR, EXIT_NO_DEVICES_FOUND, EXIT_STEAMVR_ERROR, HELP_ARG,
-    JSON_OUTPUT_ARG, POWERON_ARG, REGISTER_STEAMVR_ARG, SCAN_ARG, STANDBY_ARG, STEAMVR_STARTED_ARG,
-    STEAMVR_STOPPED_ARG, TUI_ARG, UNREGISTER_STEAMVR_ARG,
+    error_log, explain_exit_code, get_arg_value, get_arg_values, log, print_help, CommandHooks,
+    CommandResponse, DeviceStatus, ADAPTER_ARG, ADAPTIVE_SCAN_ARG, ALLOW_ARG, BATCH_ARG,
+    BENCHMARK_ARG, BLINK_ARG, CHECK_ARG, CHECK_REACHABLE_ARG, CLEANUP_GRACE_SECS, CLEAR_FILTER_ARG,
+    CONFIG_GET_ARG, CONFIG_LIST_ARG, CONFIG_PATH_ARG, CONFIG_SET_ARG, CONFIRM_ACCEPTED_ARG,
+    CONNECT_ATTEMPTS_ARG, DEFAULT_BENCHMARK_ITERATIONS, DEFAULT_BLINK_COUNT,
+    DEFAULT_RECOVER_ATTEMPTS, DEFAULT_RECOVER_DELAY_SECS, DEFAULT_VERIFY_TIMEOUT_SECS,
+    DEFAULT_WAIT_FOR_STEAMVR_INTERVAL_SECS, DEFAULT_WAIT_FOR_STEAMVR_SECS, DENY_ARG, DEVICES_ARG,
+    DEVICE_ARG, DIFF_ARG, DIRECT_FALLBACK_ARG, DISCOVERY_RETRIES_ARG, EVENT_LOG_ARG,
+    EVENT_LOG_SUPPORTED, EXIT_BLUETOOTH_ERROR, EXIT_BLUETOOTH_OFF, EXIT_CODES, EXIT_COMMAND_FAILED,
+    EXIT_GENERAL_ERROR, EXIT_NO_DEVICES_FOUND, EXIT_STEAMVR_ERROR, EXIT_SUCCESS, EXIT_TIMEOUT,
+    EXPLAIN_EXIT_ARG, FAILED_ONLY_ARG, FIRST_RUN_ARG, FORCE_PROBE_ARG, HELP_ARG, HISTORY_ARG,
+    IDENTIFY_ARG, IMPORT_PLAYSPACES_ARG, INSPECT_ARG, INSPECT_KEEPALIVE_SECS, INTERVAL_ARG,
+    JSON_OUTPUT_ARG, JSON_STREAM_ARG, LANG_ARG, LAUNCH_STEAMVR_ARG, LIMIT_ARG, LIST_ADAPTERS_ARG,
+    LIST_COMMANDS_ARG, LOG_FILE_ARG, NO_SAVE_ARG, ONLY_CHANGED_ARG, ON_FAILURE_ARG, ON_SUCCESS_ARG,
+    OPERATION_ARG, POWERON_ARG, PRINT_CONFIG_PATH_ARG, PROBE_ARG, RECOVER_ARG,
+    RECOVER_ATTEMPTS_ARG, RECOVER_CACHE_ARG, RECOVER_DELAY_ARG, REGISTER_STEAMVR_ARG, REPEAT_ARG,
+    REPLACE_ARG, RESET_RELIABILITY_ARG, RESOLVE_CONFLICT_ARG, RESPECT_EXCLUSIONS_ARG,
+    SAVE_RAW_SCAN_ARG, SCAN_ARG, SCAN_PASSES_ARG, SCAN_TIMEOUT_ARG, SETUP_ARG, SET_ALIAS_ARG,
+    SET_CHANNEL_ARG, SET_DEFAULT_ADAPTER_ARG, SIMULATE_STEAMVR_STARTED_ARG,
+    SIMULATE_STEAMVR_STOPPED_ARG, SINCE_ARG, SKIP_WEAK_ARG, SLEEP_ARG, STANDBY_ARG, STATUS_ARG,
+    STDIN_ARG, STEAMVR_STARTED_ARG, STEAMVR_STOPPED_ARG, TIMEOUT_OVERALL_ARG, TRAY_ARG, TUI_ARG,
+    UNREGISTER_STEAMVR_ARG, VERIFY_ARG, VERIFY_TIMEOUT_ARG, WAIT_FOR_STEAMVR_ARG,
+    WAIT_FOR_STEAMVR_INTERVAL_ARG, WATCH_ARG, WEAK_THRESHOLD_ARG, YES_ARG,
 };
 use lighthouse_core::bluetooth::{
-    handle_device_command, peripheral_to_device_info, power_on_lighthouses_with_json,
-    scan_process_and_save, standby_lighthouses_with_json,
+    adapter_for_options, benchmark_lighthouses, blink_device_by_address, command_devices_with_opts,
+    describe_commands, detect_lighthouse, discover_peripherals, dump_gatt, find_command,
+    find_peripheral_by_address, finish_pending_cleanup, known_peripherals, list_adapters,
+    lock_adapter, peripheral_to_device_info, power_command, power_on_lighthouses_with_opts,
+    probe_lighthouses, process_scan_results_with_opts, read_all_states, read_properties,
+    recover_lighthouses, scan_process_and_save_with_opts, scan_with_report, select_adapter,
+    set_channel_by_address, standby_lighthouses_with_opts, stream_lighthouses, ConnectionGuard,
+    Controller, ScanOptions, DEFAULT_CONNECT_ATTEMPTS, DEFAULT_DISCOVERY_RETRIES,
+    DEFAULT_REPEAT_INTERVAL, IDENTIFY, MAX_CHANNEL, MIN_CHANNEL, POWER_ON, SLEEP, STANDBY,
 };
-use lighthouse_core::bluetooth::{POWERON_COMMAND, STANDBY_COMMAND};
-use lighthouse_core::config::{load_devices, load_devices_with_json};
+use lighthouse_core::config::{
+    find_device, get_device_cache_path, load_devices, load_devices_with_json, load_settings,
+    reset_reliability, save_devices_with_json, save_settings, set_config_path_override,
+};
+use lighthouse_core::error::LighthouseError;
+use lighthouse_core::history::{
+    devices_from_history, filter_history, load_history, parse_duration, HistoryFilter,
+};
+use lighthouse_core::logging::{get_log_path, init_file_logging};
+use lighthouse_core::messages::{set_language, Language};
+use lighthouse_core::models::{match_peripherals, normalize_address, DeviceDiff, DeviceInfo};
+use lighthouse_core::notifications::notify_power_result;
 use lighthouse_core::steamvr_integration;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
+    let json_output =
+        args.contains(&JSON_STREAM_ARG.to_string()) || args.contains(&JSON_OUTPUT_ARG.to_string());
+
+    let Some(overall_timeout) = parse_numeric_arg::<u64>(&args, TIMEOUT_OVERALL_ARG, json_output)?
+    else {
+        return run(args).await;
+    };
+
+    match tokio::time::timeout(Duration::from_secs(overall_timeout), run(args)).await {
+        Ok(result) => result,
+        Err(_) => {
+            // Dropping the command stopped its scans and closed its connections in the
+            // background, so give that a moment to finish before exiting
+            finish_pending_cleanup(Duration::from_secs(CLEANUP_GRACE_SECS)).await;
+
+            let error_msg = format!(
+                "Gave up after {}s: the command did not finish within {}",
+                overall_timeout, TIMEOUT_OVERALL_ARG
+            );
+            CommandResponse::error(&error_msg, EXIT_TIMEOUT).print_and_exit(json_output);
+        }
+    }
+}
+
+async fn run(args: Vec<String>) -> Result<(), Box<dyn Error>> {
     let standby_mode = args.contains(&STANDBY_ARG.to_string());
     let poweron_mode = args.contains(&POWERON_ARG.to_string());
+    let sleep_mode = args.contains(&SLEEP_ARG.to_string());
     let scan_only = args.contains(&SCAN_ARG.to_string());
     let devices_mode = args.contains(&DEVICES_ARG.to_string());
-    let json_output = args.contains(&JSON_OUTPUT_ARG.to_string());
+    let json_stream = args.contains(&JSON_STREAM_ARG.to_string());
+    // Streaming and --stdin print JSON lines, so nothing else may be written to stdout
+    let json_output = json_stream
+        || args.contains(&STDIN_ARG.to_string())
+        || args.contains(&JSON_OUTPUT_ARG.to_string());
     let help_requested = args.contains(&HELP_ARG.to_string());
     let tui_mode = args.contains(&TUI_ARG.to_string());
+    let setup_mode =
+        args.contains(&SETUP_ARG.to_string()) || args.contains(&FIRST_RUN_ARG.to_string());
+    let batch_mode = args.contains(&BATCH_ARG.to_string());
+    let list_adapters_mode = args.contains(&LIST_ADAPTERS_ARG.to_string());
+    let set_default_adapter = args.contains(&SET_DEFAULT_ADAPTER_ARG.to_string());
 
     let register_steamvr = args.contains(&REGISTER_STEAMVR_ARG.to_string());
     let unregister_steamvr = args.contains(&UNREGISTER_STEAMVR_ARG.to_string());
     let steamvr_started = args.contains(&STEAMVR_STARTED_ARG.to_string());
     let steamvr_stopped = args.contains(&STEAMVR_STOPPED_ARG.to_string());
+    let simulate_started = args.contains(&SIMULATE_STEAMVR_STARTED_ARG.to_string());
+    let simulate_stopped = args.contains(&SIMULATE_STEAMVR_STOPPED_ARG.to_string());
+
+    // Every config file lives next to the device config, so settle its path first
+    if let Some(path) = get_arg_value(&args, CONFIG_PATH_ARG) {
+        set_config_path_override(PathBuf::from(path));
+    }
+
+    // Pick the message language before anything is logged
+    if let Some(code) = get_arg_value(&args, LANG_ARG) {
+        match Language::from_code(code) {
+            Some(language) => set_language(language),
+            None => {
+                error_log(
+                    &format!(
+                        "Warning: unsupported language '{}', falling back to English",
+                        code
+                    ),
+                    json_output,
+                );
+                set_language(Language::English);
+            }
+        }
+    }
+
+    if args.contains(&LOG_FILE_ARG.to_string()) {
+        if let Err(e) = get_log_path().and_then(init_file_logging) {
+            error_log(
+                &format!("Warning: can't write to the log file: {}", e),
+                json_output,
+            );
+        }
+    }
+
+    // Printed before the banner so scripts can capture the path on its own
+    if args.contains(&PRINT_CONFIG_PATH_ARG.to_string()) {
+        return handle_print_config_path(json_output);
+    }
+
+    // Also printed without the banner, so the output can be piped
+    if args.contains(&HISTORY_ARG.to_string()) {
+        return handle_history_command(&args, json_output);
+    }
+    if args.contains(&RECOVER_CACHE_ARG.to_string()) {
+        let confirmed = args.contains(&YES_ARG.to_string());
+        return handle_recover_cache(confirmed, json_output);
+    }
+    if args.contains(&LIST_COMMANDS_ARG.to_string()) {
+        return handle_list_commands(json_output);
+    }
+    if args.contains(&EXPLAIN_EXIT_ARG.to_string()) {
+        return handle_explain_exit(&args, json_output);
+    }
+    if args.contains(&CONFIG_LIST_ARG.to_string())
+        || args.contains(&CONFIG_GET_ARG.to_string())
+        || args.contains(&CONFIG_SET_ARG.to_string())
+    {
+        return handle_config_command(&args, json_output);
+    }
+    if args.contains(&SET_ALIAS_ARG.to_string()) {
+        return handle_set_alias(&args, json_output);
+    }
 
     log("Starting lighthouse-rs...", json_output);
 
@@ -65,15 +202,207 @@ async fn main() -> Result<(), Box<dyn Error>> {
         return tui::run_tui().await;
     }
 
+    let scan_options = ScanOptions {
+        passes: parse_numeric_arg(&args, SCAN_PASSES_ARG, json_output)?
+            .unwrap_or(1)
+            .max(1),
+        no_save: args.contains(&NO_SAVE_ARG.to_string()),
+        replace_cache: args.contains(&REPLACE_ARG.to_string()),
+        adaptive: args.contains(&ADAPTIVE_SCAN_ARG.to_string()),
+        adapter: get_arg_value(&args, ADAPTER_ARG).map(String::from),
+        only_changed: args.contains(&ONLY_CHANGED_ARG.to_string()),
+        verify: if args.contains(&VERIFY_ARG.to_string())
+            || args.contains(&VERIFY_TIMEOUT_ARG.to_string())
+        {
+            let timeout: u64 = parse_numeric_arg(&args, VERIFY_TIMEOUT_ARG, json_output)?
+                .unwrap_or(DEFAULT_VERIFY_TIMEOUT_SECS);
+            Some(Duration::from_secs(timeout))
+        } else {
+            None
+        },
+        save_raw_scan: args.contains(&SAVE_RAW_SCAN_ARG.to_string()),
+        weak_signal_threshold: parse_numeric_arg(&args, WEAK_THRESHOLD_ARG, json_output)?,
+        skip_weak: args.contains(&SKIP_WEAK_ARG.to_string()),
+        direct_fallback: args.contains(&DIRECT_FALLBACK_ARG.to_string()),
+        discovery_retries: parse_numeric_arg(&args, DISCOVERY_RETRIES_ARG, json_output)?
+            .unwrap_or(DEFAULT_DISCOVERY_RETRIES),
+        connect_attempts: parse_numeric_arg(&args, CONNECT_ATTEMPTS_ARG, json_output)?
+            .unwrap_or(DEFAULT_CONNECT_ATTEMPTS)
+            .max(1),
+        respect_exclusions: args.contains(&RESPECT_EXCLUSIONS_ARG.to_string()),
+        confirm_accepted: args.contains(&CONFIRM_ACCEPTED_ARG.to_string()),
+        scan_duration: parse_numeric_arg(&args, SCAN_TIMEOUT_ARG, json_output)?
+            .map(Duration::from_secs),
+        repeat: parse_numeric_arg(&args, REPEAT_ARG, json_output)?
+            .unwrap_or(1)
+            .max(1),
+        repeat_interval: parse_numeric_arg(&args, INTERVAL_ARG, json_output)?
+            .map_or(DEFAULT_REPEAT_INTERVAL, Duration::from_secs),
+    };
+
+    // Like the TUI, the tray is interactive and runs until the user quits it
+    if args.contains(&TRAY_ARG.to_string()) {
+        if let Err(e) = tray::run_tray(scan_options).await {
+            eprintln!("{}", e);
+            process::exit(EXIT_GENERAL_ERROR);
+        }
+        return Ok(());
+    }
+
+    // The setup wizard is interactive, so like the TUI it doesn't support JSON output
+    if setup_mode {
+        if json_output {
+            log("--json is ignored in setup mode", false);
+        }
+        return setup::run_setup(&scan_options).await;
+    }
+
+    // Batch mode is the headless counterpart of the TUI and always prints JSON
+    if batch_mode {
+        return handle_batch_command(&scan_options).await;
+    }
+
+    // Like batch mode, commands read from stdin always get JSON responses
+    if args.contains(&STDIN_ARG.to_string()) {
+        return pipe::run_pipe(scan_options).await;
+    }
+
+    let check_reachable = args.contains(&CHECK_REACHABLE_ARG.to_string());
+    if check_reachable || args.contains(&CHECK_ARG.to_string()) {
+        return handle_check_command(&scan_options, check_reachable, json_output).await;
+    }
+
+    for (arg, filter) in [
+        (ALLOW_ARG, AddressFilter::Allow),
+        (DENY_ARG, AddressFilter::Deny),
+        (CLEAR_FILTER_ARG, AddressFilter::Clear),
+    ] {
+        if args.contains(&arg.to_string()) {
+            let address = get_arg_value(&args, arg).unwrap_or_default();
+            handle_address_filter(arg, filter, address, json_output)?;
+            return Ok(());
+        }
+    }
+
+    if args.contains(&PROBE_ARG.to_string()) {
+        let force = args.contains(&FORCE_PROBE_ARG.to_string());
+        handle_probe_command(&scan_options, force, json_output).await?;
+        return Ok(());
+    }
+
+    if args.contains(&IMPORT_PLAYSPACES_ARG.to_string()) {
+        handle_import_playspaces(&scan_options, json_output).await?;
+        return Ok(());
+    }
+
+    if args.contains(&RESET_RELIABILITY_ARG.to_string()) {
+        let address =
+            get_arg_value(&args, RESET_RELIABILITY_ARG).filter(|value| !value.starts_with("--"));
+        return handle_reset_reliability(address, json_output);
+    }
+
+    let blink_flag = [BLINK_ARG, IDENTIFY_ARG]
+        .into_iter()
+        .find(|flag| args.contains(&flag.to_string()));
+    if let Some(blink_flag) = blink_flag {
+        let address = get_arg_value(&args, blink_flag)
+            .filter(|value| !value.starts_with("--"))
+            .unwrap_or_default();
+        // The blink count is an optional second value: --blink <ADDR> [COUNT]
+        let count = args
+            .iter()
+            .position(|arg| arg == blink_flag)
+            .and_then(|i| args.get(i + 2))
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(DEFAULT_BLINK_COUNT);
+        handle_blink_command(blink_flag, address, count, &scan_options, json_output).await?;
+        return Ok(());
+    }
+
+    if args.contains(&SET_CHANNEL_ARG.to_string()) {
+        return handle_set_channel_command(&args, &scan_options, json_output).await;
+    }
+
+    if args.contains(&INSPECT_ARG.to_string()) {
+        let address = get_arg_value(&args, INSPECT_ARG)
+            .filter(|value| !value.starts_with("--"))
+            .unwrap_or_default();
+        return handle_inspect_command(address, &scan_options, json_output).await;
+    }
+
+    if args.contains(&BENCHMARK_ARG.to_string()) {
+        let iterations = match get_arg_value(&args, BENCHMARK_ARG).filter(|v| !v.starts_with("--"))
+        {
+            None => DEFAULT_BENCHMARK_ITERATIONS,
+            Some(value) => match value.parse::<u32>() {
+                Ok(iterations) if iterations > 0 => iterations,
+                _ => {
+                    let error_msg =
+                        format!("Invalid iteration count '{}' for {}", value, BENCHMARK_ARG);
+                    CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR)
+                        .print_and_exit(json_output);
+                }
+            },
+        };
+        return handle_benchmark_command(iterations, &scan_options, json_output).await;
+    }
+
+    if args.contains(&RECOVER_ARG.to_string()) {
+        let delay: u64 = parse_numeric_arg(&args, RECOVER_DELAY_ARG, json_output)?
+            .unwrap_or(DEFAULT_RECOVER_DELAY_SECS);
+        let attempts: u32 = parse_numeric_arg(&args, RECOVER_ATTEMPTS_ARG, json_output)?
+            .unwrap_or(DEFAULT_RECOVER_ATTEMPTS);
+        handle_recover_command(delay, attempts, &scan_options, json_output).await?;
+        return Ok(());
+    }
+
+    if list_adapters_mode {
+        handle_list_adapters(json_output).await?;
+        return Ok(());
+    }
+
+    if set_default_adapter {
+        let selector = get_arg_value(&args, SET_DEFAULT_ADAPTER_ARG).unwrap_or_default();
+        handle_set_default_adapter(selector, json_output).await?;
+        return Ok(());
+    }
+
+    if args.contains(&STATUS_ARG.to_string()) {
+        return handle_status_command(&scan_options, json_output).await;
+    }
+
     if devices_mode {
         log("Retrieving device information...", json_output);
-        handle_devices_command(json_output).await?;
+        handle_devices_command(&scan_options, json_output).await?;
         return Ok(());
     }
 
     if register_steamvr {
+        // Without a value --wait-for-steamvr waits for the default time
+        let wait = if args.contains(&WAIT_FOR_STEAMVR_ARG.to_string()) {
+            let timeout = match get_arg_value(&args, WAIT_FOR_STEAMVR_ARG)
+                .filter(|value| !value.starts_with("--"))
+            {
+                None => DEFAULT_WAIT_FOR_STEAMVR_SECS,
+                Some(_) => parse_numeric_arg(&args, WAIT_FOR_STEAMVR_ARG, json_output)?
+                    .unwrap_or(DEFAULT_WAIT_FOR_STEAMVR_SECS),
+            };
+            let interval: u64 =
+                parse_numeric_arg(&args, WAIT_FOR_STEAMVR_INTERVAL_ARG, json_output)?
+                    .unwrap_or(DEFAULT_WAIT_FOR_STEAMVR_INTERVAL_SECS);
+            if interval == 0 {
+                let error_msg = format!(
+                    "{} needs an interval of at least 1 second",
+                    WAIT_FOR_STEAMVR_INTERVAL_ARG
+                );
+                CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+            }
+            Some((Duration::from_secs(interval), Duration::from_secs(timeout)))
+        } else {
+            None
+        };
         log("Registering lighthouse-rs with SteamVR...", json_output);
-        handle_steamvr_registration(json_output).await?;
+        handle_steamvr_registration(wait, json_output).await?;
         return Ok(());
     }
 
@@ -83,12 +412,22 @@ async fn main() -> Result<(), Box<dyn Error>> {
         return Ok(());
     }
 
+    if simulate_started || simulate_stopped {
+        handle_simulated_steamvr_event(simulate_started, &scan_options, json_output).await?;
+        return Ok(());
+    }
+
+    if args.contains(&LAUNCH_STEAMVR_ARG.to_string()) {
+        handle_launch_steamvr(&scan_options, json_output).await?;
+        return Ok(());
+    }
+
     if steamvr_started {
         log(
             "SteamVR started event detected. Powering on lighthouses...",
             json_output,
         );
-        handle_steamvr_started(json_output).await?;
+        handle_steamvr_started(&scan_options, json_output).await?;
         return Ok(());
     }
 
@@ -97,46 +436,149 @@ async fn main() -> Result<(), Box<dyn Error>> {
             "SteamVR stopped event detected. Putting lighthouses in standby...",
             json_output,
         );
-        handle_steamvr_stopped(json_output).await?;
+        handle_steamvr_stopped(&scan_options, json_output).await?;
         return Ok(());
     }
 
+    if scan_only && json_stream {
+        return handle_scan_stream(&scan_options).await;
+    }
+
+    if scan_only && args.contains(&DIFF_ARG.to_string()) {
+        return handle_scan_diff(&scan_options, json_output).await;
+    }
+
     if scan_only {
         log(
             "Scan-only mode requested. Will scan for devices and save.",
             json_output,
         );
-        handle_scan_command(json_output).await?;
+        handle_scan_command(&scan_options, json_output).await?;
         return Ok(());
     }
 
+    // --resolve-conflict only picks between power on and standby
+    if sleep_mode && (standby_mode || poweron_mode) {
+        let error_msg = format!(
+            "{} can't be combined with {} or {}",
+            SLEEP_ARG, POWERON_ARG, STANDBY_ARG
+        );
+        CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+    }
+
+    // Both flags at once is an error unless --resolve-conflict says which one wins
+    let mut standby_mode = standby_mode;
+    let mut poweron_mode = poweron_mode;
     if standby_mode && poweron_mode {
+        let resolution = get_arg_value(&args, RESOLVE_CONFLICT_ARG).unwrap_or("error");
+        match resolution {
+            "poweron" => standby_mode = false,
+            "standby" => poweron_mode = false,
+            _ => {
+                let error_msg = if resolution == "error" {
+                    format!(
+                        "{} and {} are mutually exclusive. Pass {} poweron or standby to pick one.",
+                        STANDBY_ARG, POWERON_ARG, RESOLVE_CONFLICT_ARG
+                    )
+                } else {
+                    format!(
+                        "Invalid value for {}: '{}'. Expected poweron, standby or error.",
+                        RESOLVE_CONFLICT_ARG, resolution
+                    )
+                };
+                CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+            }
+        }
         log(
-            "Warning: Both --standby and --poweron flags were provided.",
+            &format!(
+                "Both {} and {} were given, sending {} as {} asks",
+                STANDBY_ARG, POWERON_ARG, resolution, RESOLVE_CONFLICT_ARG
+            ),
             json_output,
         );
-        log(
-            "These operations are mutually exclusive. Prioritizing power on command.",
-            json_output,
+    }
+
+    let watch_interval = parse_numeric_arg::<u64>(&args, WATCH_ARG, json_output)?;
+    if watch_interval.is_some_and(|interval| !poweron_mode || interval == 0) {
+        let error_msg = format!(
+            "{} needs {} and an interval of at least 1 second",
+            WATCH_ARG, POWERON_ARG
         );
+        CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
     }
 
-    let command_mode = if poweron_mode {
-        POWERON_COMMAND
+    let command = if poweron_mode {
+        Some(&POWER_ON)
     } else if standby_mode {
-        STANDBY_COMMAND
+        Some(&STANDBY)
+    } else if sleep_mode {
+        Some(&SLEEP)
     } else {
-        0xFF // No command
+        None
     };
 
-    if command_mode != 0xFF {
-        handle_device_command_mode(command_mode, json_output).await?;
+    if let Some(command) = command {
+        let hooks = CommandHooks {
+            operation: command.id,
+            on_success: get_arg_value(&args, ON_SUCCESS_ARG).map(String::from),
+            on_failure: get_arg_value(&args, ON_FAILURE_ARG).map(String::from),
+            event_log: args.contains(&EVENT_LOG_ARG.to_string()),
+        };
+        // Other platforms have no event log, so the flag is silently ignored there
+        if hooks.event_log && cfg!(windows) && !EVENT_LOG_SUPPORTED {
+            error_log(
+                "Warning: --event-log needs a build with the winlog feature, ignoring it",
+                json_output,
+            );
+        }
+        let targets = get_arg_values(&args, DEVICE_ARG);
+        if let Some(interval) = watch_interval {
+            return handle_watch_command(interval, &targets, &scan_options, &hooks, json_output)
+                .await;
+        }
+        let result = handle_device_command_mode(
+            command.byte(),
+            &targets,
+            &scan_options,
+            &hooks,
+            json_output,
+        )
+        .await;
+        match result {
+            Ok(devices) => hooks.succeeded(&devices, json_output),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                hooks.exit(EXIT_GENERAL_ERROR, json_output);
+            }
+        }
     }
 
     Ok(())
 }
 
-async fn handle_devices_command(json_output: bool) -> Result<(), Box<dyn Error>> {
+/// Parse the value following `name`, exiting with a usage error if it is missing or malformed
+fn parse_numeric_arg<T: std::str::FromStr>(
+    args: &[String],
+    name: &str,
+    json_output: bool,
+) -> Result<Option<T>, Box<dyn Error>> {
+    if !args.iter().any(|arg| arg == name) {
+        return Ok(None);
+    }
+
+    match get_arg_value(args, name).and_then(|value| value.parse::<T>().ok()) {
+        Some(value) => Ok(Some(value)),
+        None => {
+            let error_msg = format!("Invalid or missing value for {}", name);
+            CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+        }
+    }
+}
+
+async fn handle_devices_command(
+    scan_options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
     match load_devices_with_json(json_output) {
         Ok(devices) => {
             if !devices.is_empty() {
@@ -144,15 +586,27 @@ async fn handle_devices_command(json_output: bool) -> Result<(), Box<dyn Error>>
                     &format!("Found {} cached devices", devices.len()),
                     json_output,
                 );
+                let settings = load_settings().unwrap_or_default();
+                for device in &devices {
+                    let mut line = format!("  {}", settings.format_device(device));
+                    if let Some(channel) = device.channel {
+                        line.push_str(&format!("  ch {}", channel));
+                    }
+                    if let Some(rssi) = device.rssi {
+                        line.push_str(&format!("  {} dBm", rssi));
+                    }
+                    if let Some(reliability) = device.recorded_reliability() {
+                        line.push_str(&format!("  [{}]", reliability));
+                    }
+                    log(&line, json_output);
+                }
                 let response =
                     CommandResponse::success("Successfully retrieved device information", devices);
                 println!("{}", serde_json::to_string(&response)?);
-                return Ok(());
+                Ok(())
             } else {
                 log("No cached devices found. Performing a scan...", json_output);
-                match lighthouse_core::bluetooth::scan_process_and_save_with_json(0xFF, json_output)
-                    .await
-                {
+                match scan_process_and_save_with_opts(0xFF, scan_options, json_output).await {
                     Ok(_) => {
                         let devices = load_devices_with_json(json_output).unwrap_or_default();
                         log(
@@ -164,16 +618,17 @@ async fn handle_devices_command(json_output: bool) -> Result<(), Box<dyn Error>>
                             devices,
                         );
                         println!("{}", serde_json::to_string(&response)?);
-                        return Ok(());
+                        Ok(())
                     }
                     Err(e) => {
                         error_log(&format!("Failed to scan for devices: {}", e), json_output);
-                        let response = CommandResponse::error(
+                        let response = CommandResponse::from_error(
                             &format!("Failed to scan for devices: {}", e),
+                            e.as_ref(),
                             EXIT_BLUETOOTH_ERROR,
                         );
                         println!("{}", serde_json::to_string(&response)?);
-                        process::exit(EXIT_BLUETOOTH_ERROR);
+                        process::exit(response.error_code);
                     }
                 }
             }
@@ -190,94 +645,1459 @@ async fn handle_devices_command(json_output: bool) -> Result<(), Box<dyn Error>>
     }
 }
 
-async fn handle_steamvr_registration(json_output: bool) -> Result<(), Box<dyn Error>> {
-    match steamvr_integration::register_with_steamvr(false) {
-        Ok(_) => {
-            log("Successfully registered with SteamVR", json_output);
-            if json_output {
-                let response =
-                    CommandResponse::success("Successfully registered with SteamVR", Vec::new());
-                println!("{}", serde_json::to_string(&response)?);
-            }
-            Ok(())
-        }
+/// Read the power state of every cached device and print it. Devices that weren't
+/// found or couldn't be read are listed with the reason.
+async fn handle_status_command(
+    scan_options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let states = match read_all_states(scan_options, json_output).await {
+        Ok(states) => states,
         Err(e) => {
-            error_log(
-                &format!("Failed to register with SteamVR: {}", e),
-                json_output,
-            );
-            if json_output {
-                let response = CommandResponse::error(
-                    &format!("Failed to register with SteamVR: {}", e),
-                    EXIT_STEAMVR_ERROR,
-                );
-                println!("{}", serde_json::to_string(&response)?);
+            let error_msg = format!("Failed to read power states: {}", e);
+            CommandResponse::from_error(&error_msg, e.as_ref(), EXIT_BLUETOOTH_ERROR)
+                .print_and_exit(json_output);
+        }
+    };
+    if states.is_empty() {
+        CommandResponse::error(
+            "No cached devices found. Run --scan first.",
+            EXIT_NO_DEVICES_FOUND,
+        )
+        .print_and_exit(json_output);
+    }
+
+    let read = states.iter().filter(|(_, state)| state.is_ok()).count();
+    let message = format!(
+        "Read the power state of {} of {} devices",
+        read,
+        states.len()
+    );
+    if json_output {
+        let response = CommandResponse::with_states(&message, states);
+        println!("{}", serde_json::to_string(&response)?);
+    } else {
+        let settings = load_settings().unwrap_or_default();
+        for (device, state) in &states {
+            match state {
+                Ok(state) => println!("  {}: {}", settings.format_device(device), state),
+                Err(e) => println!("  {}: unavailable ({})", settings.format_device(device), e),
             }
-            process::exit(EXIT_STEAMVR_ERROR);
         }
+        log(&message, json_output);
+    }
+
+    if read == 0 {
+        process::exit(EXIT_NO_DEVICES_FOUND);
     }
+    Ok(())
 }
 
-async fn handle_steamvr_unregistration(json_output: bool) -> Result<(), Box<dyn Error>> {
-    match steamvr_integration::unregister_from_steamvr() {
-        Ok(_) => {
-            log("Successfully unregistered from SteamVR", json_output);
+fn handle_print_config_path(json_output: bool) -> Result<(), Box<dyn Error>> {
+    match get_device_cache_path(load_settings().unwrap_or_default().cache_format) {
+        Ok(path) => {
             if json_output {
-                let response =
-                    CommandResponse::success("Successfully unregistered from SteamVR", Vec::new());
+                let response = CommandResponse::success(&path.display().to_string(), Vec::new())
+                    .with_data(serde_json::json!({ "config_path": path }));
                 println!("{}", serde_json::to_string(&response)?);
+            } else {
+                println!("{}", path.display());
             }
             Ok(())
         }
         Err(e) => {
-            error_log(
-                &format!("Failed to unregister from SteamVR: {}", e),
+            let error_msg = format!("Failed to resolve the config path: {}", e);
+            CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+        }
+    }
+}
+
+/// Print the power command history, filtered by --since, --operation,
+/// --failed-only and --limit. An empty history prints nothing.
+fn handle_history_command(args: &[String], json_output: bool) -> Result<(), Box<dyn Error>> {
+    let invalid = |name: &str| -> Result<(), Box<dyn Error>> {
+        let error_msg = format!("Invalid or missing value for {}", name);
+        CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+    };
+
+    let mut filter = HistoryFilter {
+        failed_only: args.contains(&FAILED_ONLY_ARG.to_string()),
+        limit: parse_numeric_arg(args, LIMIT_ARG, json_output)?,
+        ..Default::default()
+    };
+    if args.contains(&SINCE_ARG.to_string()) {
+        match get_arg_value(args, SINCE_ARG).and_then(parse_duration) {
+            Some(since) => filter.since = Some(since),
+            None => invalid(SINCE_ARG)?,
+        }
+    }
+    if args.contains(&OPERATION_ARG.to_string()) {
+        match get_arg_value(args, OPERATION_ARG)
+            .and_then(find_command)
+            .filter(|command| command.changes_power())
+        {
+            Some(command) => filter.operation = Some(command.id.to_string()),
+            None => invalid(OPERATION_ARG)?,
+        }
+    }
+
+    let entries = filter_history(load_history()?, &filter);
+
+    if json_output {
+        let response = CommandResponse::success("history", Vec::new())
+            .with_data(serde_json::json!({ "entries": entries }));
+        println!("{}", serde_json::to_string(&response)?);
+        return Ok(());
+    }
+
+    for entry in &entries {
+        let outcome = if entry.success {
+            let addresses: Vec<&str> = entry.devices.iter().map(|d| d.address.as_str()).collect();
+            format!("ok      {}", addresses.join(", "))
+        } else {
+            format!("failed  exit code {}", entry.exit_code)
+        };
+        println!(
+            "{:>4} ago  {:<8} {}",
+            format_age(entry.timestamp_ms),
+            entry.operation,
+            outcome
+        );
+    }
+    Ok(())
+}
+
+/// Rebuild the device cache from the most recent record of each device in the history.
+/// An existing cache is only replaced after confirmation, or with --yes.
+fn handle_recover_cache(confirmed: bool, json_output: bool) -> Result<(), Box<dyn Error>> {
+    let devices = devices_from_history(&load_history()?);
+    if devices.is_empty() {
+        CommandResponse::error(
+            "The history has no devices to recover",
+            EXIT_NO_DEVICES_FOUND,
+        )
+        .print_and_exit(json_output);
+    }
+
+    let cached = load_devices_with_json(true).unwrap_or_default();
+    if !cached.is_empty() && !confirmed {
+        // Scripts can't answer the prompt, so they have to ask for the overwrite
+        if json_output || !std::io::stdin().is_terminal() {
+            let error_msg = format!(
+                "The device cache already has {} devices, pass {} to replace it",
+                cached.len(),
+                YES_ARG
+            );
+            CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+        }
+
+        print!(
+            "Replace the {} cached devices with the {} found in the history? (y/N) ",
+            cached.len(),
+            devices.len()
+        );
+        std::io::stdout().flush()?;
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if !input.trim().eq_ignore_ascii_case("y") {
+            log("Keeping the existing device cache.", json_output);
+            return Ok(());
+        }
+    }
+
+    if let Err(e) = save_devices_with_json(&devices, json_output) {
+        let error_msg = format!("Failed to save the recovered devices: {}", e);
+        CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+    }
+
+    let message = format!("Recovered {} devices from the history", devices.len());
+    if json_output {
+        let response = CommandResponse::success(&message, load_devices_with_json(true)?);
+        println!("{}", serde_json::to_string(&response)?);
+    } else {
+        log(&message, json_output);
+        for device in &devices {
+            log(
+                &format!("  {} ({})", device.name, device.address),
                 json_output,
             );
-            if json_output {
-                let response = CommandResponse::error(
-                    &format!("Failed to unregister from SteamVR: {}", e),
-                    EXIT_STEAMVR_ERROR,
-                );
-                println!("{}", serde_json::to_string(&response)?);
+        }
+    }
+    Ok(())
+}
+
+/// Print the name and meaning of one exit code, or of all of them without an argument
+fn handle_explain_exit(args: &[String], json_output: bool) -> Result<(), Box<dyn Error>> {
+    let codes = match get_arg_value(args, EXPLAIN_EXIT_ARG).filter(|value| !value.starts_with("--"))
+    {
+        None => EXIT_CODES.to_vec(),
+        Some(value) => match value.parse().ok().and_then(explain_exit_code) {
+            Some(info) => vec![*info],
+            None => {
+                let error_msg = format!("Unknown exit code '{}'", value);
+                CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+            }
+        },
+    };
+
+    if json_output {
+        let response = CommandResponse::success("exit codes", Vec::new())
+            .with_data(serde_json::json!({ "exit_codes": codes }));
+        println!("{}", serde_json::to_string(&response)?);
+        return Ok(());
+    }
+
+    for info in &codes {
+        println!("{:<2} {:<22} {}", info.code, info.name, info.description);
+    }
+    Ok(())
+}
+
+/// Read or change settings by key: --config-list, --config-get <KEY>, --config-set <KEY> <VALUE>
+fn handle_config_command(args: &[String], json_output: bool) -> Result<(), Box<dyn Error>> {
+    let fail = |error_msg: String| -> Result<(), Box<dyn Error>> {
+        CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+    };
+    // Strings print bare so `--config-get` output can be used directly in scripts
+    let format_value = |value: &serde_json::Value| match value {
+        serde_json::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    };
+
+    let mut settings = match load_settings() {
+        Ok(settings) => settings,
+        Err(e) => return fail(format!("Failed to load settings: {}", e)),
+    };
+
+    if args.contains(&CONFIG_LIST_ARG.to_string()) {
+        let Some(serde_json::Value::Object(values)) = serde_json::to_value(&settings).ok() else {
+            return fail("Failed to read settings".to_string());
+        };
+        if json_output {
+            let response = CommandResponse::success("settings", Vec::new())
+                .with_data(serde_json::json!({ "settings": values }));
+            println!("{}", serde_json::to_string(&response)?);
+        } else {
+            for (key, value) in &values {
+                println!("{} = {}", key, format_value(value));
             }
-            process::exit(EXIT_STEAMVR_ERROR);
         }
+        return Ok(());
+    }
+
+    if args.contains(&CONFIG_GET_ARG.to_string()) {
+        let key = get_arg_value(args, CONFIG_GET_ARG).unwrap_or_default();
+        let Some(value) = settings.get(key) else {
+            return fail(format!("Unknown setting '{}'", key));
+        };
+        if json_output {
+            let response = CommandResponse::success(key, Vec::new())
+                .with_data(serde_json::json!({ "key": key, "value": value }));
+            println!("{}", serde_json::to_string(&response)?);
+        } else {
+            println!("{}", format_value(&value));
+        }
+        return Ok(());
+    }
+
+    // The value is the second argument after the flag: --config-set <KEY> <VALUE>
+    let position = args.iter().position(|arg| arg == CONFIG_SET_ARG);
+    let key = position.and_then(|i| args.get(i + 1));
+    let value = position.and_then(|i| args.get(i + 2));
+    let (Some(key), Some(value)) = (key, value) else {
+        return fail(format!("Usage: {} <KEY> <VALUE>", CONFIG_SET_ARG));
+    };
+    if let Err(e) = settings.set(key, value) {
+        return fail(e.to_string());
+    }
+    if let Err(e) = save_settings(&settings) {
+        return fail(format!("Failed to save settings: {}", e));
+    }
+
+    let value = settings.get(key).unwrap_or_default();
+    let message = format!("Set {} to {}", key, format_value(&value));
+    if json_output {
+        let response = CommandResponse::success(&message, Vec::new())
+            .with_data(serde_json::json!({ "key": key, "value": value }));
+        println!("{}", serde_json::to_string(&response)?);
+    } else {
+        println!("{}", message);
     }
+    Ok(())
 }
 
-async fn handle_steamvr_started(json_output: bool) -> Result<(), Box<dyn Error>> {
-    match power_on_lighthouses_with_json(json_output).await {
-        Ok(_) => {
-            if json_output {
-                let devices = load_devices_with_json(json_output).unwrap_or_default();
-                let response =
-                    CommandResponse::success("Successfully powered on lighthouses", devices);
+/// Print the operations a base station supports, with the flag that sends each one
+fn handle_list_commands(json_output: bool) -> Result<(), Box<dyn Error>> {
+    let commands = describe_commands();
+
+    if json_output {
+        let response = CommandResponse::success("commands", Vec::new())
+            .with_data(serde_json::json!({ "commands": commands }));
+        println!("{}", serde_json::to_string(&response)?);
+        return Ok(());
+    }
+
+    for command in &commands {
+        let verifiable = if command.verifiable {
+            "verifiable"
+        } else {
+            "not verifiable"
+        };
+        println!(
+            "{:<10} {:<11} {:<15} {}",
+            command.id,
+            command_flag(command.id),
+            verifiable,
+            command.description
+        );
+    }
+    Ok(())
+}
+
+/// The flag that sends a device command from the command line
+fn command_flag(id: &str) -> &'static str {
+    [
+        (POWER_ON.id, POWERON_ARG),
+        (STANDBY.id, STANDBY_ARG),
+        (SLEEP.id, SLEEP_ARG),
+        (IDENTIFY.id, BLINK_ARG),
+    ]
+    .iter()
+    .find(|(command, _)| *command == id)
+    .map_or("", |(_, flag)| *flag)
+}
+
+/// How long ago a timestamp was, in its largest whole unit, e.g. `12m`
+fn format_age(timestamp_ms: u64) -> String {
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    let secs = now_ms.saturating_sub(timestamp_ms) / 1000;
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+/// Set or clear the nickname of a cached device: --set-alias <ADDRESS> <NAME>.
+/// An empty name or `none` removes the nickname.
+fn handle_set_alias(args: &[String], json_output: bool) -> Result<(), Box<dyn Error>> {
+    let fail = |error_msg: String, exit_code: i32| -> Result<(), Box<dyn Error>> {
+        CommandResponse::error(&error_msg, exit_code).print_and_exit(json_output);
+    };
+
+    let position = args.iter().position(|arg| arg == SET_ALIAS_ARG);
+    let address = position.and_then(|i| args.get(i + 1));
+    let alias = position.and_then(|i| args.get(i + 2));
+    let (Some(address), Some(alias)) = (address, alias) else {
+        return fail(
+            format!("Usage: {} <ADDRESS> <NAME>", SET_ALIAS_ARG),
+            EXIT_GENERAL_ERROR,
+        );
+    };
+
+    let devices = match load_devices_with_json(json_output) {
+        Ok(devices) => devices,
+        Err(e) => {
+            return fail(
+                format!("Failed to load device cache: {}", e),
+                EXIT_GENERAL_ERROR,
+            )
+        }
+    };
+    let Some(device) = devices
+        .iter()
+        .find(|device| device.matches_address(address))
+    else {
+        return fail(
+            format!(
+                "No cached device has the address {}. Run --scan to find it first.",
+                address
+            ),
+            EXIT_NO_DEVICES_FOUND,
+        );
+    };
+
+    let alias = alias.trim();
+    let cleared = alias.is_empty() || alias.eq_ignore_ascii_case("none");
+    let address = normalize_address(&device.address);
+    let result = load_settings().and_then(|mut settings| {
+        if cleared {
+            settings.aliases.remove(&address);
+        } else {
+            settings.aliases.insert(address.clone(), alias.to_string());
+        }
+        save_settings(&settings)
+    });
+    if let Err(e) = result {
+        return fail(
+            format!("Failed to save the nickname: {}", e),
+            EXIT_GENERAL_ERROR,
+        );
+    }
+
+    let message = if cleared {
+        format!("Removed the nickname of {} ({})", device.name, address)
+    } else {
+        format!("{} ({}) is now called {}", device.name, address, alias)
+    };
+    if json_output {
+        let alias = Some(alias).filter(|_| !cleared);
+        let response = CommandResponse::success(&message, vec![device.clone()])
+            .with_data(serde_json::json!({ "address": address, "alias": alias }));
+        println!("{}", serde_json::to_string(&response)?);
+    } else {
+        println!("{}", message);
+    }
+    Ok(())
+}
+
+/// How --allow, --deny and --clear-filter change the address lists
+#[derive(Clone, Copy, PartialEq)]
+enum AddressFilter {
+    Allow,
+    Deny,
+    Clear,
+}
+
+fn handle_address_filter(
+    arg: &str,
+    filter: AddressFilter,
+    address: &str,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    if address.is_empty() {
+        let error_msg = format!("Missing device address for {}", arg);
+        CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+    }
+
+    let address = normalize_address(address);
+    let result = load_settings().and_then(|mut settings| {
+        // An address lives on at most one list, so moving it always clears the other
+        settings
+            .allowlist
+            .retain(|a| normalize_address(a) != address);
+        settings
+            .denylist
+            .retain(|a| normalize_address(a) != address);
+        match filter {
+            AddressFilter::Allow => settings.allowlist.push(address.clone()),
+            AddressFilter::Deny => settings.denylist.push(address.clone()),
+            AddressFilter::Clear => {}
+        }
+        save_settings(&settings)?;
+
+        // Drop denied devices from the cache so they aren't commanded from it either
+        if filter == AddressFilter::Deny {
+            let mut devices = load_devices_with_json(json_output)?;
+            let count = devices.len();
+            devices.retain(|device| !device.matches_address(&address));
+            if devices.len() != count {
+                save_devices_with_json(&devices, json_output)?;
+            }
+        }
+        Ok(settings)
+    });
+
+    let settings = match result {
+        Ok(settings) => settings,
+        Err(e) => {
+            let error_msg = format!("Failed to update address filters: {}", e);
+            CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+        }
+    };
+
+    let message = match filter {
+        AddressFilter::Allow => format!("Added {} to the allowlist", address),
+        AddressFilter::Deny => format!("Added {} to the denylist", address),
+        AddressFilter::Clear => format!("Removed {} from the allowlist and denylist", address),
+    };
+
+    if json_output {
+        let response =
+            CommandResponse::success(&message, Vec::new()).with_data(serde_json::json!({
+                "allowlist": settings.allowlist,
+                "denylist": settings.denylist,
+            }));
+        println!("{}", serde_json::to_string(&response)?);
+    } else {
+        log(&message, json_output);
+        log(&format!("Allowlist: {:?}", settings.allowlist), json_output);
+        log(&format!("Denylist: {:?}", settings.denylist), json_output);
+    }
+    Ok(())
+}
+
+async fn handle_probe_command(
+    scan_options: &ScanOptions,
+    force: bool,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    match probe_lighthouses(scan_options, force, json_output).await {
+        Ok(devices) if devices.is_empty() => {
+            CommandResponse::error("No Lighthouse devices found", EXIT_NO_DEVICES_FOUND)
+                .print_and_exit(json_output);
+        }
+        Ok(devices) => {
+            if json_output {
+                let response = CommandResponse::success(
+                    &format!("Probed {} Lighthouse devices", devices.len()),
+                    devices,
+                );
+                println!("{}", serde_json::to_string(&response)?);
+            } else {
+                for device in devices.iter() {
+                    let capabilities = device.capabilities.unwrap_or_default();
+                    let supported: Vec<&str> = [
+                        ("power", capabilities.power),
+                        ("power state", capabilities.power_state),
+                        ("channel", capabilities.channel),
+                        ("identify", capabilities.identify),
+                        ("serial number", capabilities.serial_number),
+                    ]
+                    .iter()
+                    .filter(|(_, supported)| *supported)
+                    .map(|(name, _)| *name)
+                    .collect();
+                    log(
+                        &format!(
+                            "{} ({}): {}",
+                            device.name,
+                            device.address,
+                            if supported.is_empty() {
+                                "no known operations".to_string()
+                            } else {
+                                supported.join(", ")
+                            }
+                        ),
+                        json_output,
+                    );
+                }
+            }
+            Ok(())
+        }
+        Err(e) => {
+            let error_msg = format!("Failed to probe devices: {}", e);
+            CommandResponse::error(&error_msg, EXIT_BLUETOOTH_ERROR).print_and_exit(json_output);
+        }
+    }
+}
+
+async fn handle_benchmark_command(
+    iterations: u32,
+    scan_options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let report = match benchmark_lighthouses(iterations, scan_options, json_output).await {
+        Ok(report) => report,
+        Err(e) => {
+            let error_msg = format!("Benchmark failed: {}", e);
+            CommandResponse::from_error(&error_msg, e.as_ref(), EXIT_BLUETOOTH_ERROR)
+                .print_and_exit(json_output);
+        }
+    };
+    if report.devices.is_empty() {
+        CommandResponse::error("No Lighthouse devices found", EXIT_NO_DEVICES_FOUND)
+            .print_and_exit(json_output);
+    }
+
+    let message = format!(
+        "Benchmarked {} devices over {} iterations",
+        report.devices.len(),
+        report.iterations
+    );
+    if json_output {
+        let devices = report.devices.iter().map(|d| d.device.clone()).collect();
+        let response =
+            CommandResponse::success(&message, devices).with_data(serde_json::json!(report));
+        println!("{}", serde_json::to_string(&response)?);
+        return Ok(());
+    }
+
+    println!("{}", message);
+    println!();
+    println!(
+        "{:<22} {:>8} {:>8} {:>8} {:>8}",
+        "ms", "min", "median", "mean", "max"
+    );
+    let rows = [
+        ("scan".to_string(), report.scan),
+        ("connect".to_string(), report.connect),
+        ("command".to_string(), report.command),
+        ("end-to-end".to_string(), report.end_to_end),
+    ];
+    let device_rows = report.devices.iter().flat_map(|device| {
+        [
+            (format!("{} connect", device.device.name), device.connect),
+            (format!("{} command", device.device.name), device.command),
+        ]
+    });
+    for (label, stats) in rows.into_iter().chain(device_rows) {
+        match stats {
+            Some(stats) => println!(
+                "{:<22} {:>8} {:>8} {:>8} {:>8}",
+                label, stats.min_ms, stats.median_ms, stats.mean_ms, stats.max_ms
+            ),
+            None => println!("{:<22} {:>8}", label, "-"),
+        }
+    }
+    for device in report.devices.iter().filter(|device| device.failures > 0) {
+        println!();
+        println!(
+            "{} failed {} of {} times: {}",
+            device.device.name,
+            device.failures,
+            report.iterations,
+            device.last_error.as_deref().unwrap_or("unknown error")
+        );
+    }
+    Ok(())
+}
+
+async fn handle_recover_command(
+    delay_secs: u64,
+    attempts: u32,
+    scan_options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let report = match recover_lighthouses(
+        scan_options,
+        Duration::from_secs(delay_secs),
+        attempts,
+        json_output,
+    )
+    .await
+    {
+        Ok(report) => report,
+        Err(e) => {
+            let error_msg = format!("Recovery failed: {}", e);
+            CommandResponse::error(&error_msg, EXIT_COMMAND_FAILED).print_and_exit(json_output);
+        }
+    };
+
+    let (message, exit_code) = if report.recovered.is_empty() && report.failed.is_empty() {
+        (
+            "No Lighthouse devices found".to_string(),
+            EXIT_NO_DEVICES_FOUND,
+        )
+    } else if report.failed.is_empty() {
+        (
+            format!(
+                "Recovered {} devices after {} power on attempts",
+                report.recovered.len(),
+                report.attempts
+            ),
+            EXIT_SUCCESS,
+        )
+    } else {
+        (
+            format!(
+                "Recovered {} devices, {} did not report being on after {} attempts",
+                report.recovered.len(),
+                report.failed.len(),
+                report.attempts
+            ),
+            EXIT_COMMAND_FAILED,
+        )
+    };
+
+    if json_output {
+        let response = if exit_code == EXIT_SUCCESS {
+            CommandResponse::success(&message, report.recovered.clone())
+        } else {
+            CommandResponse::error(&message, exit_code)
+        };
+        println!(
+            "{}",
+            serde_json::to_string(&response.with_data(serde_json::json!(report)))?
+        );
+    } else {
+        log(&message, json_output);
+        for device in &report.failed {
+            log(
+                &format!("  Not on: {} ({})", device.name, device.address),
+                json_output,
+            );
+        }
+    }
+
+    if exit_code != EXIT_SUCCESS {
+        process::exit(exit_code);
+    }
+    Ok(())
+}
+
+fn handle_reset_reliability(
+    address: Option<&str>,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    match reset_reliability(address) {
+        Ok(0) => {
+            let error_msg = match address {
+                Some(address) => format!("No cached device with address {}", address),
+                None => "No cached devices to reset".to_string(),
+            };
+            CommandResponse::error(&error_msg, EXIT_NO_DEVICES_FOUND).print_and_exit(json_output);
+        }
+        Ok(count) => {
+            let message = format!("Reset the recorded command results of {} devices", count);
+            if json_output {
+                let response = CommandResponse::success(&message, Vec::new());
+                println!("{}", serde_json::to_string(&response)?);
+            } else {
+                log(&message, json_output);
+            }
+            Ok(())
+        }
+        Err(e) => {
+            let error_msg = format!("Failed to reset the recorded command results: {}", e);
+            CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+        }
+    }
+}
+
+async fn handle_blink_command(
+    flag: &str,
+    address: &str,
+    count: u32,
+    scan_options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    if address.is_empty() {
+        let error_msg = format!("Missing device address for {}", flag);
+        CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+    }
+
+    match blink_device_by_address(address, count, scan_options, json_output).await {
+        Ok(device) => {
+            let message = format!(
+                "Blinked {} ({}) {} times",
+                device.name, device.address, count
+            );
+            if json_output {
+                let response = CommandResponse::success(&message, vec![device]);
+                println!("{}", serde_json::to_string(&response)?);
+            } else {
+                log(&message, json_output);
+            }
+            Ok(())
+        }
+        Err(e) => {
+            let error_msg = format!("Failed to blink {}: {}", address, e);
+            CommandResponse::error(&error_msg, EXIT_COMMAND_FAILED).print_and_exit(json_output);
+        }
+    }
+}
+
+/// Switch one base station to another channel, confirming it by reading it back
+async fn handle_set_channel_command(
+    args: &[String],
+    scan_options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let position = args.iter().position(|arg| arg == SET_CHANNEL_ARG);
+    let address = position.and_then(|i| args.get(i + 1));
+    let channel = position.and_then(|i| args.get(i + 2));
+    let (Some(address), Some(channel)) = (address, channel) else {
+        let error_msg = format!("Usage: {} <ADDRESS> <CHANNEL>", SET_CHANNEL_ARG);
+        CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+    };
+    // Checked here as well as in set_channel so a bad value fails before scanning
+    let channel = match channel.parse::<u8>() {
+        Ok(channel) if (MIN_CHANNEL..=MAX_CHANNEL).contains(&channel) => channel,
+        _ => {
+            let error_msg = format!(
+                "Invalid channel '{}'. It must be a number between {} and {}.",
+                channel, MIN_CHANNEL, MAX_CHANNEL
+            );
+            CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+        }
+    };
+
+    match set_channel_by_address(address, channel, scan_options, json_output).await {
+        Ok(device) => {
+            let message = format!(
+                "{} ({}) is now on channel {}",
+                device.name, device.address, channel
+            );
+            if json_output {
+                let response = CommandResponse::success(&message, vec![device]);
+                println!("{}", serde_json::to_string(&response)?);
+            } else {
+                log(&message, json_output);
+            }
+            Ok(())
+        }
+        Err(e) => {
+            let error_msg = format!("Failed to set the channel of {}: {}", address, e);
+            CommandResponse::from_error(&error_msg, e.as_ref(), EXIT_COMMAND_FAILED)
+                .print_and_exit(json_output);
+        }
+    }
+}
+
+/// Connect to one device, print its GATT tree and keep the connection open until Ctrl-C
+async fn handle_inspect_command(
+    address: &str,
+    scan_options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let fail = |error_msg: String, exit_code: i32| -> Result<(), Box<dyn Error>> {
+        CommandResponse::error(&error_msg, exit_code).print_and_exit(json_output);
+    };
+
+    if address.is_empty() {
+        return fail(
+            format!("Missing device address for {}", INSPECT_ARG),
+            EXIT_GENERAL_ERROR,
+        );
+    }
+
+    let _adapter_guard = lock_adapter().await;
+    let manager = match Manager::new().await {
+        Ok(manager) => manager,
+        Err(e) => {
+            return fail(
+                format!("Failed to initialize Bluetooth manager: {}", e),
+                EXIT_BLUETOOTH_ERROR,
+            )
+        }
+    };
+    let adapter = match adapter_for_options(&manager, scan_options, json_output).await {
+        Ok(adapter) => adapter,
+        Err(e) => return fail(e.to_string(), EXIT_BLUETOOTH_ERROR),
+    };
+    let peripheral =
+        match find_peripheral_by_address(&adapter, address, scan_options, json_output).await {
+            Ok(peripheral) => peripheral,
+            Err(e) => return fail(e.to_string(), EXIT_NO_DEVICES_FOUND),
+        };
+
+    log(&format!("Connecting to {}...", address), json_output);
+    let connection = match ConnectionGuard::exclusive(&peripheral).await {
+        Ok(connection) => connection,
+        Err(e) => {
+            return fail(
+                format!("Failed to connect to {}: {}", address, e),
+                EXIT_COMMAND_FAILED,
+            )
+        }
+    };
+    let services = match dump_gatt(&peripheral).await {
+        Ok(services) => services,
+        Err(e) => {
+            return fail(
+                format!("Failed to discover services on {}: {}", address, e),
+                EXIT_COMMAND_FAILED,
+            )
+        }
+    };
+
+    if json_output {
+        let message = format!("Found {} services on {}", services.len(), address);
+        let response =
+            CommandResponse::success(&message, Vec::new()).with_data(serde_json::json!({
+                "address": address,
+                "services": services,
+            }));
+        println!("{}", serde_json::to_string(&response)?);
+    } else {
+        for service in &services {
+            let kind = if service.primary {
+                "primary"
+            } else {
+                "secondary"
+            };
+            println!("Service {} ({})", service.uuid, kind);
+            for characteristic in &service.characteristics {
+                println!(
+                    "  Characteristic {} [{}]",
+                    characteristic.uuid,
+                    characteristic.properties.join(", ")
+                );
+                match (&characteristic.value, &characteristic.text) {
+                    (Some(value), Some(text)) => println!("    Value: {} \"{}\"", value, text),
+                    (Some(value), None) => println!("    Value: {}", value),
+                    _ => {}
+                }
+                if let Some(e) = &characteristic.read_error {
+                    println!("    Read failed: {}", e);
+                }
+                for descriptor in &characteristic.descriptors {
+                    println!("    Descriptor {}", descriptor);
+                }
+            }
+        }
+    }
+
+    log(
+        "Keeping the connection open, press Ctrl-C to disconnect",
+        json_output,
+    );
+    let mut keepalive = tokio::time::interval(Duration::from_secs(INSPECT_KEEPALIVE_SECS));
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = keepalive.tick() => {
+                if !peripheral.is_connected().await.unwrap_or(false) {
+                    return fail(format!("{} disconnected", address), EXIT_COMMAND_FAILED);
+                }
+            }
+        }
+    }
+
+    connection.disconnect().await.ok();
+    log(&format!("Disconnected from {}", address), json_output);
+    Ok(())
+}
+
+async fn handle_list_adapters(json_output: bool) -> Result<(), Box<dyn Error>> {
+    let adapters = match Manager::new().await {
+        Ok(manager) => list_adapters(&manager).await,
+        Err(e) => Err(e.into()),
+    };
+
+    match adapters {
+        Ok(adapters) => {
+            let default_adapter = load_settings().ok().and_then(|s| s.default_adapter);
+            if json_output {
+                let response = CommandResponse::success(
+                    &format!("Found {} Bluetooth adapters", adapters.len()),
+                    Vec::new(),
+                )
+                .with_data(serde_json::json!({
+                    "adapters": adapters,
+                    "default_adapter": default_adapter,
+                }));
+                println!("{}", serde_json::to_string(&response)?);
+            } else if adapters.is_empty() {
+                log("No Bluetooth adapters found", json_output);
+            } else {
+                for adapter in adapters.iter() {
+                    let marker = if default_adapter.as_deref() == Some(adapter.name.as_str()) {
+                        " (default)"
+                    } else {
+                        ""
+                    };
+                    log(
+                        &format!("{}: {}{}", adapter.index, adapter.name, marker),
+                        json_output,
+                    );
+                }
+            }
+            Ok(())
+        }
+        Err(e) => {
+            let error_msg = format!("Failed to list Bluetooth adapters: {}", e);
+            CommandResponse::error(&error_msg, EXIT_BLUETOOTH_ERROR).print_and_exit(json_output);
+        }
+    }
+}
+
+async fn handle_set_default_adapter(
+    selector: &str,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    if selector.is_empty() {
+        let error_msg = format!(
+            "Missing adapter index or name for {}",
+            SET_DEFAULT_ADAPTER_ARG
+        );
+        CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+    }
+
+    // Resolve the selector now and store the adapter's name, so the preference
+    // survives adapters being re-ordered between runs
+    let name = match Manager::new().await {
+        Ok(manager) => match select_adapter(&manager, selector).await {
+            Ok(adapter) => adapter.adapter_info().await.map_err(|e| e.into()),
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e.into()),
+    };
+
+    let name = match name {
+        Ok(name) => name,
+        Err(e) => {
+            let error_msg = format!("Failed to select adapter: {}", e);
+            CommandResponse::error(&error_msg, EXIT_BLUETOOTH_ERROR).print_and_exit(json_output);
+        }
+    };
+
+    let mut settings = load_settings().unwrap_or_default();
+    settings.default_adapter = Some(name.clone());
+    if let Err(e) = save_settings(&settings) {
+        let error_msg = format!("Failed to save settings: {}", e);
+        CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+    }
+
+    log(&format!("Default adapter set to: {}", name), json_output);
+    if json_output {
+        let response =
+            CommandResponse::success(&format!("Default adapter set to: {}", name), Vec::new())
+                .with_data(serde_json::json!({ "default_adapter": name }));
+        println!("{}", serde_json::to_string(&response)?);
+    }
+    Ok(())
+}
+
+async fn handle_import_playspaces(
+    scan_options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let playspaces = match steamvr_integration::import_playspaces() {
+        Ok(playspaces) => playspaces,
+        Err(e) => {
+            let error_msg = format!("Failed to read SteamVR playspaces: {}", e);
+            CommandResponse::error(&error_msg, EXIT_STEAMVR_ERROR).print_and_exit(json_output);
+        }
+    };
+
+    if playspaces.is_empty() {
+        let message = "No SteamVR playspaces found, nothing to import";
+        if json_output {
+            let response = CommandResponse::success(message, Vec::new())
+                .with_data(serde_json::json!({ "groups": {} }));
+            println!("{}", serde_json::to_string(&response)?);
+        } else {
+            log(message, json_output);
+        }
+        return Ok(());
+    }
+    log(
+        &format!("Found {} SteamVR playspaces", playspaces.len()),
+        json_output,
+    );
+
+    // Base stations have to be seen over Bluetooth to match them to SteamVR's serials
+    let peripherals = match Manager::new().await {
+        Ok(manager) => match adapter_for_options(&manager, scan_options, json_output).await {
+            Ok(adapter) => discover_peripherals(&adapter, scan_options, json_output).await,
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e.into()),
+    };
+    let peripherals = match peripherals {
+        Ok(peripherals) => peripherals,
+        Err(e) => {
+            let error_msg = format!("Failed to scan for devices: {}", e);
+            CommandResponse::from_error(&error_msg, e.as_ref(), EXIT_BLUETOOTH_ERROR)
+                .print_and_exit(json_output);
+        }
+    };
+
+    let settings = load_settings().unwrap_or_default();
+    let mut lighthouses = Vec::new();
+    for peripheral in peripherals {
+        let is_lighthouse = read_properties(&peripheral, json_output)
+            .await
+            .is_some_and(|properties| detect_lighthouse(&properties, &settings));
+        if is_lighthouse && settings.is_address_allowed(&peripheral.address().to_string()) {
+            lighthouses.push(peripheral);
+        }
+    }
+
+    let matches = steamvr_integration::correlate_with_steamvr(&lighthouses, json_output).await;
+    let groups = steamvr_integration::assign_playspaces(&playspaces, &matches);
+
+    if scan_options.no_save {
+        log(
+            "Saving is disabled, not updating the settings file",
+            json_output,
+        );
+    } else {
+        let mut settings = settings;
+        settings.groups.extend(groups.clone());
+        if let Err(e) = save_settings(&settings) {
+            let error_msg = format!("Failed to save settings: {}", e);
+            CommandResponse::error(&error_msg, EXIT_GENERAL_ERROR).print_and_exit(json_output);
+        }
+    }
+
+    let message = format!("Imported {} playspace groups", groups.len());
+    if json_output {
+        let response = CommandResponse::success(&message, Vec::new())
+            .with_data(serde_json::json!({ "groups": groups }));
+        println!("{}", serde_json::to_string(&response)?);
+    } else {
+        log(&message, json_output);
+        for (group, addresses) in groups.iter() {
+            log(&format!("{}: {}", group, addresses.join(", ")), json_output);
+        }
+        let unmatched = matches
+            .iter()
+            .filter(|m| m.steamvr_serial.is_none())
+            .count();
+        if unmatched > 0 {
+            log(
+                &format!(
+                    "{} base stations could not be matched to a SteamVR playspace",
+                    unmatched
+                ),
+                json_output,
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Register with SteamVR. With `wait`, an interval and a timeout, a missing SteamVR is
+/// checked for again until it's installed or the timeout passes.
+async fn handle_steamvr_registration(
+    wait: Option<(Duration, Duration)>,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let result = match wait {
+        Some((interval, timeout)) => {
+            steamvr_integration::register_when_steamvr_available(
+                false,
+                interval,
+                timeout,
+                json_output,
+            )
+            .await
+        }
+        None => steamvr_integration::register_with_steamvr(false),
+    };
+    match result {
+        Ok(_) => {
+            log("Successfully registered with SteamVR", json_output);
+            if json_output {
+                let response =
+                    CommandResponse::success("Successfully registered with SteamVR", Vec::new());
+                println!("{}", serde_json::to_string(&response)?);
+            }
+            Ok(())
+        }
+        Err(e) => {
+            // Missing SteamVR and a broken install get their own reason
+            let error_msg = format!("Failed to register with SteamVR: {}", e);
+            CommandResponse::from_error(&error_msg, e.as_ref(), EXIT_STEAMVR_ERROR)
+                .print_and_exit(json_output);
+        }
+    }
+}
+
+async fn handle_steamvr_unregistration(json_output: bool) -> Result<(), Box<dyn Error>> {
+    match steamvr_integration::unregister_from_steamvr() {
+        Ok(_) => {
+            log("Successfully unregistered from SteamVR", json_output);
+            if json_output {
+                let response =
+                    CommandResponse::success("Successfully unregistered from SteamVR", Vec::new());
+                println!("{}", serde_json::to_string(&response)?);
+            }
+            Ok(())
+        }
+        Err(e) => {
+            // Missing SteamVR and a broken install get their own reason
+            let error_msg = format!("Failed to unregister from SteamVR: {}", e);
+            CommandResponse::from_error(&error_msg, e.as_ref(), EXIT_STEAMVR_ERROR)
+                .print_and_exit(json_output);
+        }
+    }
+}
+
+/// Run the SteamVR started/stopped handlers as if SteamVR had triggered them,
+/// logging the context they run in so automation can be checked without a headset session
+async fn handle_simulated_steamvr_event(
+    started: bool,
+    scan_options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let event = if started { "started" } else { "stopped" };
+    log(
+        &format!("[simulation] Simulating SteamVR {} event", event),
+        json_output,
+    );
+
+    match steamvr_integration::get_steamvr_dir() {
+        Some(dir) => log(
+            &format!("[simulation] SteamVR directory: {}", dir.display()),
+            json_output,
+        ),
+        None => log(
+            "[simulation] SteamVR not found, continuing anyway",
+            json_output,
+        ),
+    }
+    match steamvr_integration::is_registered() {
+        Ok(registered) => log(
+            &format!("[simulation] Registered with SteamVR: {}", registered),
+            json_output,
+        ),
+        Err(e) => log(
+            &format!("[simulation] Could not check SteamVR registration: {}", e),
+            json_output,
+        ),
+    }
+    if let Ok(path) = get_device_cache_path(load_settings().unwrap_or_default().cache_format) {
+        log(
+            &format!("[simulation] Device config: {}", path.display()),
+            json_output,
+        );
+    }
+    let known_devices = load_devices_with_json(json_output).unwrap_or_default();
+    log(
+        &format!("[simulation] Known devices: {}", known_devices.len()),
+        json_output,
+    );
+    for device in known_devices.iter() {
+        log(
+            &format!("[simulation]   {} ({})", device.name, device.address),
+            json_output,
+        );
+    }
+    log(
+        &format!("[simulation] Scan options: {:?}", scan_options),
+        json_output,
+    );
+
+    let started_at = std::time::Instant::now();
+    if started {
+        log(
+            "SteamVR started event detected. Powering on lighthouses...",
+            json_output,
+        );
+        handle_steamvr_started(scan_options, json_output).await?;
+    } else {
+        log(
+            "SteamVR stopped event detected. Putting lighthouses in standby...",
+            json_output,
+        );
+        handle_steamvr_stopped(scan_options, json_output).await?;
+    }
+    log(
+        &format!(
+            "[simulation] SteamVR {} event handled in {:.1}s",
+            event,
+            started_at.elapsed().as_secs_f32()
+        ),
+        json_output,
+    );
+
+    Ok(())
+}
+
+/// Show the outcome of a SteamVR handler as a desktop notification, if enabled.
+/// Failing to show one only warns, so it never changes the exit code.
+fn notify_power_result_or_warn(
+    power_on: bool,
+    result: Result<&[DeviceInfo], String>,
+    json_output: bool,
+) {
+    if let Err(e) = notify_power_result(power_on, result) {
+        error_log(
+            &format!("Warning: Failed to show a desktop notification: {}", e),
+            json_output,
+        );
+    }
+}
+
+/// Power on every `interval_secs` seconds until Ctrl-C, to bring back base stations
+/// that dropped into standby on their own. A failed round is reported and retried
+/// on the next tick rather than ending the watch. With --device only those cached
+/// devices are powered on, and the hooks run after every round.
+async fn handle_watch_command(
+    interval_secs: u64,
+    targets: &[&str],
+    scan_options: &ScanOptions,
+    hooks: &CommandHooks,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    // Resolved once, like handle_device_command_mode does for a single run
+    let target_devices = (!targets.is_empty()).then(|| watch_targets(targets, hooks, json_output));
+
+    log(
+        &format!("Powering on every {}s, press Ctrl-C to stop", interval_secs),
+        json_output,
+    );
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+    // A slow round shouldn't be followed by a burst of catch-up rounds
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = interval.tick() => {}
+        }
+
+        let round = async {
+            let Some(devices) = &target_devices else {
+                return power_on_lighthouses_with_opts(scan_options, json_output).await;
+            };
+            let controller = Controller::with_options(scan_options.clone())
+                .await?
+                .json_output(json_output);
+            controller.command_devices(devices, POWER_ON.byte()).await?;
+            Ok(devices.clone())
+        };
+        let result = tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            result = round => result,
+        };
+        // Every round counts as a command of its own for the hooks and the history
+        let response = match result {
+            Ok(devices) => {
+                log(
+                    &format!("Powered on {} lighthouses", devices.len()),
+                    json_output,
+                );
+                hooks.succeeded(&devices, json_output);
+                CommandResponse::success("Successfully powered on lighthouses", devices)
+            }
+            Err(e) => {
+                let error_msg = format!("Failed to power on lighthouses: {}", e);
+                error_log(&error_msg, json_output);
+                let response = CommandResponse::command_failed(&error_msg, e.as_ref());
+                hooks.failed(response.error_code, json_output);
+                response
+            }
+        };
+        if json_output {
+            println!("{}", serde_json::to_string(&response)?);
+        }
+    }
+
+    log("Stopped watching", json_output);
+    Ok(())
+}
+
+/// The cached devices named by --device for --watch, leaving out any the allowlist
+/// or denylist excludes. Fails before the first round if one isn't in the cache.
+fn watch_targets(targets: &[&str], hooks: &CommandHooks, json_output: bool) -> Vec<DeviceInfo> {
+    let cached_devices = load_devices().unwrap_or_default();
+    if let Some(address) = targets
+        .iter()
+        .find(|target| find_device(&cached_devices, target).is_none())
+    {
+        let error_msg = format!(
+            "No cached device has the address {}. Run --scan to find it first.",
+            address
+        );
+        hooks.fail(
+            CommandResponse::error(&error_msg, EXIT_NO_DEVICES_FOUND),
+            json_output,
+        );
+    }
+
+    let settings = load_settings().unwrap_or_default();
+    cached_devices
+        .into_iter()
+        .filter(|device| targets.iter().any(|target| device.matches_address(target)))
+        .filter(|device| settings.is_address_allowed(&device.address))
+        .collect()
+}
+
+async fn handle_steamvr_started(
+    scan_options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    match power_on_lighthouses_with_opts(scan_options, json_output).await {
+        Ok(devices) => {
+            notify_power_result_or_warn(true, Ok(&devices), json_output);
+            if json_output {
+                let devices = load_devices_with_json(json_output).unwrap_or_default();
+                let response =
+                    CommandResponse::success("Successfully powered on lighthouses", devices);
+                println!("{}", serde_json::to_string(&response)?);
+            }
+            Ok(())
+        }
+        Err(e) => {
+            notify_power_result_or_warn(true, Err(e.to_string()), json_output);
+            let error_msg = format!("Failed to power on lighthouses: {}", e);
+            let response = CommandResponse::command_failed(&error_msg, e.as_ref());
+            if json_output {
                 println!("{}", serde_json::to_string(&response)?);
+            } else {
+                error_log(&error_msg, json_output);
             }
-            Ok(())
+            process::exit(response.error_code);
+        }
+    }
+}
+
+/// Power on the base stations, wait until they report being on, then start SteamVR.
+/// If SteamVR is already running only the power on part is done.
+async fn handle_launch_steamvr(
+    scan_options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let already_running = steamvr_integration::is_steamvr_running();
+    if already_running {
+        log(
+            "SteamVR is already running, only powering on lighthouses",
+            json_output,
+        );
+    }
+
+    // Only launch once every base station has confirmed it's on
+    let mut options = scan_options.clone();
+    options.verify = options
+        .verify
+        .or(Some(Duration::from_secs(DEFAULT_VERIFY_TIMEOUT_SECS)));
+
+    let devices = match power_on_lighthouses_with_opts(&options, json_output).await {
+        Ok(devices) if devices.is_empty() => {
+            let error_msg = "No Lighthouse devices found, not starting SteamVR";
+            CommandResponse::error(error_msg, EXIT_NO_DEVICES_FOUND).print_and_exit(json_output);
         }
+        Ok(devices) => devices,
         Err(e) => {
+            let error_msg = format!(
+                "Failed to power on lighthouses, not starting SteamVR: {}",
+                e
+            );
+            let response = CommandResponse::command_failed(&error_msg, e.as_ref());
             if json_output {
-                let response = CommandResponse::error(
-                    &format!("Failed to power on lighthouses: {}", e),
-                    EXIT_COMMAND_FAILED,
-                );
                 println!("{}", serde_json::to_string(&response)?);
             } else {
-                error_log(
-                    &format!("Failed to power on lighthouses: {}", e),
-                    json_output,
-                );
+                error_log(&error_msg, json_output);
+            }
+            process::exit(response.error_code);
+        }
+    };
+
+    let message = if already_running {
+        "Lighthouses are on, SteamVR was already running".to_string()
+    } else {
+        match steamvr_integration::launch_steamvr() {
+            Ok(launcher) => format!(
+                "Lighthouses are on, started SteamVR ({})",
+                launcher.display()
+            ),
+            Err(e) => {
+                let error_msg = format!("Failed to start SteamVR: {}", e);
+                CommandResponse::error(&error_msg, EXIT_STEAMVR_ERROR).print_and_exit(json_output);
             }
-            process::exit(EXIT_COMMAND_FAILED);
         }
+    };
+
+    if json_output {
+        let response = CommandResponse::success(&message, devices);
+        println!("{}", serde_json::to_string(&response)?);
+    } else {
+        log(&message, json_output);
     }
+    Ok(())
 }
 
-async fn handle_steamvr_stopped(json_output: bool) -> Result<(), Box<dyn Error>> {
-    match standby_lighthouses_with_json(json_output).await {
-        Ok(_) => {
+async fn handle_steamvr_stopped(
+    scan_options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    // Automatic standby never touches excluded devices
+    let scan_options = ScanOptions {
+        respect_exclusions: true,
+        ..scan_options.clone()
+    };
+    match standby_lighthouses_with_opts(&scan_options, json_output).await {
+        Ok(devices) => {
+            notify_power_result_or_warn(false, Ok(&devices), json_output);
             if json_output {
                 let devices = load_devices_with_json(json_output).unwrap_or_default();
                 let response =
@@ -287,25 +2107,24 @@ async fn handle_steamvr_stopped(json_output: bool) -> Result<(), Box<dyn Error>>
             Ok(())
         }
         Err(e) => {
+            notify_power_result_or_warn(false, Err(e.to_string()), json_output);
+            let error_msg = format!("Failed to put lighthouses in standby: {}", e);
+            let response = CommandResponse::command_failed(&error_msg, e.as_ref());
             if json_output {
-                let response = CommandResponse::error(
-                    &format!("Failed to put lighthouses in standby: {}", e),
-                    EXIT_COMMAND_FAILED,
-                );
                 println!("{}", serde_json::to_string(&response)?);
             } else {
-                error_log(
-                    &format!("Failed to put lighthouses in standby: {}", e),
-                    json_output,
-                );
+                error_log(&error_msg, json_output);
             }
-            process::exit(EXIT_COMMAND_FAILED);
+            process::exit(response.error_code);
         }
     }
 }
 
-async fn handle_scan_command(json_output: bool) -> Result<(), Box<dyn Error>> {
-    match lighthouse_core::bluetooth::scan_process_and_save_with_json(0xFF, json_output).await {
+async fn handle_scan_command(
+    scan_options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    match scan_process_and_save_with_opts(0xFF, scan_options, json_output).await {
         Ok(_) => {
             let devices = load_devices_with_json(json_output).unwrap_or_default();
             if json_output {
@@ -318,40 +2137,366 @@ async fn handle_scan_command(json_output: bool) -> Result<(), Box<dyn Error>> {
             Ok(())
         }
         Err(e) => {
+            CommandResponse::from_error(
+                &format!("Failed to scan for devices: {}", e),
+                e.as_ref(),
+                EXIT_BLUETOOTH_ERROR,
+            )
+            .print_and_exit(json_output);
+        }
+    }
+}
+
+/// Scan and report how the base stations found differ from the cache.
+/// The cache is left as it is, so the same differences show up until the next --scan.
+async fn handle_scan_diff(
+    scan_options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let cached = load_devices_with_json(json_output).unwrap_or_default();
+    let options = ScanOptions {
+        no_save: true,
+        ..scan_options.clone()
+    };
+    let report = match scan_with_report(&options, json_output).await {
+        Ok(report) => report,
+        Err(e) => {
+            let error_msg = format!("Failed to scan for devices: {}", e);
+            CommandResponse::from_error(&error_msg, e.as_ref(), EXIT_BLUETOOTH_ERROR)
+                .print_and_exit(json_output);
+        }
+    };
+    let diff = DeviceDiff::between(&cached, &report.devices);
+
+    if json_output {
+        let message = if diff.is_empty() {
+            "The scan matches the cache"
+        } else {
+            "The scan differs from the cache"
+        };
+        let response = CommandResponse::success(message, report.devices)
+            .with_data(serde_json::json!({ "diff": diff }));
+        println!("{}", serde_json::to_string(&response)?);
+        return Ok(());
+    }
+
+    println!();
+    if diff.is_empty() {
+        println!("The scan matches the {} cached devices", cached.len());
+        return Ok(());
+    }
+    for device in &diff.added {
+        println!("+ {} ({}) is new", device.name, device.address);
+    }
+    for device in &diff.missing {
+        println!("- {} ({}) was not found", device.name, device.address);
+    }
+    for device in &diff.renamed {
+        println!(
+            "~ {} was renamed from {} to {}",
+            device.address, device.cached_name, device.scanned_name
+        );
+    }
+    Ok(())
+}
+
+/// Print each base station as a JSON line as soon as the scan finds it, then a summary
+/// line with the usual response fields. Every line is flushed as it's written.
+async fn handle_scan_stream(scan_options: &ScanOptions) -> Result<(), Box<dyn Error>> {
+    fn print_line(line: &serde_json::Value) {
+        let mut stdout = std::io::stdout().lock();
+        // A closed pipe just means the reader has gone away
+        let _ = writeln!(stdout, "{}", line).and_then(|_| stdout.flush());
+    }
+    fn summary_line(response: &CommandResponse) -> Result<serde_json::Value, Box<dyn Error>> {
+        let mut line = serde_json::to_value(response)?;
+        line["type"] = "summary".into();
+        Ok(line)
+    }
+
+    let result = stream_lighthouses(
+        scan_options,
+        |device| print_line(&serde_json::json!({ "type": "device", "device": device })),
+        true,
+    )
+    .await;
+
+    match result {
+        Ok(devices) if devices.is_empty() => {
+            let response =
+                CommandResponse::error("No Lighthouse Base Stations found", EXIT_NO_DEVICES_FOUND);
+            print_line(&summary_line(&response)?);
+            process::exit(EXIT_NO_DEVICES_FOUND);
+        }
+        Ok(devices) => {
+            let message = format!("Found {} Lighthouse Base Stations", devices.len());
+            print_line(&summary_line(&CommandResponse::success(&message, devices))?);
+            Ok(())
+        }
+        Err(e) => {
+            let response = CommandResponse::from_error(
+                &format!("Failed to scan for devices: {}", e),
+                e.as_ref(),
+                EXIT_BLUETOOTH_ERROR,
+            );
+            print_line(&summary_line(&response)?);
+            process::exit(response.error_code);
+        }
+    }
+}
+
+async fn handle_batch_command(scan_options: &ScanOptions) -> Result<(), Box<dyn Error>> {
+    let json_output = true;
+    let cached_devices = load_devices_with_json(json_output).unwrap_or_default();
+
+    let manager = match Manager::new().await {
+        Ok(manager) => manager,
+        Err(e) => {
+            let response = CommandResponse::error(
+                &format!("Failed to initialize Bluetooth: {}", e),
+                EXIT_BLUETOOTH_ERROR,
+            );
+            println!("{}", serde_json::to_string(&response)?);
+            process::exit(EXIT_BLUETOOTH_ERROR);
+        }
+    };
+
+    let peripherals = match adapter_for_options(&manager, scan_options, json_output).await {
+        Ok(adapter) => discover_peripherals(&adapter, scan_options, json_output).await,
+        Err(e) => Err(e),
+    };
+    let peripherals = match peripherals {
+        Ok(peripherals) => peripherals,
+        Err(e) => {
+            let response = CommandResponse::from_error(
+                &format!("Failed to scan for devices: {}", e),
+                e.as_ref(),
+                EXIT_BLUETOOTH_ERROR,
+            );
+            println!("{}", serde_json::to_string(&response)?);
+            process::exit(response.error_code);
+        }
+    };
+
+    // Start from the known devices so ones that didn't answer are still reported
+    let mut statuses: Vec<DeviceStatus> = cached_devices
+        .iter()
+        .map(|device| DeviceStatus {
+            name: device.name.clone(),
+            address: device.address.clone(),
+            reachable: false,
+            known: true,
+            rssi: None,
+        })
+        .collect();
+
+    let settings = load_settings().unwrap_or_default();
+    for peripheral in peripherals.iter() {
+        let allowed = settings.is_address_allowed(&peripheral.address().to_string());
+        let properties = match read_properties(peripheral, json_output).await {
+            Some(properties) if allowed && detect_lighthouse(&properties, &settings) => properties,
+            _ => continue,
+        };
+        let info = peripheral_to_device_info(peripheral).await?;
+
+        match statuses
+            .iter_mut()
+            .find(|status| normalize_address(&status.address) == normalize_address(&info.address))
+        {
+            Some(status) => {
+                status.reachable = true;
+                status.rssi = properties.rssi;
+            }
+            None => statuses.push(DeviceStatus {
+                name: info.name,
+                address: info.address,
+                reachable: true,
+                known: false,
+                rssi: properties.rssi,
+            }),
+        }
+    }
+
+    // Keep the cache in sync the same way a TUI refresh does
+    process_scan_results_with_opts(peripherals, 0xFF, scan_options, json_output).await?;
+
+    if statuses.is_empty() {
+        let response = CommandResponse::error("No Lighthouse devices found", EXIT_NO_DEVICES_FOUND);
+        println!("{}", serde_json::to_string(&response)?);
+        process::exit(EXIT_NO_DEVICES_FOUND);
+    }
+
+    let reachable = statuses.iter().filter(|status| status.reachable).count();
+    let devices = statuses
+        .iter()
+        .map(|status| DeviceInfo {
+            rssi: status.rssi,
+            ..DeviceInfo::new(status.name.clone(), status.address.clone())
+        })
+        .collect();
+    let response = CommandResponse::success(
+        &format!("{} of {} devices reachable", reachable, statuses.len()),
+        devices,
+    )
+    .with_data(serde_json::json!({ "statuses": statuses }));
+    println!("{}", serde_json::to_string(&response)?);
+
+    Ok(())
+}
+
+/// Liveness probe for monitoring: exit 0 if an adapter is available and Bluetooth is on,
+/// and with `check_reachable` also if a scan hears at least one cached base station.
+/// Otherwise exit nonzero with the failed check as the reason.
+async fn handle_check_command(
+    scan_options: &ScanOptions,
+    check_reachable: bool,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut checks = serde_json::Map::new();
+    let fail =
+        |reason: &str, code: i32, checks: serde_json::Map<_, _>| -> Result<(), Box<dyn Error>> {
             if json_output {
-                let response = CommandResponse::error(
-                    &format!("Failed to scan for devices: {}", e),
-                    EXIT_BLUETOOTH_ERROR,
-                );
+                let response = CommandResponse::error(reason, code)
+                    .with_data(serde_json::json!({ "checks": checks }));
                 println!("{}", serde_json::to_string(&response)?);
             } else {
-                error_log(&format!("Failed to scan for devices: {}", e), json_output);
+                println!("FAIL: {}", reason);
             }
-            process::exit(EXIT_BLUETOOTH_ERROR);
+            process::exit(code);
+        };
+
+    let manager = match Manager::new().await {
+        Ok(manager) => manager,
+        Err(e) => {
+            return fail(
+                &format!("Bluetooth is unavailable: {}", e),
+                EXIT_BLUETOOTH_ERROR,
+                checks,
+            )
+        }
+    };
+    let adapter = match adapter_for_options(&manager, scan_options, json_output).await {
+        Ok(adapter) => adapter,
+        Err(e) => return fail(&e.to_string(), EXIT_BLUETOOTH_ERROR, checks),
+    };
+    checks.insert(
+        "adapter".into(),
+        adapter.adapter_info().await.unwrap_or_default().into(),
+    );
+
+    // Platforms that can't tell report Unknown, which isn't treated as off
+    let state = adapter
+        .adapter_state()
+        .await
+        .unwrap_or(CentralState::Unknown);
+    if state == CentralState::PoweredOff {
+        checks.insert("bluetooth_on".into(), false.into());
+        let error = LighthouseError::BluetoothOff;
+        return fail(&error.to_string(), EXIT_BLUETOOTH_OFF, checks);
+    }
+    let bluetooth_on = match state {
+        CentralState::PoweredOn => serde_json::Value::Bool(true),
+        _ => serde_json::Value::Null,
+    };
+    checks.insert("bluetooth_on".into(), bluetooth_on);
+
+    let mut message = "Bluetooth adapter is available".to_string();
+    if check_reachable {
+        let settings = load_settings().unwrap_or_default();
+        let cached: Vec<DeviceInfo> = load_devices_with_json(true)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|device| settings.is_address_allowed(&device.address))
+            .collect();
+        if cached.is_empty() {
+            return fail(
+                "No cached base stations to check",
+                EXIT_NO_DEVICES_FOUND,
+                checks,
+            );
+        }
+
+        let peripherals = match discover_peripherals(&adapter, scan_options, json_output).await {
+            Ok(peripherals) => peripherals,
+            Err(e) => {
+                return fail(
+                    &format!("Failed to scan for devices: {}", e),
+                    EXIT_BLUETOOTH_ERROR,
+                    checks,
+                )
+            }
+        };
+        let scanned: Vec<(Peripheral, String)> = peripherals
+            .iter()
+            .map(|peripheral| (peripheral.clone(), peripheral.address().to_string()))
+            .collect();
+        let (found, _) = match_peripherals(&cached, &scanned);
+        checks.insert("reachable".into(), found.len().into());
+        if found.is_empty() {
+            let reason = format!(
+                "None of the {} cached base stations are reachable",
+                cached.len()
+            );
+            return fail(&reason, EXIT_NO_DEVICES_FOUND, checks);
         }
+        message = format!(
+            "{} of {} cached base stations reachable",
+            found.len(),
+            cached.len()
+        );
+    }
+
+    if json_output {
+        let response = CommandResponse::success(&message, Vec::new())
+            .with_data(serde_json::json!({ "checks": checks }));
+        println!("{}", serde_json::to_string(&response)?);
+    } else {
+        println!("OK: {}", message);
     }
+    Ok(())
 }
 
+/// Send a power command to the cached devices, or only to the `targets` addresses
+/// when there are any
 async fn handle_device_command_mode(
     command_mode: u8,
+    targets: &[&str],
+    scan_options: &ScanOptions,
+    hooks: &CommandHooks,
     json_output: bool,
-) -> Result<(), Box<dyn Error>> {
-    let cached_devices = match load_devices() {
+) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
+    let mut cached_devices = match load_devices() {
         Ok(devices) => devices,
         Err(e) => {
-            if json_output {
-                let response = CommandResponse::error(
+            hooks.fail(
+                CommandResponse::error(
                     &format!("Failed to load known devices: {}", e),
                     EXIT_GENERAL_ERROR,
-                );
-                println!("{}", serde_json::to_string(&response)?);
-            } else {
-                eprintln!("Failed to load known devices: {}", e);
-            }
-            process::exit(EXIT_GENERAL_ERROR);
+                ),
+                json_output,
+            );
         }
     };
 
+    if !targets.is_empty() {
+        let unknown = targets.iter().find(|target| {
+            !cached_devices
+                .iter()
+                .any(|device| device.matches_address(target))
+        });
+        if let Some(address) = unknown {
+            let error_msg = format!(
+                "No cached device has the address {}. Run --scan to find it first.",
+                address
+            );
+            hooks.fail(
+                CommandResponse::error(&error_msg, EXIT_NO_DEVICES_FOUND),
+                json_output,
+            );
+        }
+        cached_devices.retain(|device| targets.iter().any(|target| device.matches_address(target)));
+    }
+
     if !cached_devices.is_empty() {
         log(
             &format!("Found {} known Lighthouse devices:", cached_devices.len()),
@@ -374,109 +2519,97 @@ async fn handle_device_command_mode(
         let manager = match Manager::new().await {
             Ok(m) => m,
             Err(e) => {
-                if json_output {
-                    let response = CommandResponse::error(
+                hooks.fail(
+                    CommandResponse::error(
                         &format!("Failed to initialize Bluetooth manager: {}", e),
                         EXIT_BLUETOOTH_ERROR,
-                    );
-                    println!("{}", serde_json::to_string(&response)?);
-                } else {
-                    eprintln!("Failed to initialize Bluetooth manager: {}", e);
-                }
-                process::exit(EXIT_BLUETOOTH_ERROR);
+                    ),
+                    json_output,
+                );
             }
         };
 
-        let adapters = match manager.adapters().await {
+        let adapter = match adapter_for_options(&manager, scan_options, json_output).await {
             Ok(a) => a,
             Err(e) => {
-                if json_output {
-                    let response = CommandResponse::error(
-                        &format!("Failed to get Bluetooth adapters: {}", e),
-                        EXIT_BLUETOOTH_ERROR,
-                    );
-                    println!("{}", serde_json::to_string(&response)?);
+                // Our own errors already carry an actionable message
+                let error_msg = if e.downcast_ref::<LighthouseError>().is_some() {
+                    e.to_string()
                 } else {
-                    eprintln!("Failed to get Bluetooth adapters: {}", e);
-                }
-                process::exit(EXIT_BLUETOOTH_ERROR);
+                    format!("Failed to get Bluetooth adapters: {}", e)
+                };
+                hooks.fail(
+                    CommandResponse::from_error(&error_msg, e.as_ref(), EXIT_BLUETOOTH_ERROR),
+                    json_output,
+                );
             }
         };
-
-        if adapters.is_empty() {
-            let error_msg = "No Bluetooth adapters found";
-            if json_output {
-                let response = CommandResponse::error(error_msg, EXIT_BLUETOOTH_ERROR);
-                println!("{}", serde_json::to_string(&response)?);
-            } else {
-                eprintln!("{}", error_msg);
-            }
-            process::exit(EXIT_BLUETOOTH_ERROR);
-        }
-
-        let adapter = &adapters[0];
         log(
             &format!("Using adapter: {}", adapter.adapter_info().await?),
             json_output,
         );
 
         // Start a scan to find the known devices
-        match adapter.start_scan(ScanFilter::default()).await {
-            Ok(_) => {}
-            Err(e) => {
-                if json_output {
-                    let response = CommandResponse::error(
-                        &format!("Failed to start Bluetooth scan: {}", e),
-                        EXIT_BLUETOOTH_ERROR,
-                    );
-                    println!("{}", serde_json::to_string(&response)?);
-                } else {
-                    eprintln!("Failed to start Bluetooth scan: {}", e);
-                }
-                process::exit(EXIT_BLUETOOTH_ERROR);
-            }
-        };
-
-        time::sleep(Duration::from_secs(5)).await;
-
-        let peripherals = match adapter.peripherals().await {
+        let peripherals = match discover_peripherals(&adapter, scan_options, json_output).await {
             Ok(p) => p,
-            Err(e) => {
-                if json_output {
-                    let response = CommandResponse::error(
-                        &format!("Failed to get peripherals: {}", e),
-                        EXIT_BLUETOOTH_ERROR,
+            // Nothing can be reached directly either while Bluetooth is off
+            Err(e)
+                if scan_options.direct_fallback
+                    && !matches!(
+                        e.downcast_ref::<LighthouseError>(),
+                        Some(LighthouseError::BluetoothOff)
+                    ) =>
+            {
+                log(
+                    &format!(
+                        "Failed to scan for devices ({}), falling back to connecting to known devices directly",
+                        e
+                    ),
+                    json_output,
+                );
+                let known = known_peripherals(&adapter, &cached_devices)
+                    .await
+                    .unwrap_or_default();
+                if known.is_empty() {
+                    let error_msg = format!(
+                        "Failed to scan for devices ({}) and none of the known devices can be reached directly",
+                        e
+                    );
+                    hooks.fail(
+                        CommandResponse::error(&error_msg, EXIT_BLUETOOTH_ERROR),
+                        json_output,
                     );
-                    println!("{}", serde_json::to_string(&response)?);
-                } else {
-                    eprintln!("Failed to get peripherals: {}", e);
                 }
-                process::exit(EXIT_BLUETOOTH_ERROR);
+                log(
+                    &format!(
+                        "Direct-connect mode: the adapter knows {} of {} known devices",
+                        known.len(),
+                        cached_devices.len()
+                    ),
+                    json_output,
+                );
+                known
             }
-        };
-
-        match adapter.stop_scan().await {
-            Ok(_) => {}
             Err(e) => {
-                log(
-                    &format!("Warning: Failed to stop Bluetooth scan: {}", e),
+                let error_msg = format!("Failed to scan for devices: {}", e);
+                hooks.fail(
+                    CommandResponse::from_error(&error_msg, e.as_ref(), EXIT_BLUETOOTH_ERROR),
                     json_output,
                 );
             }
         };
 
-        let mut lighthouse_devices = Vec::new();
-
-        for peripheral in peripherals.iter() {
-            let address = peripheral.address().to_string();
-
-            if cached_devices
-                .iter()
-                .any(|device| device.address == address)
-            {
-                lighthouse_devices.push(peripheral.clone());
-            }
-        }
+        let settings = load_settings().unwrap_or_default();
+        let allowed: Vec<DeviceInfo> = cached_devices
+            .iter()
+            .filter(|device| settings.is_address_allowed(&device.address))
+            .cloned()
+            .collect();
+        let scanned: Vec<(Peripheral, String)> = peripherals
+            .iter()
+            .map(|peripheral| (peripheral.clone(), peripheral.address().to_string()))
+            .collect();
+        let (lighthouse_devices, missing) = match_peripherals(&allowed, &scanned);
 
         if lighthouse_devices.is_empty() {
             log(
@@ -484,13 +2617,13 @@ async fn handle_device_command_mode(
                 json_output,
             );
 
-            if json_output {
+            // A new scan would command every base station it finds, not just the targets
+            if json_output || !targets.is_empty() {
                 let response = CommandResponse::error(
                     "No cached devices found in the current scan",
                     EXIT_NO_DEVICES_FOUND,
                 );
-                println!("{}", serde_json::to_string(&response)?);
-                process::exit(EXIT_NO_DEVICES_FOUND);
+                hooks.fail(response, json_output);
             } else {
                 log(
                     "Would you like to perform a new scan to find devices? (y/n)",
@@ -501,27 +2634,24 @@ async fn handle_device_command_mode(
 
                 if input.trim().eq_ignore_ascii_case("y") {
                     log("Performing a new scan...", json_output);
-                    match scan_process_and_save(command_mode).await {
+                    match scan_process_and_save_with_opts(command_mode, scan_options, false).await {
                         Ok(_) => {
                             let devices = load_devices().unwrap_or_default();
                             if json_output {
                                 let response = CommandResponse::success(
                                     "Successfully executed command on new devices",
-                                    devices,
+                                    devices.clone(),
                                 );
                                 println!("{}", serde_json::to_string(&response)?);
                             }
-                            return Ok(());
+                            Ok(devices)
                         }
                         Err(e) => {
-                            if json_output {
-                                let response = CommandResponse::error(
-                                    &format!("Failed to execute command: {}", e),
-                                    EXIT_COMMAND_FAILED,
-                                );
-                                println!("{}", serde_json::to_string(&response)?);
-                            }
-                            process::exit(EXIT_COMMAND_FAILED);
+                            let response = CommandResponse::command_failed(
+                                &format!("Failed to execute command: {}", e),
+                                e.as_ref(),
+                            );
+                            hooks.fail(response, json_output);
                         }
                     }
                 } else {
@@ -533,7 +2663,7 @@ async fn handle_device_command_mode(
                         );
                         println!("{}", serde_json::to_string(&response)?);
                     }
-                    process::exit(EXIT_NO_DEVICES_FOUND);
+                    hooks.exit(EXIT_NO_DEVICES_FOUND, json_output);
                 }
             }
         } else {
@@ -545,43 +2675,74 @@ async fn handle_device_command_mode(
                 ),
                 json_output,
             );
+            for device in &missing {
+                log(
+                    &format!("Not found: {} ({})", device.name, device.address),
+                    json_output,
+                );
+            }
 
-            match handle_device_command(&lighthouse_devices, command_mode).await {
-                Ok(_) => {
-                    if json_output {
-                        let mut found_devices = Vec::new();
-                        for device in lighthouse_devices.iter() {
-                            if let Ok(device_info) = peripheral_to_device_info(device).await {
-                                found_devices.push(device_info);
-                            }
+            match command_devices_with_opts(
+                &lighthouse_devices,
+                command_mode,
+                scan_options,
+                json_output,
+            )
+            .await
+            {
+                Ok(skipped) => {
+                    let mut found_devices = Vec::new();
+                    for device in lighthouse_devices.iter() {
+                        if let Ok(device_info) = peripheral_to_device_info(device).await {
+                            found_devices.push(device_info);
                         }
+                    }
 
-                        let command_name = if command_mode == STANDBY_COMMAND {
-                            "standby"
-                        } else {
-                            "power on"
-                        };
+                    if json_output {
+                        let command_name =
+                            power_command(command_mode).map_or("unknown", |command| command.name);
 
-                        let response = CommandResponse::success(
-                            &format!(
-                                "Successfully sent {} command to {} devices",
-                                command_name,
-                                found_devices.len()
-                            ),
-                            found_devices,
+                        let mut message = format!(
+                            "Successfully sent {} command to {} devices",
+                            command_name,
+                            lighthouse_devices.len() - skipped.len()
                         );
+                        if !skipped.is_empty() {
+                            message.push_str(&format!(
+                                ", skipped {} already in that state",
+                                skipped.len()
+                            ));
+                        }
+
+                        let mut response =
+                            CommandResponse::success(&message, found_devices.clone());
+                        if scan_options.only_changed {
+                            response =
+                                response.with_data(serde_json::json!({ "skipped": skipped }));
+                        }
                         println!("{}", serde_json::to_string(&response)?);
                     }
+                    Ok(found_devices)
                 }
+                // A lost adapter maps to EXIT_BLUETOOTH_ERROR here as well
                 Err(e) => {
-                    if json_output {
-                        let response = CommandResponse::error(
-                            &format!("Failed to send command to devices: {}", e),
-                            EXIT_COMMAND_FAILED,
-                        );
-                        println!("{}", serde_json::to_string(&response)?);
+                    let mut response = CommandResponse::command_failed(
+                        &format!("Failed to send command to devices: {}", e),
+                        e.as_ref(),
+                    );
+                    if let Some(LighthouseError::DevicesFailed { failed, .. }) = e.downcast_ref() {
+                        let mut succeeded = Vec::new();
+                        for device in lighthouse_devices.iter() {
+                            if failed.contains(&device.address().to_string()) {
+                                continue;
+                            }
+                            if let Ok(device_info) = peripheral_to_device_info(device).await {
+                                succeeded.push(device_info);
+                            }
+                        }
+                        response = response.with_devices(succeeded);
                     }
-                    process::exit(EXIT_COMMAND_FAILED);
+                    hooks.fail(response, json_output);
                 }
             }
         }
@@ -590,29 +2751,25 @@ async fn handle_device_command_mode(
             "No known devices found. Performing a scan automatically...",
             json_output,
         );
-        match scan_process_and_save(command_mode).await {
+        match scan_process_and_save_with_opts(command_mode, scan_options, false).await {
             Ok(_) => {
                 let devices = load_devices().unwrap_or_default();
                 if json_output {
                     let response = CommandResponse::success(
                         "Successfully scanned and executed command",
-                        devices,
+                        devices.clone(),
                     );
                     println!("{}", serde_json::to_string(&response)?);
                 }
+                Ok(devices)
             }
             Err(e) => {
-                if json_output {
-                    let response = CommandResponse::error(
-                        &format!("Failed to scan and execute command: {}", e),
-                        EXIT_COMMAND_FAILED,
-                    );
-                    println!("{}", serde_json::to_string(&response)?);
-                }
-                process::exit(EXIT_COMMAND_FAILED);
+                let response = CommandResponse::command_failed(
+                    &format!("Failed to scan and execute command: {}", e),
+                    e.as_ref(),
+                );
+                hooks.fail(response, json_output);
             }
         }
     }
-
-    Ok(())
 }
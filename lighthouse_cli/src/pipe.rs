@@ -0,0 +1,194 @@
+// Headless mode for scripts and pipelines: reads one command per line from stdin,
+// such as `poweron`, `status LHB-AB12` or `standby --group room1`, and prints one
+// JSON response per line. Every command goes through the same Controller, so
+// Bluetooth is only set up once however many commands are piped in.
+use crate::cli::{
+    CommandResponse, EXIT_BLUETOOTH_ERROR, EXIT_GENERAL_ERROR, EXIT_NO_DEVICES_FOUND,
+};
+use lighthouse_core::bluetooth::{find_command, Controller, ScanOptions};
+use lighthouse_core::config::{load_devices_with_json, load_settings};
+use lighthouse_core::models::{normalize_address, DeviceInfo, UNKNOWN_DEVICE_NAME};
+use std::error::Error;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// Commands understood on stdin, for the error on an unknown one
+const PIPE_COMMANDS: &str = "poweron, standby, status, scan, quit";
+
+/// Run commands from stdin until end-of-input or `quit`
+pub async fn run_pipe(options: ScanOptions) -> Result<(), Box<dyn Error>> {
+    let mut controller = match Controller::with_options(options).await {
+        Ok(controller) => controller.json_output(true),
+        Err(e) => {
+            let error_msg = format!("Failed to set up Bluetooth: {}", e);
+            CommandResponse::from_error(&error_msg, e.as_ref(), EXIT_BLUETOOTH_ERROR)
+                .print_and_exit(true);
+        }
+    };
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim();
+        // Blank lines and comments keep command files readable
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.eq_ignore_ascii_case("quit") || line.eq_ignore_ascii_case("exit") {
+            break;
+        }
+
+        let response = run_line(&mut controller, line).await;
+        println!("{}", serde_json::to_string(&with_command(response, line))?);
+    }
+    Ok(())
+}
+
+/// Run one command line and describe how it went
+async fn run_line(controller: &mut Controller, line: &str) -> CommandResponse {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default().to_ascii_lowercase();
+    let (force, words): (Vec<&str>, Vec<&str>) = words.partition(|word| *word == "--force");
+    let targets = match resolve_targets(words.into_iter()) {
+        Ok(targets) => targets,
+        Err(response) => return response,
+    };
+
+    match command.as_str() {
+        "poweron" | "standby" => {
+            let device_command =
+                find_command(&command).expect("poweron and standby are device commands");
+            let result = match &targets {
+                Some(devices) => {
+                    // --force only overrides the standby exclusions for devices named explicitly
+                    let respect_exclusions = controller.options().respect_exclusions;
+                    controller.options_mut().respect_exclusions =
+                        respect_exclusions && force.is_empty();
+                    let result = controller
+                        .command_devices(devices, device_command.byte())
+                        .await
+                        .map(|_| devices.clone());
+                    controller.options_mut().respect_exclusions = respect_exclusions;
+                    result
+                }
+                None if command == "poweron" => controller.power_on().await,
+                None => controller.standby().await,
+            };
+            match result {
+                Ok(devices) => CommandResponse::success(
+                    &format!("Sent {} to {} devices", device_command.name, devices.len()),
+                    devices,
+                ),
+                Err(e) => {
+                    let error_msg = format!("Failed to send {}: {}", device_command.name, e);
+                    CommandResponse::command_failed(&error_msg, e.as_ref())
+                }
+            }
+        }
+        "status" => {
+            let result = match &targets {
+                Some(devices) => controller.read_states_of(devices).await,
+                None => controller.read_states().await,
+            };
+            match result {
+                Ok(states) => CommandResponse::with_states("Read power states", states),
+                Err(e) => {
+                    let error_msg = format!("Failed to read power states: {}", e);
+                    CommandResponse::from_error(&error_msg, e.as_ref(), EXIT_GENERAL_ERROR)
+                }
+            }
+        }
+        "scan" if targets.is_none() => match controller.scan().await {
+            Ok(report) => CommandResponse::success(
+                &format!("Found {} Lighthouse devices", report.devices.len()),
+                report.devices,
+            ),
+            Err(e) => {
+                let error_msg = format!("Failed to scan: {}", e);
+                CommandResponse::from_error(&error_msg, e.as_ref(), EXIT_GENERAL_ERROR)
+            }
+        },
+        "scan" => CommandResponse::error("scan doesn't take devices or groups", EXIT_GENERAL_ERROR),
+        _ => CommandResponse::error(
+            &format!(
+                "Unknown command '{}', expected one of: {}",
+                command, PIPE_COMMANDS
+            ),
+            EXIT_GENERAL_ERROR,
+        ),
+    }
+}
+
+/// Look up the devices named after the command, by address, nickname or advertised
+/// name, and the members of any `--group NAME`. `None` when nothing was named,
+/// meaning every device.
+fn resolve_targets<'a>(
+    mut words: impl Iterator<Item = &'a str>,
+) -> Result<Option<Vec<DeviceInfo>>, CommandResponse> {
+    let settings = load_settings().unwrap_or_default();
+    let known = load_devices_with_json(true).unwrap_or_default();
+    let not_found = |what: String| CommandResponse::error(&what, EXIT_NO_DEVICES_FOUND);
+
+    let mut addresses: Vec<String> = Vec::new();
+    let mut any = false;
+    while let Some(word) = words.next() {
+        any = true;
+        if word == "--group" {
+            let Some(name) = words.next() else {
+                return Err(CommandResponse::error(
+                    "Missing group name for --group",
+                    EXIT_GENERAL_ERROR,
+                ));
+            };
+            match settings.groups.get(name) {
+                Some(members) => {
+                    addresses.extend(members.iter().map(|address| normalize_address(address)))
+                }
+                None => return Err(not_found(format!("No group named '{}'", name))),
+            }
+            continue;
+        }
+
+        let device = known.iter().find(|device| {
+            device.matches_address(word)
+                || settings.display_name(device).eq_ignore_ascii_case(word)
+                || device.name.eq_ignore_ascii_case(word)
+        });
+        match device {
+            Some(device) => addresses.push(normalize_address(&device.address)),
+            None => return Err(not_found(format!("No known device matches '{}'", word))),
+        }
+    }
+    if !any {
+        return Ok(None);
+    }
+
+    addresses.sort();
+    addresses.dedup();
+    let devices = addresses
+        .iter()
+        .filter(|address| settings.is_address_allowed(address))
+        .map(|address| {
+            known
+                .iter()
+                .find(|device| device.matches_address(address))
+                .cloned()
+                .unwrap_or_else(|| DeviceInfo::new(UNKNOWN_DEVICE_NAME, address.clone()))
+        })
+        .collect::<Vec<_>>();
+    if devices.is_empty() {
+        return Err(not_found(
+            "None of the named devices are allowed".to_string(),
+        ));
+    }
+    Ok(Some(devices))
+}
+
+/// Echo the command line in `data.command` so results can be matched to their input
+fn with_command(mut response: CommandResponse, line: &str) -> CommandResponse {
+    match &mut response.data {
+        Some(serde_json::Value::Object(data)) => {
+            data.insert("command".to_string(), line.into());
+        }
+        _ => response.data = Some(serde_json::json!({ "command": line })),
+    }
+    response
+}
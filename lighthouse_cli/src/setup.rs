@@ -0,0 +1,136 @@
+// Interactive first-run setup wizard
+//
+// Walks a new user through scanning, nicknaming the base stations that were found and
+// registering with SteamVR for automatic power management. Every prompt can be
+// answered with 'q' (or end-of-input) to abort, and prompts are skipped entirely
+// when stdin isn't a terminal.
+
+use std::error::Error;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use lighthouse_core::bluetooth::{scan_with_report, ScanOptions};
+use lighthouse_core::config::{load_settings, save_settings};
+use lighthouse_core::error::LighthouseError;
+use lighthouse_core::models::normalize_address;
+use lighthouse_core::steamvr_integration;
+
+/// Outcome of a single prompt
+enum Answer {
+    Text(String),
+    Abort,
+}
+
+/// Ask a question and read one line. 'q' or end-of-input aborts the wizard.
+fn prompt(question: &str) -> io::Result<Answer> {
+    print!("{} ", question);
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    if io::stdin().lock().read_line(&mut input)? == 0 {
+        return Ok(Answer::Abort);
+    }
+    let input = input.trim();
+    if input.eq_ignore_ascii_case("q") {
+        return Ok(Answer::Abort);
+    }
+    Ok(Answer::Text(input.to_string()))
+}
+
+/// Ask a yes/no question, defaulting to no. Returns `None` if the user aborted.
+fn confirm(question: &str) -> io::Result<Option<bool>> {
+    Ok(match prompt(&format!("{} (y/N, q to quit)", question))? {
+        Answer::Text(answer) => Some(answer.eq_ignore_ascii_case("y")),
+        Answer::Abort => None,
+    })
+}
+
+fn aborted() -> Result<(), Box<dyn Error>> {
+    println!("Setup aborted. Anything already saved has been kept.");
+    Ok(())
+}
+
+pub async fn run_setup(scan_options: &ScanOptions) -> Result<(), Box<dyn Error>> {
+    let interactive = io::stdin().is_terminal();
+
+    println!("lighthouse-rs setup");
+    println!("===================");
+    if !interactive {
+        println!("stdin is not a terminal, running without prompts.");
+    }
+    println!();
+
+    // Step 1: find the base stations
+    println!("Step 1: Scanning for Lighthouse base stations...");
+    let report = scan_with_report(scan_options, true).await?;
+    println!(
+        "Scanned {} Bluetooth devices, found {} base stations.",
+        report.total_seen,
+        report.devices.len()
+    );
+    for (i, device) in report.devices.iter().enumerate() {
+        println!("  {}. {} ({})", i + 1, device.name, device.address);
+    }
+    println!();
+
+    if report.devices.is_empty() {
+        println!(
+            "Make sure your base stations are plugged in and within range, then run setup again."
+        );
+        return Ok(());
+    }
+
+    if !interactive {
+        println!("Skipping nicknames and SteamVR registration.");
+        println!("Run with --register-steamvr to enable automatic power management.");
+        return Ok(());
+    }
+
+    // Step 2: nicknames, saved as soon as they're entered so an abort keeps them
+    println!("Step 2: Nicknames (press Enter to keep the current name)");
+    for device in report.devices.iter() {
+        let answer = prompt(&format!(
+            "  Nickname for {} ({}):",
+            device.name, device.address
+        ))?;
+        let nickname = match answer {
+            Answer::Text(nickname) => nickname,
+            Answer::Abort => return aborted(),
+        };
+        if nickname.is_empty() {
+            continue;
+        }
+
+        let mut settings = load_settings()?;
+        settings
+            .aliases
+            .insert(normalize_address(&device.address), nickname);
+        save_settings(&settings)?;
+    }
+    println!();
+
+    // Step 3: SteamVR registration, which is what drives automatic power management
+    println!("Step 3: Automatic power management");
+    match steamvr_integration::is_registered() {
+        Ok(true) => println!("Already registered with SteamVR."),
+        // SteamVR is missing or incomplete, so registering can't work either
+        Err(e) if e.downcast_ref::<LighthouseError>().is_some() => {
+            println!("{}", e);
+            println!("Skipped. Run with --register-steamvr once SteamVR is set up.");
+        }
+        _ => {
+            let question = "Register with SteamVR so base stations power on and off with SteamVR?";
+            match confirm(question)? {
+                Some(true) => match steamvr_integration::register_with_steamvr(false) {
+                    Ok(_) => println!("Registered with SteamVR."),
+                    Err(e) => println!("Failed to register with SteamVR: {}", e),
+                },
+                Some(false) => println!("Skipped. Run with --register-steamvr to do this later."),
+                None => return aborted(),
+            }
+        }
+    }
+    println!();
+
+    println!("Setup complete.");
+    Ok(())
+}
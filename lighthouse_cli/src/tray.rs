@@ -0,0 +1,258 @@
+// System tray icon that shows at a glance whether the base stations are on, with
+// power on, standby and toggle in its menu. Only built with the `tray` feature.
+use lighthouse_core::bluetooth::ScanOptions;
+use std::error::Error;
+
+#[cfg(feature = "tray")]
+pub use tray_icon_impl::run_tray;
+
+/// Without the `tray` feature there is nothing to show
+#[cfg(not(feature = "tray"))]
+pub async fn run_tray(_options: ScanOptions) -> Result<(), Box<dyn Error>> {
+    Err("This build has no system tray support. Rebuild with `--features tray`.".into())
+}
+
+#[cfg(feature = "tray")]
+mod tray_icon_impl {
+    use super::*;
+    use crate::cli::EXIT_GENERAL_ERROR;
+    use lighthouse_core::bluetooth::Controller;
+    use lighthouse_core::models::{DeviceInfo, PowerState};
+    use std::process;
+    use std::sync::Arc;
+    use tao::event::{Event, StartCause};
+    use tao::event_loop::{ControlFlow, EventLoopBuilder, EventLoopProxy};
+    use tokio::runtime::Handle;
+    use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+    use tray_icon::{Icon, TrayIconBuilder};
+
+    /// Power states of the cached base stations, or why they couldn't be read
+    type States = Result<Vec<(DeviceInfo, Result<PowerState, String>)>, String>;
+
+    /// Events sent to the tray's event loop
+    enum TrayEvent {
+        Menu(MenuEvent),
+        /// An operation finished, with its error if it failed, and the states read afterwards
+        Updated {
+            error: Option<String>,
+            states: States,
+        },
+    }
+
+    #[derive(Clone, Copy)]
+    enum Action {
+        PowerOn,
+        Standby,
+        Refresh,
+    }
+
+    // Icon colours for how many base stations are on
+    const ALL_ON: [u8; 3] = [46, 160, 67];
+    const SOME_ON: [u8; 3] = [219, 156, 28];
+    const NONE_ON: [u8; 3] = [128, 128, 128];
+    const FAILED: [u8; 3] = [207, 34, 46];
+
+    /// Show the tray icon until Quit is picked from its menu
+    pub async fn run_tray(options: ScanOptions) -> Result<(), Box<dyn Error>> {
+        // GTK aborts the process when there's no display, so check first
+        #[cfg(target_os = "linux")]
+        if std::env::var_os("DISPLAY").is_none() && std::env::var_os("WAYLAND_DISPLAY").is_none() {
+            return Err("The system tray needs a graphical session, and none was found".into());
+        }
+
+        let controller = Arc::new(Controller::with_options(options).await?.json_output(true));
+        let runtime = Handle::current();
+
+        let event_loop = EventLoopBuilder::<TrayEvent>::with_user_event().build();
+        let proxy = event_loop.create_proxy();
+        MenuEvent::set_event_handler(Some(move |event| {
+            proxy.send_event(TrayEvent::Menu(event)).ok();
+        }));
+
+        let status_item = MenuItem::new("Reading base stations...", false, None);
+        let power_on_item = MenuItem::new("Power on", false, None);
+        let standby_item = MenuItem::new("Standby", false, None);
+        let toggle_item = MenuItem::new("Toggle", false, None);
+        let refresh_item = MenuItem::new("Refresh", false, None);
+        let quit_item = MenuItem::new("Quit", true, None);
+        let menu = Menu::new();
+        menu.append_items(&[
+            &status_item,
+            &PredefinedMenuItem::separator(),
+            &power_on_item,
+            &standby_item,
+            &toggle_item,
+            &refresh_item,
+            &PredefinedMenuItem::separator(),
+            &quit_item,
+        ])?;
+        let action_items = [
+            power_on_item.clone(),
+            standby_item.clone(),
+            toggle_item.clone(),
+            refresh_item.clone(),
+        ];
+
+        let proxy = event_loop.create_proxy();
+        let mut tray_icon = None;
+        let mut any_on = false;
+
+        event_loop.run(move |event, _, control_flow| {
+            *control_flow = ControlFlow::Wait;
+
+            match event {
+                // macOS only allows creating the icon once the event loop is running
+                Event::NewEvents(StartCause::Init) => {
+                    let built = TrayIconBuilder::new()
+                        .with_menu(Box::new(menu.clone()))
+                        .with_tooltip("Lighthouse base stations")
+                        .with_icon(status_icon(NONE_ON))
+                        .build();
+                    match built {
+                        Ok(icon) => tray_icon = Some(icon),
+                        Err(e) => {
+                            eprintln!("No system tray is available: {}", e);
+                            process::exit(EXIT_GENERAL_ERROR);
+                        }
+                    }
+                    spawn_action(&runtime, &controller, &proxy, Action::Refresh);
+                }
+                Event::UserEvent(TrayEvent::Menu(event)) => {
+                    let action = if event.id == *quit_item.id() {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    } else if event.id == *power_on_item.id() {
+                        Action::PowerOn
+                    } else if event.id == *standby_item.id() {
+                        Action::Standby
+                    } else if event.id == *toggle_item.id() {
+                        if any_on {
+                            Action::Standby
+                        } else {
+                            Action::PowerOn
+                        }
+                    } else if event.id == *refresh_item.id() {
+                        Action::Refresh
+                    } else {
+                        return;
+                    };
+
+                    // One operation at a time. The items are enabled again once it's done.
+                    for item in &action_items {
+                        item.set_enabled(false);
+                    }
+                    status_item.set_text(match action {
+                        Action::PowerOn => "Powering on...",
+                        Action::Standby => "Putting in standby...",
+                        Action::Refresh => "Reading base stations...",
+                    });
+                    spawn_action(&runtime, &controller, &proxy, action);
+                }
+                Event::UserEvent(TrayEvent::Updated { error, states }) => {
+                    let (summary, details, color) = summarize(&states);
+                    any_on = color == ALL_ON || color == SOME_ON;
+
+                    let summary = match error {
+                        Some(e) => format!("Failed: {}", e),
+                        None => summary,
+                    };
+                    status_item.set_text(&summary);
+                    for item in &action_items {
+                        item.set_enabled(true);
+                    }
+                    if let Some(tray_icon) = &tray_icon {
+                        let tooltip = std::iter::once(summary).chain(details).collect::<Vec<_>>();
+                        tray_icon.set_tooltip(Some(tooltip.join("\n"))).ok();
+                        tray_icon.set_icon(Some(status_icon(color))).ok();
+                    }
+                }
+                _ => {}
+            }
+        })
+    }
+
+    /// Run an operation in the background, then read the states and report back
+    fn spawn_action(
+        runtime: &Handle,
+        controller: &Arc<Controller>,
+        proxy: &EventLoopProxy<TrayEvent>,
+        action: Action,
+    ) {
+        let controller = Arc::clone(controller);
+        let proxy = proxy.clone();
+        runtime.spawn(async move {
+            let error = match action {
+                Action::PowerOn => controller.power_on().await.err(),
+                Action::Standby => controller.standby().await.err(),
+                Action::Refresh => None,
+            }
+            .map(|e| e.to_string());
+            let states = controller.read_states().await.map_err(|e| e.to_string());
+            proxy.send_event(TrayEvent::Updated { error, states }).ok();
+        });
+    }
+
+    /// A one-line summary, a line per base station and the icon colour for the states
+    fn summarize(states: &States) -> (String, Vec<String>, [u8; 3]) {
+        let states = match states {
+            Ok(states) if states.is_empty() => {
+                return (
+                    "No known base stations, run --scan first".to_string(),
+                    Vec::new(),
+                    NONE_ON,
+                );
+            }
+            Ok(states) => states,
+            Err(e) => {
+                return (
+                    format!("Couldn't read the base stations: {}", e),
+                    Vec::new(),
+                    FAILED,
+                )
+            }
+        };
+
+        let on = states
+            .iter()
+            .filter(|(_, state)| matches!(state, Ok(PowerState::On | PowerState::Booting)))
+            .count();
+        let details = states
+            .iter()
+            .map(|(device, state)| match state {
+                Ok(state) => format!("{}: {}", device.name, state),
+                Err(e) => format!("{}: {}", device.name, e),
+            })
+            .collect();
+        let color = match on {
+            0 => NONE_ON,
+            on if on == states.len() => ALL_ON,
+            _ => SOME_ON,
+        };
+        (
+            format!("{} of {} base stations on", on, states.len()),
+            details,
+            color,
+        )
+    }
+
+    /// A filled circle in the given colour
+    fn status_icon(color: [u8; 3]) -> Icon {
+        const SIZE: u32 = 32;
+        let center = (SIZE as f32 - 1.0) / 2.0;
+        let radius = SIZE as f32 / 2.0 - 2.0;
+
+        let mut rgba = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let (dx, dy) = (x as f32 - center, y as f32 - center);
+                let alpha = if dx * dx + dy * dy <= radius * radius {
+                    255
+                } else {
+                    0
+                };
+                rgba.extend_from_slice(&[color[0], color[1], color[2], alpha]);
+            }
+        }
+        Icon::from_rgba(rgba, SIZE, SIZE).expect("the icon buffer matches its size")
+    }
+}
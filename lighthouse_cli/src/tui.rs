@@ -6,16 +6,20 @@ use crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, Key
 use crossterm::execute;
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
 use ratatui::backend::CrosstermBackend;
-use ratatui::layout::{Alignment, Constraint, Direction, Layout};
+use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Wrap};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap};
 use ratatui::Terminal;
 
-use lighthouse_core::bluetooth::{scan_process_and_save_with_json, POWERON_COMMAND, STANDBY_COMMAND};
-use lighthouse_core::config::load_devices;
+use lighthouse_core::bluetooth::{
+    blink_device_by_address, scan_process_and_save_with_json, ScanOptions, POWER_ON, STANDBY,
+};
+use lighthouse_core::config::{load_devices, load_settings, Settings};
 use lighthouse_core::models::DeviceInfo;
 
+use crate::cli::DEFAULT_BLINK_COUNT;
+
 pub async fn run_tui() -> Result<(), Box<dyn Error>> {
     // Setup terminal
     enable_raw_mode()?;
@@ -36,22 +40,71 @@ pub async fn run_tui() -> Result<(), Box<dyn Error>> {
     res
 }
 
+/// All key bindings, shown in the help overlay
+const KEYBINDINGS: &[(&str, &str)] = &[
+    ("Up / Down", "Select previous / next device"),
+    ("r", "Rescan for devices"),
+    ("p", "Power on all devices"),
+    ("s", "Put all devices in standby"),
+    ("i", "Blink the selected device to identify it"),
+    ("?", "Show this help"),
+    ("q / Esc", "Quit"),
+];
+
 struct AppState {
     devices: Vec<DeviceInfo>,
+    settings: Settings,
     selected: usize,
     status: String,
     last_refresh: Instant,
+    show_help: bool,
 }
 
 impl AppState {
     fn new() -> Self {
         Self {
             devices: Vec::new(),
+            settings: load_settings().unwrap_or_default(),
             selected: 0,
             status: "Press 'r' to scan for devices".to_string(),
             last_refresh: Instant::now(),
+            show_help: false,
         }
     }
+
+    /// Reload the cached devices, e.g. to show the results of a command.
+    /// The selection follows the selected device if it's still there.
+    fn reload_devices(&mut self) -> Result<(), Box<dyn Error>> {
+        let devices = load_devices()?;
+        let selected_address = self.devices.get(self.selected).map(|d| &d.address);
+        self.selected = selected_address
+            .and_then(|address| devices.iter().position(|d| &d.address == address))
+            .unwrap_or(self.selected)
+            .min(devices.len().saturating_sub(1));
+        self.devices = devices;
+        Ok(())
+    }
+}
+
+/// Compute a rectangle of the given percentage size centered within `area`
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
 }
 
 async fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<(), Box<dyn Error>> {
@@ -104,7 +157,16 @@ async fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Resul
                     .enumerate()
                     .map(|(i, d)| {
                         let marker = if i == app.selected { "> " } else { "  " };
-                        let line = format!("{}{} — {}", marker, d.name, d.address);
+                        let mut line = format!("{}{}", marker, app.settings.format_device(d));
+                        if let Some(channel) = d.channel {
+                            line.push_str(&format!("  ch {}", channel));
+                        }
+                        if let Some(rssi) = d.rssi {
+                            line.push_str(&format!("  {} dBm", rssi));
+                        }
+                        if let Some(reliability) = d.recorded_reliability() {
+                            line.push_str(&format!("  [{}]", reliability));
+                        }
                         ListItem::new(line)
                     })
                     .collect()
@@ -133,7 +195,11 @@ async fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Resul
                 Span::styled("p", Style::default().fg(Color::Yellow)),
                 Span::raw(" power on  "),
                 Span::styled("s", Style::default().fg(Color::Yellow)),
-                Span::raw(" standby"),
+                Span::raw(" standby  "),
+                Span::styled("i", Style::default().fg(Color::Yellow)),
+                Span::raw(" blink  "),
+                Span::styled("?", Style::default().fg(Color::Yellow)),
+                Span::raw(" help"),
             ]);
             let keys_para = Paragraph::new(keys_line).wrap(Wrap { trim: true });
             f.render_widget(keys_para, cols[0]);
@@ -146,28 +212,59 @@ async fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Resul
             .alignment(Alignment::Right)
             .wrap(Wrap { trim: true });
             f.render_widget(status_para, cols[1]);
+
+            // Help overlay drawn on top of everything else
+            if app.show_help {
+                let area = centered_rect(60, 60, size);
+                let lines: Vec<Line> = KEYBINDINGS
+                    .iter()
+                    .map(|(key, description)| {
+                        Line::from(vec![
+                            Span::styled(
+                                format!("{:<12}", key),
+                                Style::default().fg(Color::Yellow),
+                            ),
+                            Span::raw(*description),
+                        ])
+                    })
+                    .collect();
+                let help = Paragraph::new(lines)
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title("Key bindings (press any key to close)"),
+                    )
+                    .wrap(Wrap { trim: true });
+                f.render_widget(Clear, area);
+                f.render_widget(help, area);
+            }
         })?;
 
         // Input handling with small tick
         if event::poll(Duration::from_millis(200))? {
             if let Event::Key(KeyEvent { code, .. }) = event::read()? {
+                // Any key dismisses the help overlay
+                if app.show_help {
+                    app.show_help = false;
+                    continue;
+                }
+
                 match code {
+                    KeyCode::Char('?') => {
+                        app.show_help = true;
+                    }
                     KeyCode::Char('q') | KeyCode::Esc => {
                         break;
                     }
-                    KeyCode::Up => {
-                        if !app.devices.is_empty() {
-                            if app.selected == 0 {
-                                app.selected = app.devices.len() - 1;
-                            } else {
-                                app.selected -= 1;
-                            }
+                    KeyCode::Up if !app.devices.is_empty() => {
+                        if app.selected == 0 {
+                            app.selected = app.devices.len() - 1;
+                        } else {
+                            app.selected -= 1;
                         }
                     }
-                    KeyCode::Down => {
-                        if !app.devices.is_empty() {
-                            app.selected = (app.selected + 1) % app.devices.len();
-                        }
+                    KeyCode::Down if !app.devices.is_empty() => {
+                        app.selected = (app.selected + 1) % app.devices.len();
                     }
                     KeyCode::Char('r') => {
                         app.status = "Scanning for devices...".into();
@@ -176,12 +273,8 @@ async fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Resul
                         if let Err(e) = scan_process_and_save_with_json(0xFF, false).await {
                             app.status = format!("Scan failed: {}", e);
                         } else {
-                            match load_devices() {
-                                Ok(devs) => {
-                                    app.selected = 0;
-                                    app.devices = devs;
-                                    app.status = format!("Found {} devices", app.devices.len());
-                                }
+                            match app.reload_devices() {
+                                Ok(()) => app.status = format!("Found {} devices", app.devices.len()),
                                 Err(e) => app.status = format!("Failed to load cache: {}", e),
                             }
                         }
@@ -190,19 +283,37 @@ async fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Resul
                     KeyCode::Char('p') => {
                         app.status = "Powering on all devices...".into();
                         terminal.draw(|_| {}).ok();
-                        match scan_process_and_save_with_json(POWERON_COMMAND, false).await {
-                            Ok(_) => app.status = "Power on command sent".into(),
+                        match scan_process_and_save_with_json(POWER_ON.byte(), false).await {
+                            Ok(_) => {
+                                app.status = "Power on command sent".into();
+                                app.reload_devices().ok();
+                            }
                             Err(e) => app.status = format!("Power on failed: {}", e),
                         }
                     }
                     KeyCode::Char('s') => {
                         app.status = "Putting all devices to standby...".into();
                         terminal.draw(|_| {}).ok();
-                        match scan_process_and_save_with_json(STANDBY_COMMAND, false).await {
-                            Ok(_) => app.status = "Standby command sent".into(),
+                        match scan_process_and_save_with_json(STANDBY.byte(), false).await {
+                            Ok(_) => {
+                                app.status = "Standby command sent".into();
+                                app.reload_devices().ok();
+                            }
                             Err(e) => app.status = format!("Standby failed: {}", e),
                         }
                     }
+                    KeyCode::Char('i') => {
+                        if let Some(device) = app.devices.get(app.selected).cloned() {
+                            app.status = format!("Blinking {}...", device.name);
+                            terminal.draw(|_| {}).ok();
+                            // Logging is silenced so it doesn't draw over the TUI
+                            let options = ScanOptions::default();
+                            match blink_device_by_address(&device.address, DEFAULT_BLINK_COUNT, &options, true).await {
+                                Ok(_) => app.status = format!("Blinked {}", device.name),
+                                Err(e) => app.status = format!("Blink failed: {}", e),
+                            }
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -0,0 +1,177 @@
+use crate::bluetooth::ScanOptions;
+use crate::config::load_settings;
+use crate::error::LighthouseError;
+use crate::logging::log;
+use btleplug::api::{Central, CentralState, Manager as _, ScanFilter};
+use btleplug::platform::{Adapter, Manager};
+use serde::Serialize;
+use std::error::Error;
+use tokio::sync::{Mutex, MutexGuard};
+
+/// Held for the duration of every high-level operation that scans or connects.
+/// BlueZ, WinRT and CoreBluetooth all misbehave when one adapter is asked to scan
+/// and connect for two callers at once, so these operations run one at a time.
+static ADAPTER_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// Wait for exclusive use of the Bluetooth adapter.
+///
+/// The public scan, power, probe and blink entry points take this lock themselves,
+/// so concurrent requests (e.g. from a daemon or the FFI) are serialized in arrival
+/// order. Callers that drive the lower-level helpers such as `discover_peripherals`
+/// and `handle_device_command_with_json` directly should hold it for the whole operation.
+/// The lock is not reentrant: don't call the locking entry points while holding it.
+pub async fn lock_adapter() -> MutexGuard<'static, ()> {
+    ADAPTER_LOCK.lock().await
+}
+
+/// Description of an available Bluetooth adapter
+#[derive(Serialize, Debug, Clone)]
+pub struct AdapterDescription {
+    pub index: usize,
+    /// Platform description of the adapter (name and, where available, address)
+    pub name: String,
+}
+
+/// Get the first available Bluetooth adapter
+pub async fn first_adapter(manager: &Manager) -> Result<Adapter, Box<dyn Error>> {
+    let adapters = manager.adapters().await?;
+    adapters
+        .into_iter()
+        .next()
+        .ok_or_else(|| LighthouseError::NoAdapter.into())
+}
+
+/// List the available Bluetooth adapters
+pub async fn list_adapters(manager: &Manager) -> Result<Vec<AdapterDescription>, Box<dyn Error>> {
+    let mut descriptions = Vec::new();
+    for (index, adapter) in manager.adapters().await?.iter().enumerate() {
+        let name = adapter
+            .adapter_info()
+            .await
+            .unwrap_or_else(|_| "Unknown adapter".to_string());
+        descriptions.push(AdapterDescription { index, name });
+    }
+    Ok(descriptions)
+}
+
+/// Select an adapter by index, or by a case-insensitive (partial) name match
+pub async fn select_adapter(manager: &Manager, selector: &str) -> Result<Adapter, Box<dyn Error>> {
+    let adapters = manager.adapters().await?;
+    if adapters.is_empty() {
+        return Err(LighthouseError::NoAdapter.into());
+    }
+
+    if let Ok(index) = selector.trim().parse::<usize>() {
+        return adapters
+            .into_iter()
+            .nth(index)
+            .ok_or_else(|| LighthouseError::AdapterNotFound(selector.to_string()).into());
+    }
+
+    let needle = selector.trim().to_ascii_lowercase();
+    for adapter in adapters {
+        if let Ok(info) = adapter.adapter_info().await {
+            if info.to_ascii_lowercase().contains(&needle) {
+                return Ok(adapter);
+            }
+        }
+    }
+
+    Err(LighthouseError::AdapterNotFound(selector.to_string()).into())
+}
+
+/// Get the adapter to use: the one selected in the options, else the saved default, else the
+/// first adapter. A saved default that is no longer present falls back to the first adapter.
+pub async fn adapter_for_options(
+    manager: &Manager,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<Adapter, Box<dyn Error>> {
+    if let Some(selector) = &options.adapter {
+        return select_adapter(manager, selector).await;
+    }
+
+    let default_adapter = load_settings().ok().and_then(|s| s.default_adapter);
+    if let Some(selector) = default_adapter {
+        match select_adapter(manager, &selector).await {
+            Ok(adapter) => return Ok(adapter),
+            Err(e) => log(
+                &format!(
+                    "Default adapter unavailable ({}), using the first adapter",
+                    e
+                ),
+                json_output,
+            ),
+        }
+    }
+
+    first_adapter(manager).await
+}
+
+/// Whether the system's Bluetooth is turned off. Platforms that can't tell
+/// report Unknown, which isn't treated as off.
+pub async fn is_bluetooth_off(adapter: &Adapter) -> bool {
+    adapter.adapter_state().await.ok() == Some(CentralState::PoweredOff)
+}
+
+/// Start a BLE scan, reporting adapters that can't scan for LE devices and
+/// Bluetooth being turned off distinctly
+pub async fn start_le_scan(adapter: &Adapter) -> Result<(), Box<dyn Error>> {
+    if is_bluetooth_off(adapter).await {
+        return Err(LighthouseError::BluetoothOff.into());
+    }
+    match adapter.start_scan(ScanFilter::default()).await {
+        Ok(()) => Ok(()),
+        Err(e) if is_not_supported(&e) => {
+            Err(LighthouseError::AdapterNotLeCapable(e.to_string()).into())
+        }
+        Err(e) => Err(check_adapter_lost(adapter, e).await),
+    }
+}
+
+/// Fragments of the platform messages reported once an adapter has been removed
+const ADAPTER_LOST_MARKERS: &[&str] = &[
+    "org.bluez.error.notready",
+    "no such adapter",
+    "adapter not found",
+    "adapter was removed",
+    "device is not ready",
+    "device not ready",
+];
+
+/// Whether an error from a scan or connection means the adapter itself has gone away
+pub fn is_adapter_lost(error: &(dyn Error + 'static)) -> bool {
+    if matches!(
+        error.downcast_ref::<LighthouseError>(),
+        Some(LighthouseError::AdapterLost)
+    ) {
+        return true;
+    }
+    let message = error.to_string().to_ascii_lowercase();
+    ADAPTER_LOST_MARKERS
+        .iter()
+        .any(|marker| message.contains(marker))
+}
+
+/// Turn a platform error from an operation on `adapter` into `LighthouseError::AdapterLost`
+/// if the adapter has disappeared, either by its message or because it no longer answers at all
+pub async fn check_adapter_lost(adapter: &Adapter, error: btleplug::Error) -> Box<dyn Error> {
+    if is_adapter_lost(&error) || adapter.adapter_info().await.is_err() {
+        LighthouseError::AdapterLost.into()
+    } else {
+        error.into()
+    }
+}
+
+/// Whether a btleplug error means the adapter doesn't support the operation
+fn is_not_supported(error: &btleplug::Error) -> bool {
+    match error {
+        btleplug::Error::NotSupported(_) => true,
+        // Platform backends often surface this as a generic error with the OS message
+        btleplug::Error::Other(e) => {
+            let message = e.to_string().to_ascii_lowercase();
+            message.contains("notsupported") || message.contains("not supported")
+        }
+        _ => false,
+    }
+}
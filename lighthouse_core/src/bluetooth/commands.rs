@@ -0,0 +1,146 @@
+use crate::bluetooth::{
+    IDENTIFY_CHAR_UUID, IDENTIFY_COMMAND, LIGHTHOUSE_CHAR_UUID, POWERON_COMMAND, SLEEP_COMMAND,
+    STANDBY_COMMAND,
+};
+use crate::models::PowerState;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// A command that can be written to a base station. Everything that depends on
+/// the kind of command (what to write where, what to call it, how to tell it
+/// worked) lives here, so a new command only needs a new entry in `DEVICE_COMMANDS`.
+#[derive(Debug)]
+pub struct DeviceCommand {
+    /// Identifier used on the command line, in the history and in hook variables
+    pub id: &'static str,
+    /// Name used in log messages
+    pub name: &'static str,
+    /// One-line explanation of what the command does, for listings
+    pub description: &'static str,
+    /// Characteristic the command is written to
+    pub characteristic: Uuid,
+    /// Bytes written to the characteristic
+    pub bytes: &'static [u8],
+    /// Whether a device in the given power state has done what the command asks.
+    /// `None` for commands that don't change the power state.
+    pub reached_by: Option<fn(PowerState) -> bool>,
+    /// Where the firmware reports whether it accepted the command, for
+    /// `ScanOptions::confirm_accepted`. No base station firmware is known to have
+    /// one yet, so none of the built-in commands set it.
+    pub status: Option<CommandStatus>,
+}
+
+/// A characteristic that reports whether the device accepted the last command,
+/// beyond the write being acknowledged
+#[derive(Debug)]
+pub struct CommandStatus {
+    pub characteristic: Uuid,
+    /// Whether the bytes read from the characteristic mean the command was accepted
+    pub accepted: fn(&[u8]) -> bool,
+}
+
+impl DeviceCommand {
+    /// The command byte, for functions that take a power command as a `u8`
+    pub fn byte(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    /// Whether the command changes the power state, so it can be verified
+    pub fn changes_power(&self) -> bool {
+        self.reached_by.is_some()
+    }
+
+    /// Whether a device in `state` has done what the command asks.
+    /// A booting device counts as on, and sleep counts as standby.
+    pub fn is_reached_by(&self, state: PowerState) -> bool {
+        self.reached_by.is_some_and(|reached_by| reached_by(state))
+    }
+
+    /// Serializable description of the command
+    pub fn summary(&self) -> CommandSummary {
+        CommandSummary {
+            id: self.id,
+            name: self.name,
+            description: self.description,
+            verifiable: self.changes_power(),
+        }
+    }
+}
+
+/// What a command does, for tools that discover the available commands at runtime
+#[derive(Serialize, Debug, Clone)]
+pub struct CommandSummary {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Whether the result can be confirmed by reading back the power state
+    pub verifiable: bool,
+}
+
+pub static POWER_ON: DeviceCommand = DeviceCommand {
+    id: "poweron",
+    name: "power on",
+    description: "Wake the base station and start its rotors and lasers",
+    characteristic: LIGHTHOUSE_CHAR_UUID,
+    bytes: &[POWERON_COMMAND],
+    reached_by: Some(|state| matches!(state, PowerState::On | PowerState::Booting)),
+    status: None,
+};
+
+pub static STANDBY: DeviceCommand = DeviceCommand {
+    id: "standby",
+    name: "standby",
+    description: "Put the base station in standby, stopping its rotors and lasers",
+    characteristic: LIGHTHOUSE_CHAR_UUID,
+    bytes: &[STANDBY_COMMAND],
+    reached_by: Some(|state| matches!(state, PowerState::Standby | PowerState::Sleep)),
+    status: None,
+};
+
+pub static SLEEP: DeviceCommand = DeviceCommand {
+    id: "sleep",
+    name: "sleep",
+    description:
+        "Put the base station in deep sleep, which uses less power than standby but wakes slower",
+    characteristic: LIGHTHOUSE_CHAR_UUID,
+    bytes: &[SLEEP_COMMAND],
+    reached_by: Some(|state| state == PowerState::Sleep),
+    status: None,
+};
+
+pub static IDENTIFY: DeviceCommand = DeviceCommand {
+    id: "identify",
+    name: "identify",
+    description: "Flash the base station's LED so it can be found",
+    characteristic: IDENTIFY_CHAR_UUID,
+    bytes: &[IDENTIFY_COMMAND],
+    reached_by: None,
+    status: None,
+};
+
+/// Every command a base station accepts
+pub static DEVICE_COMMANDS: &[&DeviceCommand] = &[&POWER_ON, &STANDBY, &SLEEP, &IDENTIFY];
+
+/// Describe every command a base station accepts
+pub fn describe_commands() -> Vec<CommandSummary> {
+    DEVICE_COMMANDS
+        .iter()
+        .map(|command| command.summary())
+        .collect()
+}
+
+/// Look up a command by its identifier, e.g. `poweron`
+pub fn find_command(id: &str) -> Option<&'static DeviceCommand> {
+    DEVICE_COMMANDS
+        .iter()
+        .copied()
+        .find(|command| command.id.eq_ignore_ascii_case(id))
+}
+
+/// Look up the command that writes `byte` to the power characteristic
+pub fn power_command(byte: u8) -> Option<&'static DeviceCommand> {
+    DEVICE_COMMANDS
+        .iter()
+        .copied()
+        .find(|command| command.characteristic == LIGHTHOUSE_CHAR_UUID && command.bytes == [byte])
+}
@@ -0,0 +1,164 @@
+// A Bluetooth manager and adapter kept across operations, for library consumers
+// that scan and command several times. The free functions set both up on every call.
+use crate::bluetooth::adapter::{adapter_for_options, lock_adapter};
+use crate::bluetooth::device_control::{
+    command_devices_with_opts, command_lighthouses_on, read_all_states_on, read_states_on,
+};
+use crate::bluetooth::scanning::{discover_peripherals, scan_and_report_on};
+use crate::bluetooth::{ScanOptions, POWER_ON, STANDBY};
+use crate::error::LighthouseError;
+use crate::logging::log;
+use crate::models::{match_peripherals, DeviceInfo, PowerState, ScanReport};
+use btleplug::api::Peripheral as _;
+use btleplug::platform::{Adapter, Manager, Peripheral};
+use std::error::Error;
+use std::time::Instant;
+
+/// Controls base stations through one adapter that is looked up once.
+/// Create it with `Controller::new().await?`, then call `scan`, `power_on` and so on.
+pub struct Controller {
+    // The adapter belongs to the manager, so keep it alive as long as the adapter is used
+    _manager: Manager,
+    adapter: Adapter,
+    options: ScanOptions,
+    json_output: bool,
+}
+
+impl Controller {
+    /// Set up Bluetooth with the default scan options
+    pub async fn new() -> Result<Self, Box<dyn Error>> {
+        Self::with_options(ScanOptions::default()).await
+    }
+
+    /// Set up Bluetooth, picking the adapter named in `options` like the free functions do
+    pub async fn with_options(options: ScanOptions) -> Result<Self, Box<dyn Error>> {
+        let manager = Manager::new().await?;
+        let adapter = adapter_for_options(&manager, &options, false).await?;
+        Ok(Self {
+            _manager: manager,
+            adapter,
+            options,
+            json_output: false,
+        })
+    }
+
+    /// Suppress progress messages on stdout, like the `_with_json` functions do
+    pub fn json_output(mut self, json_output: bool) -> Self {
+        self.json_output = json_output;
+        self
+    }
+
+    /// The adapter every operation uses
+    pub fn adapter(&self) -> &Adapter {
+        &self.adapter
+    }
+
+    /// The scan options every operation uses
+    pub fn options(&self) -> &ScanOptions {
+        &self.options
+    }
+
+    /// Change the scan options for later operations. The adapter stays the one
+    /// picked when the controller was created.
+    pub fn options_mut(&mut self) -> &mut ScanOptions {
+        &mut self.options
+    }
+
+    /// Scan for nearby devices without filtering or saving them
+    pub async fn discover(&self) -> Result<Vec<Peripheral>, Box<dyn Error>> {
+        let _adapter_guard = lock_adapter().await;
+        discover_peripherals(&self.adapter, &self.options, self.json_output).await
+    }
+
+    /// Scan and save the base stations found, like `scan_with_report`
+    pub async fn scan(&self) -> Result<ScanReport, Box<dyn Error>> {
+        let _adapter_guard = lock_adapter().await;
+        scan_and_report_on(
+            &self.adapter,
+            0xFF,
+            &self.options,
+            Instant::now(),
+            self.json_output,
+        )
+        .await
+    }
+
+    /// Power on every nearby base station, like `power_on_lighthouses_with_opts`
+    pub async fn power_on(&self) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
+        log("Powering on lighthouses...", self.json_output);
+        let _adapter_guard = lock_adapter().await;
+        command_lighthouses_on(&self.adapter, &POWER_ON, &self.options, self.json_output).await
+    }
+
+    /// Put every nearby base station in standby, like `standby_lighthouses_with_opts`
+    pub async fn standby(&self) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
+        log("Putting lighthouses in standby mode...", self.json_output);
+        let _adapter_guard = lock_adapter().await;
+        command_lighthouses_on(&self.adapter, &STANDBY, &self.options, self.json_output).await
+    }
+
+    /// Read the power state of every cached base station, like `read_all_states`
+    pub async fn read_states(
+        &self,
+    ) -> Result<Vec<(DeviceInfo, Result<PowerState, String>)>, Box<dyn Error>> {
+        let _adapter_guard = lock_adapter().await;
+        read_all_states_on(&self.adapter, &self.options, self.json_output).await
+    }
+
+    /// Read the power state of just the given devices
+    pub async fn read_states_of(
+        &self,
+        devices: &[DeviceInfo],
+    ) -> Result<Vec<(DeviceInfo, Result<PowerState, String>)>, Box<dyn Error>> {
+        let _adapter_guard = lock_adapter().await;
+        read_states_on(
+            &self.adapter,
+            devices.to_vec(),
+            &self.options,
+            self.json_output,
+        )
+        .await
+    }
+
+    /// Send a power command to just the given devices. Devices the scan didn't find
+    /// count as failed, so the error is `DevicesFailed` whether a device was missing
+    /// or didn't take the command.
+    pub async fn command_devices(
+        &self,
+        devices: &[DeviceInfo],
+        command: u8,
+    ) -> Result<(), Box<dyn Error>> {
+        let _adapter_guard = lock_adapter().await;
+        let peripherals =
+            discover_peripherals(&self.adapter, &self.options, self.json_output).await?;
+        let scanned: Vec<(Peripheral, String)> = peripherals
+            .iter()
+            .map(|peripheral| (peripheral.clone(), peripheral.address().to_string()))
+            .collect();
+        let (found, missing) = match_peripherals(devices, &scanned);
+
+        let mut failed: Vec<String> = missing.into_iter().map(|device| device.address).collect();
+        if !found.is_empty() {
+            if let Err(e) =
+                command_devices_with_opts(&found, command, &self.options, self.json_output).await
+            {
+                match e.downcast_ref::<LighthouseError>() {
+                    Some(LighthouseError::DevicesFailed {
+                        failed: not_done, ..
+                    }) => failed.extend(not_done.iter().cloned()),
+                    _ => return Err(e),
+                }
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(LighthouseError::DevicesFailed {
+                failed,
+                attempted: devices.len(),
+            }
+            .into())
+        }
+    }
+}
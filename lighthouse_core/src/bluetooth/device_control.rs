@@ -1,16 +1,32 @@
+use crate::bluetooth::adapter::{adapter_for_options, is_adapter_lost, lock_adapter};
+use crate::bluetooth::guards::{ConnectionGuard, ScanGuard};
+use crate::bluetooth::scanning::scan_and_report_on;
 use crate::bluetooth::{
-    LIGHTHOUSE_CHAR_UUID, LIGHTHOUSE_SERVICE_UUID, LHB_PREFIX, LIGHTHOUSE_MANUFACTURER_ID,
-    POWERON_COMMAND, STANDBY_COMMAND,
+    detect_lighthouse, discover_peripherals, power_command, read_properties,
+    wait_for_known_lighthouses, DeviceCommand, ScanOptions, CHANNEL_CHAR_UUID,
+    FIRMWARE_REVISION_CHAR_UUID, IDENTIFY, IDENTIFY_CHAR_UUID, LIGHTHOUSE_CHAR_UUID,
+    LIGHTHOUSE_SERVICE_UUID, MAX_CHANNEL, MIN_CHANNEL, POWERON_COMMAND, POWER_ON,
+    SERIAL_NUMBER_CHAR_UUID, SLEEP_COMMAND, STANDBY, STANDBY_COMMAND,
 };
-use crate::config::save_devices;
+use crate::config::{
+    find_device, load_devices_with_json, load_settings, save_devices_with_json,
+    save_scanned_devices_with_json,
+};
+use crate::error::LighthouseError;
+use crate::events::{record_event, EventKind};
 use crate::logging::{error_log, log};
-use crate::models::DeviceInfo;
-use btleplug::api::{
-    Central, CharPropFlags, Characteristic, Manager as _, Peripheral as _, WriteType,
+use crate::messages::{msg, msgf, Msg};
+use crate::models::{
+    normalize_address, BenchmarkReport, DeviceBenchmark, DeviceCapabilities, DeviceInfo,
+    DeviceReliability, GattCharacteristic, GattService, PowerState, RecoveryReport, StateBytes,
+    TimingStats, UNKNOWN_DEVICE_NAME,
 };
-use btleplug::platform::{Manager, Peripheral};
+use btleplug::api::{Central, CharPropFlags, Characteristic, Peripheral as _, Service, WriteType};
+use btleplug::platform::{Adapter, Manager, Peripheral};
+use futures::stream::{self, StreamExt};
+use std::collections::BTreeSet;
 use std::error::Error;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::time;
 
 /// Send a command to a device
@@ -28,22 +44,94 @@ pub async fn send_command_to_device_with_json(
     command: u8,
     json_output: bool,
 ) -> Result<(), Box<dyn Error>> {
-    let device_name = match peripheral.properties().await? {
-        Some(props) => props.local_name.unwrap_or_else(|| "Unknown".to_string()),
-        None => "Unknown".to_string(),
-    };
+    let options = ScanOptions::default();
+    send_command_to_device_with_opts(peripheral, command, &options, json_output).await
+}
+
+/// Delay before the second connection attempt in `send_command_to_device_with_opts`,
+/// doubled for each attempt after that
+const CONNECT_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Same as `send_command_to_device_with_json`, retrying service discovery as often as
+/// `options.discovery_retries` allows. If connecting, discovering or writing fails, the
+/// whole sequence is tried again with exponential backoff, up to `options.connect_attempts`
+/// times in total.
+pub async fn send_command_to_device_with_opts(
+    peripheral: &Peripheral,
+    command: u8,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let device_name = read_properties(peripheral, json_output)
+        .await
+        .and_then(|props| props.local_name)
+        .unwrap_or_else(|| UNKNOWN_DEVICE_NAME.to_string());
 
-    let command_name = match command {
-        STANDBY_COMMAND => "standby (0x00)",
-        POWERON_COMMAND => "power on (0x01)",
-        _ => "unknown",
+    let command_name = match power_command(command) {
+        Some(device_command) => format!("{} (0x{:02x})", device_command.name, command),
+        None => "unknown".to_string(),
     };
 
+    let attempts = options.connect_attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        // The error is dropped before sleeping, which keeps the future Send
+        let delay = {
+            let error = match send_command_once(
+                peripheral,
+                command,
+                options,
+                &device_name,
+                &command_name,
+                json_output,
+            )
+            .await
+            {
+                Ok(()) => return Ok(()),
+                Err(e) => e,
+            };
+            // Trying again can't help once the adapter is gone or the device refused the command
+            let rejected = matches!(
+                error.downcast_ref::<LighthouseError>(),
+                Some(LighthouseError::CommandRejected { .. })
+            );
+            if attempt >= attempts || rejected || is_adapter_lost(error.as_ref()) {
+                return Err(error);
+            }
+
+            let delay = CONNECT_RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+            log(
+                &format!(
+                    "Attempt {} of {} failed for {} ({}), retrying in {}ms...",
+                    attempt,
+                    attempts,
+                    device_name,
+                    error,
+                    delay.as_millis()
+                ),
+                json_output,
+            );
+            delay
+        };
+        time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Connect, discover services and write the command once
+async fn send_command_once(
+    peripheral: &Peripheral,
+    command: u8,
+    options: &ScanOptions,
+    device_name: &str,
+    command_name: &str,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
     log(&format!("Connecting to {}...", device_name), json_output);
 
-    // Connect to the device
-    if !peripheral.is_connected().await? {
-        peripheral.connect().await?;
+    // Connect to the device. The guard disconnects again if anything below fails.
+    let connection = ConnectionGuard::exclusive(peripheral).await?;
+    if !connection.was_connected() {
         log(&format!("Connected to {}", device_name), json_output);
     } else {
         log(
@@ -53,14 +141,17 @@ pub async fn send_command_to_device_with_json(
     }
 
     // Discover services
-    peripheral.discover_services().await?;
+    let services = discover_writable_services(
+        peripheral,
+        options.discovery_retries,
+        device_name,
+        json_output,
+    )
+    .await?;
     log(
         &format!("Discovered services for {}", device_name),
         json_output,
     );
-
-    // Get device services
-    let services = peripheral.services();
     log(
         &format!("Found {} services for {}", services.len(), device_name),
         json_output,
@@ -127,17 +218,13 @@ pub async fn send_command_to_device_with_json(
             .await?;
 
         log(
-            &format!(
-                "{} command sent successfully to {}",
-                if command == STANDBY_COMMAND {
-                    "Standby"
-                } else {
-                    "Power on"
-                },
-                device_name
-            ),
+            &format!("Sent {} command to {}", command_name, device_name),
             json_output,
         );
+
+        if let (true, Some(device_command)) = (options.confirm_accepted, power_command(command)) {
+            check_command_accepted(peripheral, device_command, device_name, json_output).await?;
+        }
     } else {
         log(
             &format!(
@@ -150,237 +237,1584 @@ pub async fn send_command_to_device_with_json(
     }
 
     // Disconnect from the device
-    peripheral.disconnect().await?;
+    connection.disconnect().await?;
     log(&format!("Disconnected from {}", device_name), json_output);
 
     Ok(())
 }
 
-/// Handle device commands for multiple devices
-pub async fn handle_device_command(
-    devices: &[Peripheral],
-    command: u8,
-) -> Result<(), Box<dyn Error>> {
-    // Default to non-JSON output for internal calls
-    handle_device_command_with_json(devices, command, false).await
-}
-
-/// Handle device commands for multiple devices with JSON output control
-pub async fn handle_device_command_with_json(
-    devices: &[Peripheral],
-    command: u8,
+/// Read the command's status characteristic and fail with `CommandRejected` if the
+/// device reports it didn't accept the command. Skipped when the command has no status
+/// characteristic or the device doesn't expose it. Services must be discovered.
+async fn check_command_accepted(
+    peripheral: &Peripheral,
+    command: &DeviceCommand,
+    device_name: &str,
     json_output: bool,
 ) -> Result<(), Box<dyn Error>> {
-    let command_name = match command {
-        STANDBY_COMMAND => "standby",
-        POWERON_COMMAND => "power on",
-        _ => "unknown operation",
+    let Some(status) = &command.status else {
+        return Ok(());
+    };
+    let characteristic = peripheral
+        .characteristics()
+        .into_iter()
+        .find(|c| c.uuid == status.characteristic && c.properties.contains(CharPropFlags::READ));
+    let Some(characteristic) = characteristic else {
+        log(
+            &format!(
+                "{} has no status characteristic, not checking that it accepted the command",
+                device_name
+            ),
+            json_output,
+        );
+        return Ok(());
     };
 
-    log(
-        &format!(
-            "Sending {} command to {} Lighthouse devices...",
-            command_name,
-            devices.len()
-        ),
-        json_output,
-    );
-
-    for (i, device) in devices.iter().enumerate() {
+    let response = peripheral.read(&characteristic).await?;
+    if (status.accepted)(&response) {
         log(
-            &format!("Processing device {} of {}...", i + 1, devices.len()),
+            &format!("{} accepted the {} command", device_name, command.name),
             json_output,
         );
+        return Ok(());
+    }
+    Err(LighthouseError::CommandRejected {
+        device: device_name.to_string(),
+        command: command.name,
+        status: response.iter().map(|b| format!("{:02x}", b)).collect(),
+    }
+    .into())
+}
 
-        match send_command_to_device_with_json(device, command, json_output).await {
-            Ok(_) => log(
-                &format!(
-                    "Successfully sent {} command to device {}",
-                    command_name,
-                    i + 1
-                ),
-                json_output,
-            ),
-            Err(e) => log(
-                &format!(
-                    "Failed to send {} command to device {}: {}",
-                    command_name,
-                    i + 1,
-                    e
-                ),
-                json_output,
-            ),
+/// Delay between service discovery attempts in `discover_writable_services`
+const DISCOVERY_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Discover services, trying again up to `retries` times while discovery comes back
+/// empty or without a writable characteristic. Some adapters intermittently return a
+/// partial list for a valid device, which would otherwise fail the command.
+async fn discover_writable_services(
+    peripheral: &Peripheral,
+    retries: u32,
+    device_name: &str,
+    json_output: bool,
+) -> Result<BTreeSet<Service>, Box<dyn Error>> {
+    let mut attempt = 0;
+    loop {
+        peripheral.discover_services().await?;
+        let services = peripheral.services();
+        let writable = services
+            .iter()
+            .flat_map(|service| service.characteristics.iter())
+            .any(|c| {
+                c.properties
+                    .intersects(CharPropFlags::WRITE | CharPropFlags::WRITE_WITHOUT_RESPONSE)
+            });
+        if writable || attempt >= retries {
+            return Ok(services);
         }
 
-        // Add a small delay between devices to avoid overwhelming the Bluetooth adapter
-        time::sleep(Duration::from_millis(500)).await;
+        attempt += 1;
+        log(
+            &format!(
+                "No writable characteristic found on {}, discovering services again ({}/{})...",
+                device_name, attempt, retries
+            ),
+            json_output,
+        );
+        time::sleep(DISCOVERY_RETRY_DELAY).await;
     }
+}
 
-    log(
-        &format!("{} operation completed", command_name),
-        json_output,
-    );
-    Ok(())
+/// Read the serial number from the Device Information Service.
+/// Returns `None` if the device doesn't expose one.
+pub async fn read_serial_number(peripheral: &Peripheral) -> Result<Option<String>, Box<dyn Error>> {
+    let connection = ConnectionGuard::connect(peripheral).await?;
+
+    // Send + Sync keeps the future Send while the result is held across the disconnect
+    let result: Result<Option<String>, Box<dyn Error + Send + Sync>> = async {
+        peripheral.discover_services().await?;
+        let characteristic = peripheral
+            .characteristics()
+            .into_iter()
+            .find(|c| c.uuid == SERIAL_NUMBER_CHAR_UUID);
+
+        match characteristic {
+            Some(characteristic) => {
+                let serial = dis_string(&peripheral.read(&characteristic).await?);
+                Ok(Some(serial).filter(|s| !s.is_empty()))
+            }
+            None => Ok(None),
+        }
+    }
+    .await;
+
+    // Leave the connection as we found it, even if the read failed
+    connection.disconnect().await.ok();
+
+    result.map_err(|e| e as Box<dyn Error>)
 }
 
-/// Power on lighthouses (called when SteamVR starts)
-#[allow(dead_code)]
-pub async fn power_on_lighthouses() -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
-    // Default to non-JSON output for internal calls
-    power_on_lighthouses_with_json(false).await
+/// Read the channel a base station is on from its channel characteristic.
+/// Returns `None` for devices without one.
+pub async fn read_channel(peripheral: &Peripheral) -> Result<Option<u8>, Box<dyn Error>> {
+    let connection = ConnectionGuard::connect(peripheral).await?;
+
+    // Send + Sync keeps the future Send while the result is held across the disconnect
+    let result: Result<Option<u8>, Box<dyn Error + Send + Sync>> = async {
+        peripheral.discover_services().await?;
+        let characteristic = peripheral
+            .characteristics()
+            .into_iter()
+            .find(|c| c.uuid == CHANNEL_CHAR_UUID);
+
+        match characteristic {
+            Some(characteristic) => {
+                let bytes = peripheral.read(&characteristic).await?;
+                Ok(Some(*bytes.first().ok_or("Empty channel response")?))
+            }
+            None => Ok(None),
+        }
+    }
+    .await;
+
+    connection.disconnect().await.ok();
+
+    result.map_err(|e| e as Box<dyn Error>)
 }
 
-/// Power on lighthouses with JSON output control
-/// Returns the list of devices that were found and powered on
-pub async fn power_on_lighthouses_with_json(json_output: bool) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
-    log("Powering on lighthouses...", json_output);
+/// Switch a base station to another channel, then read the channel back to confirm
+/// the device took it. Channels outside `MIN_CHANNEL..=MAX_CHANNEL` are rejected
+/// without connecting.
+pub async fn set_channel(peripheral: &Peripheral, channel: u8) -> Result<(), Box<dyn Error>> {
+    if !(MIN_CHANNEL..=MAX_CHANNEL).contains(&channel) {
+        return Err(format!(
+            "Channel {} is out of range, it must be between {} and {}",
+            channel, MIN_CHANNEL, MAX_CHANNEL
+        )
+        .into());
+    }
 
-    // Initialize Bluetooth
-    let manager = Manager::new().await?;
-    let adapters = manager.adapters().await?;
+    let connection = ConnectionGuard::connect(peripheral).await?;
+
+    // Send + Sync keeps the future Send while the result is held across the disconnect
+    let result: Result<(), Box<dyn Error + Send + Sync>> = async {
+        peripheral.discover_services().await?;
+        let characteristic = peripheral
+            .characteristics()
+            .into_iter()
+            .find(|c| c.uuid == CHANNEL_CHAR_UUID)
+            .ok_or("Device does not support setting the channel")?;
+
+        peripheral
+            .write(&characteristic, &[channel], WriteType::WithResponse)
+            .await?;
 
-    if adapters.is_empty() {
-        error_log("No Bluetooth adapters found", json_output);
-        return Err("No Bluetooth adapters found".into());
+        let bytes = peripheral.read(&characteristic).await?;
+        match bytes.first() {
+            Some(&read_back) if read_back == channel => Ok(()),
+            Some(&read_back) => Err(format!(
+                "Device reports channel {} after being set to {}",
+                read_back, channel
+            )
+            .into()),
+            None => Err("Empty channel response".into()),
+        }
     }
+    .await;
 
-    let adapter = &adapters[0];
-    log(
-        &format!("Using adapter: {}", adapter.adapter_info().await?),
-        json_output,
-    );
+    connection.disconnect().await.ok();
 
-    // Start scanning for devices
-    log("Scanning for Lighthouse devices...", json_output);
-    adapter
-        .start_scan(btleplug::api::ScanFilter::default())
-        .await?;
-    time::sleep(Duration::from_secs(3)).await;
+    result.map_err(|e| e as Box<dyn Error>)
+}
 
-    let peripherals = adapter.peripherals().await?;
-    adapter.stop_scan().await?;
+/// Scan for the base station with the given address, switch it to `channel` and
+/// save the new channel with the cached device
+pub async fn set_channel_by_address(
+    address: &str,
+    channel: u8,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<DeviceInfo, Box<dyn Error>> {
+    let _adapter_guard = lock_adapter().await;
+    let manager = Manager::new().await?;
+    let adapter = adapter_for_options(&manager, options, json_output).await?;
 
-    // Find lighthouse devices by checking manufacturer ID and name prefix
-    let mut lighthouse_devices = Vec::new();
-    let mut device_info_list = Vec::new();
+    let peripheral = find_peripheral_by_address(&adapter, address, options, json_output).await?;
+    let address = normalize_address(address);
 
-    for peripheral in peripherals.iter() {
-        if let Ok(Some(properties)) = peripheral.properties().await {
-            let name = properties.local_name.clone().unwrap_or_default();
-            
-            // Check if this is a lighthouse device
-            let is_lighthouse = name.starts_with(LHB_PREFIX) &&
-                properties.manufacturer_data.iter().any(|(id, _)| *id == LIGHTHOUSE_MANUFACTURER_ID);
-            
-            if is_lighthouse {
-                lighthouse_devices.push(peripheral.clone());
-                device_info_list.push(DeviceInfo {
-                    name: name.clone(),
-                    address: peripheral.address().to_string(),
-                });
-                log(&format!("Found lighthouse: {} ({})", name, peripheral.address()), json_output);
+    set_channel(&peripheral, channel).await?;
+
+    if !options.no_save {
+        let mut devices = load_devices_with_json(true).unwrap_or_default();
+        if let Some(device) = devices.iter_mut().find(|d| d.matches_address(&address)) {
+            device.channel = Some(channel);
+            if let Err(e) = save_devices_with_json(&devices, true) {
+                log(
+                    &format!("Failed to save the new channel: {}", e),
+                    json_output,
+                );
             }
         }
     }
 
-    if lighthouse_devices.is_empty() {
-        log("No Lighthouse devices found", json_output);
-        return Ok(Vec::new());
-    }
+    let name = read_properties(&peripheral, json_output)
+        .await
+        .and_then(|p| p.local_name)
+        .unwrap_or_else(|| UNKNOWN_DEVICE_NAME.to_string());
+    Ok(DeviceInfo {
+        channel: Some(channel),
+        ..DeviceInfo::new(name, address)
+    })
+}
 
-    log(
-        &format!("Found {} Lighthouse devices, saving and powering on...", lighthouse_devices.len()),
-        json_output,
-    );
+/// Decode a Device Information Service string, which some firmware pads with NULs
+fn dis_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches('\0')
+        .trim()
+        .to_string()
+}
 
-    // Save the discovered devices
-    if let Err(e) = save_devices(&device_info_list) {
-        log(&format!("Failed to save devices: {}", e), json_output);
+/// Read the current power state from the power characteristic
+pub async fn read_power_state(peripheral: &Peripheral) -> Result<PowerState, Box<dyn Error>> {
+    let connection = ConnectionGuard::connect(peripheral).await?;
+
+    // Send + Sync keeps the future Send while the result is held across the disconnect
+    let result = async {
+        let characteristic = power_state_characteristic(peripheral).await?;
+        read_power_state_from(peripheral, &characteristic).await
     }
+    .await;
 
-    // Send the power on command to all found devices
-    handle_device_command_with_json(&lighthouse_devices, POWERON_COMMAND, json_output).await?;
+    connection.disconnect().await.ok();
 
-    Ok(device_info_list)
+    result.map_err(|e| e as Box<dyn Error>)
 }
 
-/// Put lighthouses in standby mode (called when SteamVR stops)
-#[allow(dead_code)]
-pub async fn standby_lighthouses() -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
-    // Default to non-JSON output for internal calls
-    standby_lighthouses_with_json(false).await
+/// Find the readable power characteristic of a connected device
+async fn power_state_characteristic(
+    peripheral: &Peripheral,
+) -> Result<Characteristic, Box<dyn Error + Send + Sync>> {
+    peripheral.discover_services().await?;
+    let characteristic = peripheral
+        .characteristics()
+        .into_iter()
+        .find(|c| c.uuid == LIGHTHOUSE_CHAR_UUID && c.properties.contains(CharPropFlags::READ))
+        .ok_or("Device does not report its power state")?;
+    Ok(characteristic)
 }
 
-/// Put lighthouses in standby mode with JSON output control
-/// Returns the list of devices that were found and put in standby
-pub async fn standby_lighthouses_with_json(json_output: bool) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
-    log("Putting lighthouses in standby mode...", json_output);
+async fn read_power_state_from(
+    peripheral: &Peripheral,
+    characteristic: &Characteristic,
+) -> Result<PowerState, Box<dyn Error + Send + Sync>> {
+    Ok(PowerState::from_byte(
+        read_power_byte_from(peripheral, characteristic).await?,
+    ))
+}
 
-    // Initialize Bluetooth
-    let manager = Manager::new().await?;
-    let adapters = manager.adapters().await?;
+async fn read_power_byte_from(
+    peripheral: &Peripheral,
+    characteristic: &Characteristic,
+) -> Result<u8, Box<dyn Error + Send + Sync>> {
+    let bytes = peripheral.read(characteristic).await?;
+    Ok(*bytes.first().ok_or("Empty power state response")?)
+}
 
-    if adapters.is_empty() {
-        error_log("No Bluetooth adapters found", json_output);
-        return Err("No Bluetooth adapters found".into());
+/// Power state values to verify a connected device with, whose services are already
+/// discovered. The firmware revision is only read when the settings override the
+/// values for some revisions.
+async fn verification_state_bytes(peripheral: &Peripheral) -> StateBytes {
+    let settings = load_settings().unwrap_or_default();
+    if settings.state_bytes.is_empty() {
+        return StateBytes::default();
     }
+    let characteristic = peripheral
+        .characteristics()
+        .into_iter()
+        .find(|c| c.uuid == FIRMWARE_REVISION_CHAR_UUID);
+    let revision = match characteristic {
+        Some(characteristic) => peripheral
+            .read(&characteristic)
+            .await
+            .ok()
+            .map(|bytes| dis_string(&bytes)),
+        None => None,
+    };
+    settings.state_bytes_for(revision.as_deref())
+}
 
-    let adapter = &adapters[0];
-    log(
-        &format!("Using adapter: {}", adapter.adapter_info().await?),
-        json_output,
-    );
+/// Base delay between state reads in `await_state`, and the most random jitter added to it
+const AWAIT_STATE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const AWAIT_STATE_POLL_JITTER: Duration = Duration::from_millis(250);
 
-    // Start scanning for devices
-    log("Scanning for Lighthouse devices...", json_output);
-    adapter
-        .start_scan(btleplug::api::ScanFilter::default())
-        .await?;
-    time::sleep(Duration::from_secs(3)).await;
+/// Poll the power state until the device settles where `command` puts it, or `timeout`
+/// passes. Intermediate states such as booting don't count as failures, so a device that
+/// goes booting -> on succeeds; it only fails if it's still in the wrong or an unsettled
+/// state at the timeout. Returns the state it settled in. States are decoded with the
+/// `state_bytes` of the device's firmware revision where the settings have some.
+pub async fn await_state(
+    peripheral: &Peripheral,
+    command: u8,
+    timeout: Duration,
+) -> Result<PowerState, Box<dyn Error>> {
+    let connection = ConnectionGuard::connect(peripheral).await?;
 
-    let peripherals = adapter.peripherals().await?;
-    adapter.stop_scan().await?;
+    let deadline = time::Instant::now() + timeout;
+    let result: Result<PowerState, Box<dyn Error + Send + Sync>> = async {
+        let characteristic = power_state_characteristic(peripheral).await?;
+        let state_bytes = verification_state_bytes(peripheral).await;
+        let mut last = None;
+        loop {
+            // A failed read right after a command is as likely to be transient as the state
+            if let Ok(value) = read_power_byte_from(peripheral, &characteristic).await {
+                let state = state_bytes.decode(value);
+                if state.is_settled() && state.satisfies(command) {
+                    return Ok(state);
+                }
+                last = Some(state);
+            }
 
-    // Find lighthouse devices by checking manufacturer ID and name prefix
-    let mut lighthouse_devices = Vec::new();
-    let mut device_info_list = Vec::new();
+            let now = time::Instant::now();
+            if now >= deadline {
+                return Err(match last {
+                    Some(state) if state.is_settled() => format!("Device settled in {}", state),
+                    Some(state) => format!("Device did not settle (last state: {})", state),
+                    None => "Could not read the power state".to_string(),
+                }
+                .into());
+            }
+            time::sleep((AWAIT_STATE_POLL_INTERVAL + poll_jitter()).min(deadline - now)).await;
+        }
+    }
+    .await;
 
-    for peripheral in peripherals.iter() {
-        if let Ok(Some(properties)) = peripheral.properties().await {
-            let name = properties.local_name.clone().unwrap_or_default();
-            
-            // Check if this is a lighthouse device
-            let is_lighthouse = name.starts_with(LHB_PREFIX) &&
-                properties.manufacturer_data.iter().any(|(id, _)| *id == LIGHTHOUSE_MANUFACTURER_ID);
-            
-            if is_lighthouse {
-                lighthouse_devices.push(peripheral.clone());
-                device_info_list.push(DeviceInfo {
-                    name: name.clone(),
-                    address: peripheral.address().to_string(),
-                });
-                log(&format!("Found lighthouse: {} ({})", name, peripheral.address()), json_output);
+    connection.disconnect().await.ok();
+
+    result.map_err(|e| e as Box<dyn Error>)
+}
+
+/// Random-enough delay up to `AWAIT_STATE_POLL_JITTER`, so several devices polled
+/// together don't keep reading in lockstep
+fn poll_jitter() -> Duration {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    AWAIT_STATE_POLL_JITTER.mul_f64(f64::from(nanos % 1000) / 1000.0)
+}
+
+/// Split devices into those that still need the command and those already in its target state.
+/// Devices whose state can't be read are commanded anyway.
+pub async fn devices_needing_command(
+    devices: &[Peripheral],
+    command: u8,
+    json_output: bool,
+) -> (Vec<Peripheral>, Vec<DeviceInfo>) {
+    let mut to_command = Vec::new();
+    let mut skipped = Vec::new();
+
+    for device in devices {
+        let name = read_properties(device, json_output)
+            .await
+            .and_then(|p| p.local_name)
+            .unwrap_or_else(|| UNKNOWN_DEVICE_NAME.to_string());
+
+        match read_power_state(device).await {
+            Ok(state) if state.satisfies(command) => {
+                log(
+                    &format!("{} is already {}, skipping", name, state),
+                    json_output,
+                );
+                skipped.push(DeviceInfo::new(name, device.address().to_string()));
+            }
+            Ok(_) => to_command.push(device.clone()),
+            Err(e) => {
+                log(
+                    &format!(
+                        "Could not read the state of {} ({}), sending the command anyway",
+                        name, e
+                    ),
+                    json_output,
+                );
+                to_command.push(device.clone());
             }
         }
     }
 
-    if lighthouse_devices.is_empty() {
-        log("No Lighthouse devices found", json_output);
-        return Ok(Vec::new());
+    (to_command, skipped)
+}
+
+/// Warn about devices whose signal is below the weak signal threshold, since connecting
+/// to them will likely fail. Returns the devices to command, which leaves the weak ones
+/// out when `options.skip_weak` is set.
+pub async fn check_signal_strength(
+    devices: &[Peripheral],
+    options: &ScanOptions,
+    json_output: bool,
+) -> Vec<Peripheral> {
+    let threshold = options
+        .weak_signal_threshold
+        .unwrap_or_else(|| load_settings().unwrap_or_default().weak_signal_threshold());
+    let cached = load_devices_with_json(true).unwrap_or_default();
+
+    let mut reachable = Vec::with_capacity(devices.len());
+    for device in devices {
+        let address = device.address().to_string();
+        let properties = read_properties(device, json_output).await;
+        // Prefer what the last scan heard, falling back to when the device was last seen
+        let rssi = properties
+            .as_ref()
+            .and_then(|p| p.rssi)
+            .or_else(|| find_device(&cached, &address).and_then(|d| d.rssi));
+
+        if let Some(rssi) = rssi.filter(|rssi| *rssi < threshold) {
+            let name = properties
+                .and_then(|p| p.local_name)
+                .unwrap_or_else(|| UNKNOWN_DEVICE_NAME.to_string());
+            if options.skip_weak {
+                log(
+                    &format!(
+                        "Skipping {} ({}): signal is too weak ({} dBm, threshold {} dBm)",
+                        name, address, rssi, threshold
+                    ),
+                    json_output,
+                );
+                continue;
+            }
+            error_log(
+                &format!(
+                    "Warning: {} ({}) has a weak signal ({} dBm, threshold {} dBm), \
+                     the command may fail because it is out of range",
+                    name, address, rssi, threshold
+                ),
+                json_output,
+            );
+        }
+        reachable.push(device.clone());
     }
+    reachable
+}
 
-    log(
-        &format!("Found {} Lighthouse devices, saving and putting in standby...", lighthouse_devices.len()),
-        json_output,
-    );
+/// Leave out the devices the settings exclude from standby
+fn without_standby_exclusions(devices: &[Peripheral], json_output: bool) -> Vec<Peripheral> {
+    let settings = load_settings().unwrap_or_default();
+    devices
+        .iter()
+        .filter(|peripheral| {
+            let address = peripheral.address().to_string();
+            let excluded = settings.is_standby_excluded(&address);
+            if excluded {
+                log(
+                    &format!("Leaving {} on, it is in standby_exclusions", address),
+                    json_output,
+                );
+            }
+            !excluded
+        })
+        .cloned()
+        .collect()
+}
 
-    // Save the discovered devices
-    if let Err(e) = save_devices(&device_info_list) {
-        log(&format!("Failed to save devices: {}", e), json_output);
+/// Send a command to devices, skipping ones already in its target state when
+/// `options.only_changed` is set and ones with a weak signal when `options.skip_weak`
+/// is set. Standby and sleep also leave out excluded devices when
+/// `options.respect_exclusions` is set. Returns the devices that were skipped because they were already in the
+/// target state.
+pub async fn command_devices_with_opts(
+    devices: &[Peripheral],
+    command: u8,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
+    let turns_off = command == STANDBY_COMMAND || command == SLEEP_COMMAND;
+    let devices = if turns_off && options.respect_exclusions {
+        without_standby_exclusions(devices, json_output)
+    } else {
+        devices.to_vec()
+    };
+    let devices = check_signal_strength(&devices, options, json_output).await;
+    let (to_command, skipped) = if options.only_changed {
+        devices_needing_command(&devices, command, json_output).await
+    } else {
+        (devices, Vec::new())
+    };
+
+    let attempted = to_command.len() + skipped.len();
+    if !to_command.is_empty() {
+        let outcomes = if options.repeat > 1 {
+            send_until_confirmed(&to_command, command, options, json_output).await?
+        } else {
+            let mut outcomes =
+                send_command_to_devices(&to_command, command, options, json_output).await?;
+            if let Some(timeout) = options.verify {
+                verify_outcomes(&to_command, &mut outcomes, command, timeout, json_output).await;
+            }
+            outcomes
+        };
+        remember_capabilities(&to_command, options, json_output);
+        remember_outcomes(&outcomes, options, json_output);
+
+        let failed: Vec<String> = outcomes
+            .into_iter()
+            .filter(|(_, success)| !success)
+            .map(|(address, _)| address)
+            .collect();
+        if !failed.is_empty() {
+            return Err(LighthouseError::DevicesFailed { failed, attempted }.into());
+        }
+    }
+    if !skipped.is_empty() {
+        log(
+            &format!(
+                "Skipped {} devices already in the requested state",
+                skipped.len()
+            ),
+            json_output,
+        );
     }
 
-    // Send the standby command to all found devices
-    handle_device_command_with_json(&lighthouse_devices, STANDBY_COMMAND, json_output).await?;
+    Ok(skipped)
+}
 
-    Ok(device_info_list)
+/// How long each device gets to confirm a repeated command when `--verify` doesn't set a timeout
+const REPEAT_CONFIRM_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Send a command up to `options.repeat` times, checking after each send that every
+/// device reached the target state. Only the devices that haven't are sent the command
+/// again, after `options.repeat_interval`. A device that never confirms counts as failed.
+/// Devices that can't report their power state are sent the command once, and the
+/// write's result is their outcome.
+async fn send_until_confirmed(
+    devices: &[Peripheral],
+    command: u8,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<Vec<(String, bool)>, Box<dyn Error>> {
+    let timeout = options.verify.unwrap_or(REPEAT_CONFIRM_TIMEOUT);
+    let mut sent = send_command_to_devices(devices, command, options, json_output).await?;
+
+    // The first send discovered each device's services, so it's now known which ones can be verified
+    let cached_devices = load_devices_with_json(true).unwrap_or_default();
+    let mut pending = Vec::new();
+    let mut outcomes = Vec::with_capacity(sent.len());
+    for (address, success) in &sent {
+        match devices.iter().find(|p| p.address().to_string() == *address) {
+            Some(peripheral) if reports_power_state(peripheral, &cached_devices) => {
+                pending.push(peripheral.clone());
+                outcomes.push((address.clone(), false));
+            }
+            _ => {
+                log(
+                    &format!(
+                        "Warning: {} doesn't report its power state, ignoring --repeat for it",
+                        address
+                    ),
+                    json_output,
+                );
+                outcomes.push((address.clone(), *success));
+            }
+        }
+    }
+    sent.retain(|(address, _)| pending.iter().any(|p| p.address().to_string() == *address));
+
+    let mut attempt = 1;
+    loop {
+        verify_outcomes(&pending, &mut sent, command, timeout, json_output).await;
+        for (address, _) in sent.into_iter().filter(|(_, confirmed)| *confirmed) {
+            if let Some((_, success)) = outcomes.iter_mut().find(|(a, _)| *a == address) {
+                *success = true;
+            }
+            pending.retain(|peripheral| peripheral.address().to_string() != address);
+        }
+        if pending.is_empty() || attempt >= options.repeat {
+            break;
+        }
+
+        time::sleep(options.repeat_interval).await;
+        attempt += 1;
+        log(
+            &format!(
+                "Re-sending to {} devices (attempt {} of {})...",
+                pending.len(),
+                attempt,
+                options.repeat
+            ),
+            json_output,
+        );
+        sent = send_command_to_devices(&pending, command, options, json_output).await?;
+    }
+    Ok(outcomes)
+}
+
+/// Whether a device's power state can be read back, going by the characteristics found
+/// when it was last connected to, or else its cached capabilities. A device not known
+/// either way is assumed to report it.
+fn reports_power_state(peripheral: &Peripheral, cached_devices: &[DeviceInfo]) -> bool {
+    let characteristics = peripheral.characteristics();
+    if !characteristics.is_empty() {
+        return capabilities_from_characteristics(&characteristics).power_state;
+    }
+    find_device(cached_devices, &peripheral.address().to_string())
+        .and_then(|device| device.capabilities.as_ref())
+        .is_none_or(|capabilities| capabilities.power_state)
+}
+
+/// Wait for each device the command was sent to to settle in its target state,
+/// marking the ones that don't as failed
+async fn verify_outcomes(
+    devices: &[Peripheral],
+    outcomes: &mut [(String, bool)],
+    command: u8,
+    timeout: Duration,
+    json_output: bool,
+) {
+    log("Verifying power states...", json_output);
+    for (address, success) in outcomes.iter_mut().filter(|(_, success)| *success) {
+        let Some(peripheral) = devices.iter().find(|p| p.address().to_string() == *address) else {
+            continue;
+        };
+        match await_state(peripheral, command, timeout).await {
+            Ok(state) => log(&format!("{} is {}", address, state), json_output),
+            Err(e) => {
+                error_log(
+                    &format!("Verification failed for {}: {}", address, e),
+                    json_output,
+                );
+                *success = false;
+            }
+        }
+    }
+}
+
+/// Maximum number of base stations `read_all_states` connects to at once
+const MAX_CONCURRENT_STATE_READS: usize = 4;
+
+/// Read the power state of every cached base station from a single scan.
+/// Devices are connected to concurrently, and one that wasn't seen or couldn't be
+/// read gets an error entry instead of failing the whole call.
+pub async fn read_all_states(
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<Vec<(DeviceInfo, Result<PowerState, String>)>, Box<dyn Error>> {
+    let _adapter_guard = lock_adapter().await;
+    let manager = Manager::new().await?;
+    let adapter = adapter_for_options(&manager, options, json_output).await?;
+    read_all_states_on(&adapter, options, json_output).await
+}
+
+/// Same as `read_all_states` on an adapter the caller already has.
+/// The caller should hold `lock_adapter`.
+pub(crate) async fn read_all_states_on(
+    adapter: &Adapter,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<Vec<(DeviceInfo, Result<PowerState, String>)>, Box<dyn Error>> {
+    let settings = load_settings().unwrap_or_default();
+    let cached_devices: Vec<DeviceInfo> = load_devices_with_json(json_output)?
+        .into_iter()
+        .filter(|device| settings.is_address_allowed(&device.address))
+        .collect();
+    read_states_on(adapter, cached_devices, options, json_output).await
+}
+
+/// Read the power state of the given devices from a single scan, like `read_all_states_on`.
+/// The caller should hold `lock_adapter`.
+pub(crate) async fn read_states_on(
+    adapter: &Adapter,
+    devices: Vec<DeviceInfo>,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<Vec<(DeviceInfo, Result<PowerState, String>)>, Box<dyn Error>> {
+    if devices.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    log(msg(Msg::ScanningLighthouses), json_output);
+    let peripherals = discover_peripherals(adapter, options, json_output).await?;
+
+    let states = stream::iter(devices)
+        .map(|device| {
+            let peripheral = peripherals
+                .iter()
+                .find(|p| device.matches_address(&p.address().to_string()));
+            async move {
+                let state = match peripheral {
+                    Some(peripheral) => read_power_state(peripheral)
+                        .await
+                        .map_err(|e| e.to_string()),
+                    None => Err("Device not found".to_string()),
+                };
+                (device, state)
+            }
+        })
+        .buffered(MAX_CONCURRENT_STATE_READS)
+        .collect()
+        .await;
+
+    Ok(states)
+}
+
+/// How long each base station gets to spin up and report being on
+const RECOVERY_VERIFY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Power cycle every nearby base station: standby, wait `delay`, power on and then
+/// check each one reports being on, re-sending power on to the ones that don't
+/// for up to `attempts` power on attempts in total.
+pub async fn recover_lighthouses(
+    options: &ScanOptions,
+    delay: Duration,
+    attempts: u32,
+    json_output: bool,
+) -> Result<RecoveryReport, Box<dyn Error>> {
+    let _adapter_guard = lock_adapter().await;
+    let manager = Manager::new().await?;
+    let adapter = adapter_for_options(&manager, options, json_output).await?;
+
+    log(msg(Msg::ScanningLighthouses), json_output);
+    let peripherals = discover_peripherals(&adapter, options, json_output).await?;
+
+    let settings = load_settings().unwrap_or_default();
+    let mut lighthouses = Vec::new();
+    for peripheral in peripherals {
+        let properties = match read_properties(&peripheral, json_output).await {
+            Some(properties) if detect_lighthouse(&properties, &settings) => properties,
+            _ => continue,
+        };
+        let address = peripheral.address().to_string();
+        if !settings.is_address_allowed(&address) {
+            continue;
+        }
+        let name = properties
+            .local_name
+            .unwrap_or_else(|| UNKNOWN_DEVICE_NAME.to_string());
+        let device = DeviceInfo {
+            rssi: properties.rssi,
+            ..DeviceInfo::new(name, address)
+        };
+        lighthouses.push((peripheral, device));
+    }
+
+    if lighthouses.is_empty() {
+        log(msg(Msg::NoLighthousesFound), json_output);
+        return Ok(RecoveryReport {
+            recovered: Vec::new(),
+            failed: Vec::new(),
+            attempts: 0,
+        });
+    }
+
+    let all: Vec<Peripheral> = lighthouses.iter().map(|(p, _)| p.clone()).collect();
+    send_command_to_devices(&all, STANDBY_COMMAND, options, json_output).await?;
+    log(
+        &format!("Waiting {}s before powering back on...", delay.as_secs()),
+        json_output,
+    );
+    time::sleep(delay).await;
+
+    let attempts = attempts.max(1);
+    let mut pending = lighthouses;
+    let mut recovered = Vec::new();
+    let mut attempt = 0;
+    while attempt < attempts && !pending.is_empty() {
+        attempt += 1;
+        if attempt > 1 {
+            log(
+                &format!(
+                    "Retrying power on for {} devices (attempt {} of {})...",
+                    pending.len(),
+                    attempt,
+                    attempts
+                ),
+                json_output,
+            );
+        }
+        let to_power: Vec<Peripheral> = pending.iter().map(|(p, _)| p.clone()).collect();
+        send_command_to_devices(&to_power, POWERON_COMMAND, options, json_output).await?;
+
+        let mut still_pending = Vec::new();
+        for (peripheral, device) in pending {
+            match await_state(&peripheral, POWERON_COMMAND, RECOVERY_VERIFY_TIMEOUT).await {
+                Ok(state) => {
+                    log(&format!("{} is {}", device.name, state), json_output);
+                    recovered.push(device);
+                }
+                Err(e) => {
+                    log(
+                        &format!("{} did not come back on: {}", device.name, e),
+                        json_output,
+                    );
+                    still_pending.push((peripheral, device));
+                }
+            }
+        }
+        pending = still_pending;
+    }
+
+    Ok(RecoveryReport {
+        recovered,
+        failed: pending.into_iter().map(|(_, device)| device).collect(),
+        attempts: attempt,
+    })
+}
+
+/// Time scans, connections and commands over `iterations` rounds, to help pick
+/// scan and retry settings for the hardware at hand. The command written is the one
+/// each base station is already in, so the benchmark leaves every device as it was.
+/// Nothing is saved to the device cache.
+pub async fn benchmark_lighthouses(
+    iterations: u32,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<BenchmarkReport, Box<dyn Error>> {
+    let _adapter_guard = lock_adapter().await;
+    let manager = Manager::new().await?;
+    let adapter = adapter_for_options(&manager, options, json_output).await?;
+    let options = ScanOptions {
+        no_save: true,
+        ..options.clone()
+    };
+
+    let iterations = iterations.max(1);
+    let mut scan_samples = Vec::new();
+    let mut end_to_end_samples = Vec::new();
+    // Each device with its connect and command samples
+    let mut devices: Vec<(DeviceBenchmark, Vec<u64>, Vec<u64>)> = Vec::new();
+
+    for iteration in 1..=iterations {
+        log(
+            &format!("Benchmark iteration {} of {}...", iteration, iterations),
+            json_output,
+        );
+        let started_at = Instant::now();
+        let report = scan_and_report_on(&adapter, 0xFF, &options, started_at, json_output).await?;
+        scan_samples.push(report.duration_ms);
+        let peripherals = adapter.peripherals().await?;
+
+        for device in &report.devices {
+            let known = devices
+                .iter()
+                .any(|(entry, _, _)| entry.device.matches_address(&device.address));
+            if !known {
+                let entry = DeviceBenchmark {
+                    device: device.clone(),
+                    connect: None,
+                    command: None,
+                    // Missed by the scans before this one
+                    failures: iteration - 1,
+                    last_error: None,
+                };
+                devices.push((entry, Vec::new(), Vec::new()));
+            }
+        }
+
+        for (entry, connect_samples, command_samples) in devices.iter_mut() {
+            let seen = report
+                .devices
+                .iter()
+                .any(|device| entry.device.matches_address(&device.address));
+            let peripheral = peripherals
+                .iter()
+                .find(|p| seen && entry.device.matches_address(&p.address().to_string()));
+            let result = match peripheral {
+                Some(peripheral) => time_device(peripheral).await,
+                None => Err("Not found by the scan".into()),
+            };
+            match result {
+                Ok((connect_ms, command_ms)) => {
+                    connect_samples.push(connect_ms);
+                    command_samples.push(command_ms);
+                }
+                Err(e) => {
+                    log(
+                        &format!("Could not time {}: {}", entry.device.name, e),
+                        json_output,
+                    );
+                    entry.failures += 1;
+                    entry.last_error = Some(e.to_string());
+                }
+            }
+        }
+        end_to_end_samples.push(started_at.elapsed().as_millis() as u64);
+    }
+
+    let all_connect: Vec<u64> = devices
+        .iter()
+        .flat_map(|(_, samples, _)| samples.iter().copied())
+        .collect();
+    let all_command: Vec<u64> = devices
+        .iter()
+        .flat_map(|(_, _, samples)| samples.iter().copied())
+        .collect();
+    Ok(BenchmarkReport {
+        iterations,
+        scan: TimingStats::from_samples(&scan_samples),
+        connect: TimingStats::from_samples(&all_connect),
+        command: TimingStats::from_samples(&all_command),
+        end_to_end: TimingStats::from_samples(&end_to_end_samples),
+        devices: devices
+            .into_iter()
+            .map(
+                |(entry, connect_samples, command_samples)| DeviceBenchmark {
+                    connect: TimingStats::from_samples(&connect_samples),
+                    command: TimingStats::from_samples(&command_samples),
+                    ..entry
+                },
+            )
+            .collect(),
+    })
+}
+
+/// Time connecting to a base station, then reading its power state and writing the
+/// command it's already in. Returns the connect and command times in milliseconds.
+async fn time_device(peripheral: &Peripheral) -> Result<(u64, u64), Box<dyn Error>> {
+    let started_at = Instant::now();
+    let connection = ConnectionGuard::exclusive(peripheral).await?;
+    let connect_ms = started_at.elapsed().as_millis() as u64;
+
+    // Send + Sync keeps the future Send while the result is held across the disconnect
+    let started_at = Instant::now();
+    let result: Result<(), Box<dyn Error + Send + Sync>> = async {
+        let characteristic = power_state_characteristic(peripheral).await?;
+        let command = match read_power_state_from(peripheral, &characteristic).await? {
+            PowerState::On | PowerState::Booting => Some(POWERON_COMMAND),
+            PowerState::Standby => Some(STANDBY_COMMAND),
+            PowerState::Sleep => Some(SLEEP_COMMAND),
+            // There's no telling what a write would do, so only the read is timed
+            PowerState::Unknown(_) => None,
+        };
+        if let Some(command) = command {
+            peripheral
+                .write(&characteristic, &[command], WriteType::WithoutResponse)
+                .await?;
+        }
+        Ok(())
+    }
+    .await;
+    let command_ms = started_at.elapsed().as_millis() as u64;
+
+    connection.disconnect().await.ok();
+    result.map_err(|e| e as Box<dyn Error>)?;
+    Ok((connect_ms, command_ms))
+}
+
+/// Pause between identify requests, so each one is visible as a separate blink sequence
+const BLINK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Make a base station flash its LED `count` times so it can be told apart from the others
+pub async fn blink_device(peripheral: &Peripheral, count: u32) -> Result<(), Box<dyn Error>> {
+    blink_device_with_json(peripheral, count, false).await
+}
+
+/// Make a base station flash its LED `count` times with JSON output control.
+/// The firmware has no blink count, so this sends one identify request per blink.
+pub async fn blink_device_with_json(
+    peripheral: &Peripheral,
+    count: u32,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let connection = ConnectionGuard::connect(peripheral).await?;
+
+    // Send + Sync keeps the future Send while the result is held across the disconnect
+    let result: Result<(), Box<dyn Error + Send + Sync>> = async {
+        peripheral.discover_services().await?;
+        let characteristic = peripheral
+            .characteristics()
+            .into_iter()
+            .find(|c| c.uuid == IDENTIFY.characteristic)
+            .ok_or("Device does not support identify")?;
+
+        for i in 0..count.max(1) {
+            if i > 0 {
+                time::sleep(BLINK_INTERVAL).await;
+            }
+            log(
+                &format!(
+                    "Blinking {} ({}/{})",
+                    peripheral.address(),
+                    i + 1,
+                    count.max(1)
+                ),
+                json_output,
+            );
+            peripheral
+                .write(&characteristic, IDENTIFY.bytes, WriteType::WithResponse)
+                .await?;
+        }
+        Ok(())
+    }
+    .await;
+
+    connection.disconnect().await.ok();
+
+    result.map_err(|e| e as Box<dyn Error>)
+}
+
+/// Scan for the base station with the given address and make it blink `count` times
+pub async fn blink_device_by_address(
+    address: &str,
+    count: u32,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<DeviceInfo, Box<dyn Error>> {
+    let _adapter_guard = lock_adapter().await;
+    let manager = Manager::new().await?;
+    let adapter = adapter_for_options(&manager, options, json_output).await?;
+
+    let peripheral = find_peripheral_by_address(&adapter, address, options, json_output).await?;
+    let address = normalize_address(address);
+
+    blink_device_with_json(&peripheral, count, json_output).await?;
+
+    let name = read_properties(&peripheral, json_output)
+        .await
+        .and_then(|p| p.local_name)
+        .unwrap_or_else(|| UNKNOWN_DEVICE_NAME.to_string());
+    Ok(DeviceInfo::new(name, address))
+}
+
+/// Scan for base stations and return the one with the given address
+pub async fn find_peripheral_by_address(
+    adapter: &Adapter,
+    address: &str,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<Peripheral, Box<dyn Error>> {
+    log(&format!("Looking for {}...", address), json_output);
+    let peripherals = discover_peripherals(adapter, options, json_output).await?;
+
+    let address = normalize_address(address);
+    peripherals
+        .into_iter()
+        .find(|p| normalize_address(&p.address().to_string()) == address)
+        .ok_or_else(|| format!("Device {} not found", address).into())
+}
+
+/// How long `dump_gatt` waits for each characteristic read
+const GATT_READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Discover the services of a connected device and read every readable characteristic.
+/// A read that fails is recorded on its characteristic rather than failing the dump,
+/// since some characteristics refuse reads despite advertising them.
+pub async fn dump_gatt(peripheral: &Peripheral) -> Result<Vec<GattService>, Box<dyn Error>> {
+    peripheral.discover_services().await?;
+
+    let mut services = Vec::new();
+    for service in peripheral.services() {
+        let mut characteristics = Vec::new();
+        for characteristic in &service.characteristics {
+            let mut gatt_characteristic = GattCharacteristic {
+                uuid: characteristic.uuid.to_string(),
+                properties: characteristic
+                    .properties
+                    .iter_names()
+                    .map(|(name, _)| name.to_string())
+                    .collect(),
+                value: None,
+                text: None,
+                read_error: None,
+                descriptors: characteristic
+                    .descriptors
+                    .iter()
+                    .map(|descriptor| descriptor.uuid.to_string())
+                    .collect(),
+            };
+            if characteristic.properties.contains(CharPropFlags::READ) {
+                match time::timeout(GATT_READ_TIMEOUT, peripheral.read(characteristic)).await {
+                    Ok(Ok(value)) => gatt_characteristic.set_value(&value),
+                    Ok(Err(e)) => gatt_characteristic.read_error = Some(e.to_string()),
+                    Err(_) => gatt_characteristic.read_error = Some("timed out".to_string()),
+                }
+            }
+            characteristics.push(gatt_characteristic);
+        }
+        services.push(GattService {
+            uuid: service.uuid.to_string(),
+            primary: service.primary,
+            characteristics,
+        });
+    }
+    Ok(services)
+}
+
+/// Work out a device's capabilities from the characteristics found by service discovery
+pub fn capabilities_from_characteristics(
+    characteristics: &BTreeSet<Characteristic>,
+) -> DeviceCapabilities {
+    let find = |uuid| characteristics.iter().find(|c| c.uuid == uuid);
+    let power = find(LIGHTHOUSE_CHAR_UUID);
+
+    DeviceCapabilities {
+        power: power.is_some_and(|c| {
+            c.properties
+                .intersects(CharPropFlags::WRITE | CharPropFlags::WRITE_WITHOUT_RESPONSE)
+        }),
+        power_state: power.is_some_and(|c| c.properties.contains(CharPropFlags::READ)),
+        channel: find(CHANNEL_CHAR_UUID).is_some(),
+        identify: find(IDENTIFY_CHAR_UUID).is_some(),
+        serial_number: find(SERIAL_NUMBER_CHAR_UUID).is_some(),
+    }
+}
+
+/// Connect to a device and report which operations it supports
+pub async fn probe_capabilities(
+    peripheral: &Peripheral,
+) -> Result<DeviceCapabilities, Box<dyn Error>> {
+    let connection = ConnectionGuard::connect(peripheral).await?;
+
+    let result = peripheral.discover_services().await;
+
+    connection.disconnect().await.ok();
+
+    result?;
+    Ok(capabilities_from_characteristics(
+        &peripheral.characteristics(),
+    ))
+}
+
+/// Cache the capabilities of devices that were just connected to, for any cached
+/// device that hasn't been probed yet. This reuses the services discovered while
+/// sending commands, so it doesn't connect again.
+pub fn remember_capabilities(peripherals: &[Peripheral], options: &ScanOptions, json_output: bool) {
+    if options.no_save {
+        return;
+    }
+
+    let mut devices = match load_devices_with_json(true) {
+        Ok(devices) => devices,
+        Err(_) => return,
+    };
+
+    let mut changed = false;
+    for peripheral in peripherals {
+        let characteristics = peripheral.characteristics();
+        if characteristics.is_empty() {
+            continue;
+        }
+
+        let address = peripheral.address().to_string();
+        if let Some(device) = devices
+            .iter_mut()
+            .find(|device| device.matches_address(&address) && device.capabilities.is_none())
+        {
+            device.capabilities = Some(capabilities_from_characteristics(&characteristics));
+            changed = true;
+        }
+    }
+
+    if changed {
+        if let Err(e) = save_devices_with_json(&devices, true) {
+            log(
+                &format!("Failed to save device capabilities: {}", e),
+                json_output,
+            );
+        }
+    }
+}
+
+/// Add the outcome of a command to each cached device's recent results, and
+/// warn about devices that have now failed several times in a row
+pub fn remember_outcomes(outcomes: &[(String, bool)], options: &ScanOptions, json_output: bool) {
+    if options.no_save || outcomes.is_empty() {
+        return;
+    }
+
+    let mut devices = match load_devices_with_json(true) {
+        Ok(devices) => devices,
+        Err(_) => return,
+    };
+
+    let mut changed = false;
+    for (address, success) in outcomes {
+        let Some(device) = devices
+            .iter_mut()
+            .find(|device| device.matches_address(address))
+        else {
+            continue;
+        };
+        let reliability = device
+            .reliability
+            .get_or_insert_with(DeviceReliability::default);
+        reliability.record(*success);
+        changed = true;
+
+        if !success && reliability.is_failing() {
+            error_log(
+                &format!(
+                    "Warning: {} ({}) has failed the last {} commands",
+                    device.name,
+                    device.address,
+                    reliability.consecutive_failures()
+                ),
+                json_output,
+            );
+        }
+    }
+
+    if changed {
+        if let Err(e) = save_devices_with_json(&devices, true) {
+            log(
+                &format!("Failed to save command results: {}", e),
+                json_output,
+            );
+        }
+    }
+}
+
+/// Probe the capabilities of all nearby lighthouses, read their channel and cache both.
+/// Capabilities probed on an earlier run are reused unless `force` is set.
+pub async fn probe_lighthouses(
+    options: &ScanOptions,
+    force: bool,
+    json_output: bool,
+) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
+    let _adapter_guard = lock_adapter().await;
+    let manager = Manager::new().await?;
+    let adapter = adapter_for_options(&manager, options, json_output).await?;
+
+    log(msg(Msg::ScanningLighthouses), json_output);
+    let peripherals = discover_peripherals(&adapter, options, json_output).await?;
+
+    let settings = load_settings().unwrap_or_default();
+    let mut cached_devices = load_devices_with_json(json_output).unwrap_or_default();
+    let mut probed = Vec::new();
+
+    for peripheral in peripherals.iter() {
+        let properties = match read_properties(peripheral, json_output).await {
+            Some(properties) if detect_lighthouse(&properties, &settings) => properties,
+            _ => continue,
+        };
+        let address = peripheral.address().to_string();
+        if !settings.is_address_allowed(&address) {
+            continue;
+        }
+        let name = properties
+            .local_name
+            .unwrap_or_else(|| UNKNOWN_DEVICE_NAME.to_string());
+
+        let cached = cached_devices
+            .iter()
+            .position(|device| device.matches_address(&address));
+        let known = cached.and_then(|i| cached_devices[i].capabilities);
+
+        let capabilities = match known {
+            Some(capabilities) if !force => {
+                log(
+                    &format!("Using cached capabilities for {} ({})", name, address),
+                    json_output,
+                );
+                capabilities
+            }
+            _ => {
+                log(&format!("Probing {} ({})...", name, address), json_output);
+                match probe_capabilities(peripheral).await {
+                    Ok(capabilities) => capabilities,
+                    Err(e) => {
+                        error_log(&format!("Failed to probe {}: {}", name, e), json_output);
+                        continue;
+                    }
+                }
+            }
+        };
+
+        // The channel can be changed at any time, so it's read on every probe
+        let channel = if capabilities.channel {
+            match read_channel(peripheral).await {
+                Ok(channel) => channel,
+                Err(e) => {
+                    error_log(
+                        &format!("Failed to read the channel of {}: {}", name, e),
+                        json_output,
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let device = DeviceInfo {
+            capabilities: Some(capabilities),
+            channel,
+            ..DeviceInfo::new(name, address)
+        };
+        match cached {
+            Some(i) => cached_devices[i] = device.clone(),
+            None => cached_devices.push(device.clone()),
+        }
+        probed.push(device);
+    }
+
+    if options.no_save {
+        log(
+            "Saving is disabled, not updating the config file",
+            json_output,
+        );
+    } else if !probed.is_empty() {
+        save_devices_with_json(&cached_devices, json_output)?;
+    }
+
+    Ok(probed)
+}
+
+/// Handle device commands for multiple devices
+pub async fn handle_device_command(
+    devices: &[Peripheral],
+    command: u8,
+) -> Result<(), Box<dyn Error>> {
+    // Default to non-JSON output for internal calls
+    handle_device_command_with_json(devices, command, false).await
+}
+
+/// Handle device commands for multiple devices with JSON output control
+pub async fn handle_device_command_with_json(
+    devices: &[Peripheral],
+    command: u8,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    send_command_to_devices(devices, command, &ScanOptions::default(), json_output).await?;
+    Ok(())
+}
+
+/// Send a command to each device in turn, returning each address with whether
+/// the command succeeded
+async fn send_command_to_devices(
+    devices: &[Peripheral],
+    command: u8,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<Vec<(String, bool)>, Box<dyn Error>> {
+    let command_name = power_command(command).map_or("unknown operation", |command| command.name);
+
+    log(
+        &msgf(Msg::SendingCommand, &[&command_name, &devices.len()]),
+        json_output,
+    );
+
+    let mut outcomes = Vec::with_capacity(devices.len());
+    for (i, device) in devices.iter().enumerate() {
+        log(
+            &format!("Processing device {} of {}...", i + 1, devices.len()),
+            json_output,
+        );
+
+        match send_command_to_device_with_opts(device, command, options, json_output).await {
+            Ok(_) => {
+                outcomes.push((device.address().to_string(), true));
+                log(
+                    &msgf(Msg::CommandSent, &[&command_name, &(i + 1)]),
+                    json_output,
+                );
+                record_event(
+                    EventKind::Command,
+                    format!("Sent {} command to {}", command_name, device.address()),
+                );
+            }
+            // Every remaining device would fail the same way, so stop here
+            Err(e) if is_adapter_lost(e.as_ref()) => {
+                record_event(EventKind::Error, LighthouseError::AdapterLost.to_string());
+                return Err(LighthouseError::AdapterLost.into());
+            }
+            Err(e) => {
+                outcomes.push((device.address().to_string(), false));
+                log(
+                    &msgf(Msg::CommandFailed, &[&command_name, &(i + 1), &e]),
+                    json_output,
+                );
+                record_event(
+                    EventKind::Error,
+                    format!(
+                        "Failed to send {} command to {}: {}",
+                        command_name,
+                        device.address(),
+                        e
+                    ),
+                );
+            }
+        }
+
+        // Add a small delay between devices to avoid overwhelming the Bluetooth adapter
+        time::sleep(Duration::from_millis(500)).await;
+    }
+
+    log(
+        &format!("{} operation completed", command_name),
+        json_output,
+    );
+    Ok(outcomes)
+}
+
+/// Power on lighthouses (called when SteamVR starts)
+#[allow(dead_code)]
+pub async fn power_on_lighthouses() -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
+    // Default to non-JSON output for internal calls
+    power_on_lighthouses_with_json(false).await
+}
+
+/// Longest scan before sending --poweron/--standby, unless `ScanOptions::scan_duration` is set
+const POWER_SCAN_DURATION: Duration = Duration::from_secs(3);
+
+/// Power on lighthouses with JSON output control
+/// Returns the list of devices that were found and powered on
+pub async fn power_on_lighthouses_with_json(
+    json_output: bool,
+) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
+    power_on_lighthouses_with_opts(&ScanOptions::default(), json_output).await
+}
+
+/// Same as `power_on_lighthouses_with_json`, using the given scan options
+pub async fn power_on_lighthouses_with_opts(
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
+    log("Powering on lighthouses...", json_output);
+
+    let _adapter_guard = lock_adapter().await;
+
+    // Initialize Bluetooth
+    let manager = Manager::new().await?;
+    let adapter = match adapter_for_options(&manager, options, json_output).await {
+        Ok(adapter) => adapter,
+        Err(e) => {
+            error_log(&e.to_string(), json_output);
+            return Err(e);
+        }
+    };
+    log(
+        &format!("Using adapter: {}", adapter.adapter_info().await?),
+        json_output,
+    );
+
+    command_lighthouses_on(&adapter, &POWER_ON, options, json_output).await
+}
+
+/// Scan for base stations on an adapter the caller already has and send `command` to
+/// the ones found. The caller should hold `lock_adapter`.
+pub(crate) async fn command_lighthouses_on(
+    adapter: &Adapter,
+    command: &DeviceCommand,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
+    let settings = load_settings().unwrap_or_default();
+    let known_devices: Vec<DeviceInfo> = load_devices_with_json(json_output)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|device| settings.is_address_allowed(&device.address))
+        .collect();
+
+    // Start scanning for devices
+    log(msg(Msg::ScanningLighthouses), json_output);
+    let scan = ScanGuard::start(adapter).await?;
+    // Stop early once every cached base station has been heard
+    let scan_duration = options.scan_duration.unwrap_or(POWER_SCAN_DURATION);
+    wait_for_known_lighthouses(adapter, &known_devices, scan_duration, json_output).await;
+
+    let peripherals = adapter.peripherals().await?;
+    scan.stop().await?;
+
+    // Find lighthouse devices by checking manufacturer ID and name prefix
+    let mut lighthouse_devices = Vec::new();
+    let mut device_info_list = Vec::new();
+
+    for peripheral in peripherals.iter() {
+        if let Some(properties) = read_properties(peripheral, json_output).await {
+            let name = properties.local_name.clone().unwrap_or_default();
+
+            // Check if this is a lighthouse device
+            let is_lighthouse = detect_lighthouse(&properties, &settings);
+
+            if is_lighthouse && !settings.is_address_allowed(&peripheral.address().to_string()) {
+                log(
+                    &format!(
+                        "Ignoring filtered lighthouse: {} ({})",
+                        name,
+                        peripheral.address()
+                    ),
+                    json_output,
+                );
+            } else if is_lighthouse {
+                lighthouse_devices.push(peripheral.clone());
+                device_info_list.push(DeviceInfo {
+                    rssi: properties.rssi,
+                    ..DeviceInfo::new(name.clone(), peripheral.address().to_string())
+                });
+                log(
+                    &msgf(Msg::FoundLighthouse, &[&name, &peripheral.address()]),
+                    json_output,
+                );
+            }
+        }
+    }
+
+    if lighthouse_devices.is_empty() {
+        log(msg(Msg::NoLighthousesFound), json_output);
+        return Ok(Vec::new());
+    }
+
+    log(
+        &format!(
+            "Found {} Lighthouse devices, saving and sending {}...",
+            lighthouse_devices.len(),
+            command.name
+        ),
+        json_output,
+    );
+
+    // Save the discovered devices
+    if options.no_save {
+        log(
+            "Saving is disabled, not updating the config file",
+            json_output,
+        );
+    } else if let Err(e) =
+        save_scanned_devices_with_json(&device_info_list, options.replace_cache, json_output)
+    {
+        log(&format!("Failed to save devices: {}", e), json_output);
+    }
+
+    command_devices_with_opts(&lighthouse_devices, command.byte(), options, json_output).await?;
+
+    Ok(device_info_list)
+}
+
+/// Put lighthouses in standby mode (called when SteamVR stops)
+#[allow(dead_code)]
+pub async fn standby_lighthouses() -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
+    // Default to non-JSON output for internal calls
+    standby_lighthouses_with_json(false).await
+}
+
+/// Put lighthouses in standby mode with JSON output control
+/// Returns the list of devices that were found and put in standby
+pub async fn standby_lighthouses_with_json(
+    json_output: bool,
+) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
+    standby_lighthouses_with_opts(&ScanOptions::default(), json_output).await
+}
+
+/// Same as `standby_lighthouses_with_json`, using the given scan options
+pub async fn standby_lighthouses_with_opts(
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
+    log("Putting lighthouses in standby mode...", json_output);
+
+    let _adapter_guard = lock_adapter().await;
+
+    // Initialize Bluetooth
+    let manager = Manager::new().await?;
+    let adapter = match adapter_for_options(&manager, options, json_output).await {
+        Ok(adapter) => adapter,
+        Err(e) => {
+            error_log(&e.to_string(), json_output);
+            return Err(e);
+        }
+    };
+    log(
+        &format!("Using adapter: {}", adapter.adapter_info().await?),
+        json_output,
+    );
+
+    command_lighthouses_on(&adapter, &STANDBY, options, json_output).await
 }
@@ -0,0 +1,123 @@
+// Guards that undo a scan or connection when they go out of scope, so an early
+// return, error or panic can't leave the adapter scanning or a device connected.
+//
+// Drop can't await, so a guard that is dropped without being finished spawns the
+// cleanup on the current Tokio runtime. Call `stop`/`disconnect` on the normal path
+// to wait for the cleanup and see its error, or `finish_pending_cleanup` to wait
+// for the cleanup of guards that were dropped, e.g. by a cancelled future.
+use crate::bluetooth::adapter::start_le_scan;
+use btleplug::api::{Central, Peripheral as _};
+use btleplug::platform::{Adapter, Peripheral};
+use std::error::Error;
+use std::mem;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+/// Cleanup spawned by dropped guards that may still be running
+static PENDING_CLEANUP: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+/// An LE scan that is stopped when the guard is dropped
+pub struct ScanGuard {
+    adapter: Option<Adapter>,
+}
+
+impl ScanGuard {
+    /// Start scanning on the adapter
+    pub async fn start(adapter: &Adapter) -> Result<Self, Box<dyn Error>> {
+        start_le_scan(adapter).await?;
+        Ok(Self {
+            adapter: Some(adapter.clone()),
+        })
+    }
+
+    /// Stop the scan and wait for the adapter to confirm
+    pub async fn stop(mut self) -> Result<(), btleplug::Error> {
+        match self.adapter.take() {
+            Some(adapter) => adapter.stop_scan().await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ScanGuard {
+    fn drop(&mut self) {
+        if let Some(adapter) = self.adapter.take() {
+            spawn_cleanup(async move {
+                adapter.stop_scan().await.ok();
+            });
+        }
+    }
+}
+
+/// A connection to a device that is closed when the guard is dropped
+pub struct ConnectionGuard {
+    peripheral: Option<Peripheral>,
+    was_connected: bool,
+}
+
+impl ConnectionGuard {
+    /// Connect to the device unless it's already connected. Only a connection opened
+    /// here is closed again, so one the caller already had is left as it was.
+    pub async fn connect(peripheral: &Peripheral) -> Result<Self, Box<dyn Error>> {
+        let was_connected = peripheral.is_connected().await?;
+        if !was_connected {
+            peripheral.connect().await?;
+        }
+        Ok(Self {
+            peripheral: Some(peripheral.clone()).filter(|_| !was_connected),
+            was_connected,
+        })
+    }
+
+    /// Connect to the device if needed, and close the connection afterwards either way
+    pub async fn exclusive(peripheral: &Peripheral) -> Result<Self, Box<dyn Error>> {
+        let mut guard = Self::connect(peripheral).await?;
+        guard.peripheral = Some(peripheral.clone());
+        Ok(guard)
+    }
+
+    /// Whether the device was already connected before the guard was created
+    pub fn was_connected(&self) -> bool {
+        self.was_connected
+    }
+
+    /// Close the connection, if this guard owns it, and wait for it to be closed
+    pub async fn disconnect(mut self) -> Result<(), btleplug::Error> {
+        match self.peripheral.take() {
+            Some(peripheral) => peripheral.disconnect().await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if let Some(peripheral) = self.peripheral.take() {
+            spawn_cleanup(async move {
+                peripheral.disconnect().await.ok();
+            });
+        }
+    }
+}
+
+/// Run cleanup from a `Drop` impl. Outside a runtime there's nothing to run it on,
+/// which only happens once the process is exiting anyway.
+fn spawn_cleanup(cleanup: impl std::future::Future<Output = ()> + Send + 'static) {
+    if let Ok(handle) = Handle::try_current() {
+        let task = handle.spawn(cleanup);
+        let mut pending = PENDING_CLEANUP.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|task| !task.is_finished());
+        pending.push(task);
+    }
+}
+
+/// Wait up to `timeout` for the cleanup of dropped guards to finish. Call this
+/// before exiting after cancelling an operation, since exiting the process would
+/// otherwise cut the cleanup short and could leave a scan running or a device connected.
+pub async fn finish_pending_cleanup(timeout: Duration) {
+    let pending = mem::take(&mut *PENDING_CLEANUP.lock().unwrap_or_else(|e| e.into_inner()));
+    let all_done = futures::future::join_all(pending);
+    tokio::time::timeout(timeout, all_done).await.ok();
+}
@@ -1,9 +1,19 @@
 // Bluetooth module for device control and scanning
+mod adapter;
+mod commands;
+mod controller;
 mod device_control;
+mod guards;
+mod options;
 mod scanning;
 
 // Re-export public functions
+pub use adapter::*;
+pub use commands::*;
+pub use controller::*;
 pub use device_control::*;
+pub use guards::*;
+pub use options::*;
 pub use scanning::*;
 
 // Bluetooth constants
@@ -16,6 +26,28 @@ pub const LIGHTHOUSE_SERVICE_UUID: uuid::Uuid =
 pub const LIGHTHOUSE_CHAR_UUID: uuid::Uuid =
     uuid::Uuid::from_u128(0x00001525_1212_efde_1523_785feabcd124);
 
-// Command values
+// Channel characteristic
+pub const CHANNEL_CHAR_UUID: uuid::Uuid =
+    uuid::Uuid::from_u128(0x00001524_1212_efde_1523_785feabcd124);
+
+// Range of channels a base station can be set to
+pub const MIN_CHANNEL: u8 = 1;
+pub const MAX_CHANNEL: u8 = 16;
+
+// Identify characteristic, a write makes the base station flash its LED
+pub const IDENTIFY_CHAR_UUID: uuid::Uuid =
+    uuid::Uuid::from_u128(0x00008421_1212_efde_1523_785feabcd124);
+
+// Device Information Service serial number string characteristic
+pub const SERIAL_NUMBER_CHAR_UUID: uuid::Uuid =
+    uuid::Uuid::from_u128(0x00002a25_0000_1000_8000_00805f9b34fb);
+
+// Device Information Service firmware revision string characteristic
+pub const FIRMWARE_REVISION_CHAR_UUID: uuid::Uuid =
+    uuid::Uuid::from_u128(0x00002a26_0000_1000_8000_00805f9b34fb);
+
+// Command values, see `DEVICE_COMMANDS` for what each one does
 pub const STANDBY_COMMAND: u8 = 0x00;
 pub const POWERON_COMMAND: u8 = 0x01;
+pub const SLEEP_COMMAND: u8 = 0x02;
+pub const IDENTIFY_COMMAND: u8 = 0x00;
@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+/// Options controlling how devices are discovered
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Number of scan passes to run. Results are unioned by address, which finds
+    /// devices that a single scan misses due to advertisement timing.
+    pub passes: u32,
+    /// Don't write discovered devices to the config file
+    pub no_save: bool,
+    /// Overwrite the config file with only the devices found, instead of merging them
+    /// into the cached ones
+    pub replace_cache: bool,
+    /// Retry with progressively longer scans when no base stations are found
+    pub adaptive: bool,
+    /// Adapter to use, by index or name. `None` uses the saved default, then the first adapter.
+    pub adapter: Option<String>,
+    /// Read each device's power state first and only command the ones not already in the target state
+    pub only_changed: bool,
+    /// After commanding, wait up to this long for each device to settle in the target state
+    pub verify: Option<Duration>,
+    /// Write everything the scan saw to a diagnostic file
+    pub save_raw_scan: bool,
+    /// Signal strength in dBm below which devices get a warning before being commanded.
+    /// `None` uses the threshold from the settings.
+    pub weak_signal_threshold: Option<i16>,
+    /// Leave out devices below the weak signal threshold instead of only warning
+    pub skip_weak: bool,
+    /// When a scan can't be started, connect to cached devices the platform already
+    /// knows by address instead of giving up
+    pub direct_fallback: bool,
+    /// How many more times to discover a device's services when the first discovery
+    /// finds no writable characteristic
+    pub discovery_retries: u32,
+    /// How many times to try connecting to a device and sending it a command before
+    /// giving up on it, with a growing delay between attempts
+    pub connect_attempts: u32,
+    /// Leave devices listed in the `standby_exclusions` setting out of standby commands
+    pub respect_exclusions: bool,
+    /// After a write, read the command's status characteristic where the device has
+    /// one, and fail with `CommandRejected` if it reports the command was rejected
+    pub confirm_accepted: bool,
+    /// How long each scan pass runs, and the longest wait for base stations before
+    /// --poweron/--standby. `None` uses the built-in durations.
+    pub scan_duration: Option<Duration>,
+    /// How many times to send a power command to a device that hasn't confirmed the
+    /// target state. Above 1, each device's state is checked after every send.
+    pub repeat: u32,
+    /// How long to wait before re-sending to the devices that didn't confirm
+    pub repeat_interval: Duration,
+}
+
+/// Default for `ScanOptions::discovery_retries`
+pub const DEFAULT_DISCOVERY_RETRIES: u32 = 2;
+
+/// Default for `ScanOptions::connect_attempts`
+pub const DEFAULT_CONNECT_ATTEMPTS: u32 = 3;
+
+/// Default for `ScanOptions::repeat_interval`
+pub const DEFAULT_REPEAT_INTERVAL: Duration = Duration::from_secs(2);
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            passes: 1,
+            no_save: false,
+            replace_cache: false,
+            adaptive: false,
+            adapter: None,
+            only_changed: false,
+            verify: None,
+            save_raw_scan: false,
+            weak_signal_threshold: None,
+            skip_weak: false,
+            direct_fallback: false,
+            discovery_retries: DEFAULT_DISCOVERY_RETRIES,
+            connect_attempts: DEFAULT_CONNECT_ATTEMPTS,
+            respect_exclusions: false,
+            confirm_accepted: false,
+            scan_duration: None,
+            repeat: 1,
+            repeat_interval: DEFAULT_REPEAT_INTERVAL,
+        }
+    }
+}
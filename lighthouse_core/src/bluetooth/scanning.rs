@@ -1,26 +1,126 @@
-use crate::bluetooth::device_control::handle_device_command_with_json;
-use crate::bluetooth::{LHB_PREFIX, LIGHTHOUSE_MANUFACTURER_ID};
-use crate::config::save_devices;
+use crate::bluetooth::adapter::{adapter_for_options, check_adapter_lost, lock_adapter};
+use crate::bluetooth::device_control::command_devices_with_opts;
+use crate::bluetooth::guards::ScanGuard;
+use crate::bluetooth::{
+    ScanOptions, LHB_PREFIX, LIGHTHOUSE_MANUFACTURER_ID, LIGHTHOUSE_SERVICE_UUID,
+};
+use crate::config::{
+    find_device, load_settings, save_raw_scan, save_scanned_devices_with_json, Settings,
+};
+use crate::events::{record_event, EventKind};
 use crate::logging::{error_log, log};
-use crate::models::DeviceInfo;
-use btleplug::api::{Central, Manager as _, Peripheral as _, ScanFilter};
-use btleplug::platform::{Manager, Peripheral};
+use crate::messages::{msg, msgf, Msg};
+use crate::models::{DeviceInfo, RawPeripheral, RawScan, ScanReport, UNKNOWN_DEVICE_NAME};
+use btleplug::api::{Central, CentralEvent, Peripheral as _, PeripheralProperties};
+use btleplug::platform::{Adapter, Manager, Peripheral};
+use futures::StreamExt;
+use std::collections::HashMap;
 use std::error::Error;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::time;
 
+/// Scan duration for a single-pass scan
+const SCAN_DURATION: Duration = Duration::from_secs(5);
+
+/// Scan duration for each pass of a multi-pass scan
+const SCAN_PASS_DURATION: Duration = Duration::from_secs(3);
+
+/// How much longer each adaptive retry scans for, and the longest scan it will try
+const ADAPTIVE_SCAN_STEP: Duration = Duration::from_secs(5);
+const ADAPTIVE_SCAN_MAX_DURATION: Duration = Duration::from_secs(15);
+
+/// How often `wait_for_known_lighthouses` checks whether every device has been heard
+const KNOWN_DEVICE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long to wait for a single peripheral's properties before skipping it
+const PROPERTIES_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Read a peripheral's advertised properties, giving up after a timeout.
+/// Failures are logged and returned as `None` so one misbehaving device
+/// can be skipped without aborting the whole scan.
+pub async fn read_properties(
+    peripheral: &Peripheral,
+    json_output: bool,
+) -> Option<PeripheralProperties> {
+    match time::timeout(PROPERTIES_TIMEOUT, peripheral.properties()).await {
+        Ok(Ok(properties)) => properties,
+        Ok(Err(e)) => {
+            error_log(
+                &format!(
+                    "Warning: Failed to read properties of {}: {}",
+                    peripheral.address(),
+                    e
+                ),
+                json_output,
+            );
+            None
+        }
+        Err(_) => {
+            error_log(
+                &format!(
+                    "Warning: Timed out reading properties of {}",
+                    peripheral.address()
+                ),
+                json_output,
+            );
+            None
+        }
+    }
+}
+
+/// Check whether advertised properties identify a Lighthouse base station
+/// (name prefix and manufacturer ID both match)
+pub fn is_lighthouse_properties(properties: &PeripheralProperties) -> bool {
+    let name = properties
+        .local_name
+        .as_deref()
+        .unwrap_or(UNKNOWN_DEVICE_NAME);
+    name.starts_with(LHB_PREFIX)
+        && properties
+            .manufacturer_data
+            .keys()
+            .any(|id| *id == LIGHTHOUSE_MANUFACTURER_ID)
+}
+
+/// Check whether advertised properties identify a Lighthouse base station using the
+/// configured criteria: the name and manufacturer ID as in `is_lighthouse_properties`,
+/// or, with `detect_by_service_uuid` set, the Lighthouse service UUID whatever the name
+pub fn detect_lighthouse(properties: &PeripheralProperties, settings: &Settings) -> bool {
+    is_lighthouse_properties(properties)
+        || (settings.detect_by_service_uuid
+            && properties.services.contains(&LIGHTHOUSE_SERVICE_UUID))
+}
+
+/// Check whether any of the peripherals is a Lighthouse base station
+async fn contains_lighthouse(peripherals: &[Peripheral], json_output: bool) -> bool {
+    let settings = load_settings().unwrap_or_default();
+    for peripheral in peripherals {
+        if let Some(properties) = read_properties(peripheral, json_output).await {
+            if detect_lighthouse(&properties, &settings) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
 /// Convert a peripheral to DeviceInfo
 pub async fn peripheral_to_device_info(
     peripheral: &Peripheral,
 ) -> Result<DeviceInfo, Box<dyn Error>> {
-    let properties = peripheral.properties().await?;
+    let properties = time::timeout(PROPERTIES_TIMEOUT, peripheral.properties())
+        .await
+        .map_err(|_| "Timed out reading device properties")??;
     let address = peripheral.address().to_string();
     let name = properties
         .as_ref()
         .and_then(|p| p.local_name.clone())
-        .unwrap_or_else(|| "Unknown".to_string());
+        .unwrap_or_else(|| UNKNOWN_DEVICE_NAME.to_string());
 
-    Ok(DeviceInfo { name, address })
+    Ok(DeviceInfo {
+        rssi: properties.as_ref().and_then(|p| p.rssi),
+        ..DeviceInfo::new(name, address)
+    })
 }
 
 /// Scan for devices and save them to cache
@@ -40,41 +140,410 @@ pub async fn scan_process_and_save_with_json(
     command_mode: u8,
     json_output: bool,
 ) -> Result<(), Box<dyn Error>> {
+    scan_process_and_save_with_opts(command_mode, &ScanOptions::default(), json_output).await
+}
+
+/// Scan, process results and optionally send a command using the given scan options
+pub async fn scan_process_and_save_with_opts(
+    command_mode: u8,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    scan_and_report(command_mode, options, json_output)
+        .await
+        .map(|_| ())
+}
+
+/// Scan and save devices, returning how many BLE devices were seen alongside the base stations
+pub async fn scan_with_report(
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<ScanReport, Box<dyn Error>> {
+    scan_and_report(0xFF, options, json_output).await
+}
+
+async fn scan_and_report(
+    command_mode: u8,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<ScanReport, Box<dyn Error>> {
+    let _adapter_guard = lock_adapter().await;
+    let started_at = Instant::now();
+
     // Initialize the Bluetooth manager
     let manager = Manager::new().await?;
 
-    // Get the list of available Bluetooth adapters
-    let adapters = manager.adapters().await?;
-    if adapters.is_empty() {
-        error_log("No Bluetooth adapters found", json_output);
-        return Err("No Bluetooth adapters found".into());
-    }
-
-    // Use the first adapter
-    let adapter = &adapters[0];
+    // Use the first available Bluetooth adapter
+    let adapter = match adapter_for_options(&manager, options, json_output).await {
+        Ok(adapter) => adapter,
+        Err(e) => {
+            error_log(&e.to_string(), json_output);
+            record_event(EventKind::Error, e.to_string());
+            return Err(e);
+        }
+    };
     log(
         &format!("Using adapter: {}", adapter.adapter_info().await?),
         json_output,
     );
 
-    // Start scanning for devices with a specified timeout
-    log("Scanning for Bluetooth devices...", json_output);
-    adapter.start_scan(ScanFilter::default()).await?;
+    scan_and_report_on(&adapter, command_mode, options, started_at, json_output).await
+}
+
+/// Scan on an adapter the caller already has, save the base stations found and
+/// optionally send them a command. The caller should hold `lock_adapter`.
+pub(crate) async fn scan_and_report_on(
+    adapter: &Adapter,
+    command_mode: u8,
+    options: &ScanOptions,
+    started_at: Instant,
+    json_output: bool,
+) -> Result<ScanReport, Box<dyn Error>> {
+    // Scan for devices, possibly over several passes
+    log(msg(Msg::ScanningBluetooth), json_output);
+    let peripherals = discover_peripherals(adapter, options, json_output).await?;
+    log(msg(Msg::ScanCompleted), json_output);
+    let total_seen = peripherals.len();
+
+    if options.save_raw_scan {
+        let raw_scan = collect_raw_scan(adapter, &peripherals, json_output).await;
+        match save_raw_scan(&raw_scan) {
+            Ok(path) => log(
+                &format!("Saved the raw scan to: {}", path.display()),
+                json_output,
+            ),
+            Err(e) => error_log(
+                &format!("Warning: Failed to save the raw scan: {}", e),
+                json_output,
+            ),
+        }
+    }
+
+    // Process the scan results and potentially send commands
+    let devices =
+        process_scan_results_with_opts(peripherals, command_mode, options, json_output).await?;
+
+    let report = ScanReport {
+        devices,
+        total_seen,
+        duration_ms: started_at.elapsed().as_millis() as u64,
+    };
+    record_event(
+        EventKind::Scan,
+        format!(
+            "Scan saw {} devices, {} base stations in {} ms",
+            report.total_seen,
+            report.devices.len(),
+            report.duration_ms
+        ),
+    );
+    Ok(report)
+}
+
+/// Scan for base stations, calling `on_found` with each one as soon as it's first
+/// heard rather than once the scan is over. A device whose name only arrives in a
+/// later advertisement is reported then. Returns every base station found, which
+/// are saved like the results of a normal scan.
+pub async fn stream_lighthouses<F>(
+    options: &ScanOptions,
+    mut on_found: F,
+    json_output: bool,
+) -> Result<Vec<DeviceInfo>, Box<dyn Error>>
+where
+    F: FnMut(&DeviceInfo) + Send,
+{
+    let _adapter_guard = lock_adapter().await;
+    let manager = Manager::new().await?;
+    let adapter = adapter_for_options(&manager, options, json_output).await?;
+    let settings = load_settings().unwrap_or_default();
+
+    // Subscribe before starting the scan so no early advertisement is missed
+    let mut events = adapter.events().await?;
+    let scan = ScanGuard::start(&adapter).await?;
+    let deadline =
+        time::sleep(options.scan_duration.unwrap_or(SCAN_DURATION) * options.passes.max(1));
+    tokio::pin!(deadline);
+
+    let mut found: Vec<DeviceInfo> = Vec::new();
+    loop {
+        let event = tokio::select! {
+            _ = &mut deadline => break,
+            event = events.next() => event,
+        };
+        let id = match event {
+            Some(CentralEvent::DeviceDiscovered(id)) | Some(CentralEvent::DeviceUpdated(id)) => id,
+            Some(_) => continue,
+            None => break,
+        };
+
+        let Ok(peripheral) = adapter.peripheral(&id).await else {
+            continue;
+        };
+        let address = peripheral.address().to_string();
+        if found.iter().any(|device| device.matches_address(&address))
+            || !settings.is_address_allowed(&address)
+        {
+            continue;
+        }
+        let Some(properties) = read_properties(&peripheral, json_output).await else {
+            continue;
+        };
+        if !detect_lighthouse(&properties, &settings) {
+            continue;
+        }
+
+        let name = properties
+            .local_name
+            .unwrap_or_else(|| UNKNOWN_DEVICE_NAME.to_string());
+        let device = DeviceInfo {
+            rssi: properties.rssi,
+            ..DeviceInfo::new(name, address)
+        };
+        on_found(&device);
+        found.push(device);
+    }
+
+    if let Err(e) = scan.stop().await {
+        log(
+            &format!("Warning: Failed to stop Bluetooth scan: {}", e),
+            json_output,
+        );
+    }
+    record_event(
+        EventKind::Scan,
+        format!("Streaming scan found {} base stations", found.len()),
+    );
+
+    if !options.no_save && !found.is_empty() {
+        if let Err(e) = save_scanned_devices_with_json(&found, options.replace_cache, json_output) {
+            error_log(&format!("Failed to save devices: {}", e), json_output);
+        }
+    }
+    Ok(found)
+}
+
+/// Gather everything the OS reports about the peripherals a scan saw. Base
+/// stations come first, then the rest by signal strength, so a size cap on the
+/// saved file drops the least interesting entries.
+async fn collect_raw_scan(
+    adapter: &Adapter,
+    peripherals: &[Peripheral],
+    json_output: bool,
+) -> RawScan {
+    let settings = load_settings().unwrap_or_default();
+    let mut raw_peripherals = Vec::with_capacity(peripherals.len());
+    for peripheral in peripherals {
+        let properties = read_properties(peripheral, json_output)
+            .await
+            .unwrap_or_default();
+        raw_peripherals.push(RawPeripheral {
+            address: peripheral.address().to_string(),
+            is_lighthouse: detect_lighthouse(&properties, &settings),
+            name: properties.local_name,
+            rssi: properties.rssi,
+            tx_power_level: properties.tx_power_level,
+            manufacturer_data: properties
+                .manufacturer_data
+                .into_iter()
+                .map(|(id, data)| (id, to_hex(&data)))
+                .collect(),
+            service_data: properties
+                .service_data
+                .into_iter()
+                .map(|(uuid, data)| (uuid.to_string(), to_hex(&data)))
+                .collect(),
+            services: properties
+                .services
+                .iter()
+                .map(|uuid| uuid.to_string())
+                .collect(),
+        });
+    }
+    raw_peripherals.sort_by_key(|p| (!p.is_lighthouse, std::cmp::Reverse(p.rssi)));
+
+    RawScan {
+        timestamp_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        adapter: adapter
+            .adapter_info()
+            .await
+            .unwrap_or_else(|_| "unknown".to_string()),
+        peripherals: raw_peripherals,
+        omitted: 0,
+    }
+}
 
-    // Delay to allow time for scanning
-    time::sleep(Duration::from_secs(5)).await;
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
 
-    // Get the list of discovered devices
+/// Find cached devices the platform already knows about, without scanning, so they
+/// can be connected to by address when a scan can't be started. BlueZ remembers
+/// devices it has seen before; elsewhere only devices found by a scan in this session
+/// are known, so this usually finds nothing.
+pub async fn known_peripherals(
+    adapter: &Adapter,
+    cached: &[DeviceInfo],
+) -> Result<Vec<Peripheral>, Box<dyn Error>> {
     let peripherals = adapter.peripherals().await?;
+    Ok(peripherals
+        .into_iter()
+        .filter(|peripheral| find_device(cached, &peripheral.address().to_string()).is_some())
+        .collect())
+}
 
-    // Process the scan results and potentially send commands
-    process_scan_results_with_json(peripherals, command_mode, json_output).await?;
+/// Scan with the given adapter and return the discovered peripherals.
+/// With more than one pass, the results of each pass are unioned by address,
+/// keeping the most complete record (known name, then strongest RSSI) per device.
+/// With adaptive scanning, a scan that finds no base stations is retried with
+/// longer durations up to a cap.
+pub async fn discover_peripherals(
+    adapter: &Adapter,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<Vec<Peripheral>, Box<dyn Error>> {
+    let passes = options.passes.max(1);
+    let mut duration = match options.scan_duration {
+        Some(duration) => duration,
+        None if passes == 1 => SCAN_DURATION,
+        None => SCAN_PASS_DURATION,
+    };
+
+    let mut peripherals = scan_passes(adapter, passes, duration, json_output).await?;
+
+    // Interference can delay advertisements, so retry with longer scans before giving up
+    while options.adaptive
+        && duration < ADAPTIVE_SCAN_MAX_DURATION
+        && !contains_lighthouse(&peripherals, json_output).await
+    {
+        duration = (duration + ADAPTIVE_SCAN_STEP).min(ADAPTIVE_SCAN_MAX_DURATION);
+        log(
+            &format!(
+                "No Lighthouse Base Stations found, retrying with a {}s scan...",
+                duration.as_secs()
+            ),
+            json_output,
+        );
+        peripherals = scan_passes(adapter, passes, duration, json_output).await?;
+    }
 
-    // Stop scanning
-    adapter.stop_scan().await?;
-    log("Scanning completed", json_output);
+    Ok(peripherals)
+}
 
-    Ok(())
+/// Run `passes` scans of `duration` each and union the results by address
+async fn scan_passes(
+    adapter: &Adapter,
+    passes: u32,
+    duration: Duration,
+    json_output: bool,
+) -> Result<Vec<Peripheral>, Box<dyn Error>> {
+    // Address -> (peripheral, has a known name, RSSI, number of passes it was seen in)
+    let mut seen: HashMap<String, (Peripheral, bool, Option<i16>, u32)> = HashMap::new();
+    let mut order = Vec::new();
+
+    for pass in 1..=passes {
+        if passes > 1 {
+            log(&format!("Scan pass {} of {}...", pass, passes), json_output);
+        }
+
+        let scan = ScanGuard::start(adapter).await?;
+
+        // Delay to allow time for scanning
+        time::sleep(duration).await;
+
+        let peripherals = adapter.peripherals().await;
+
+        if let Err(e) = scan.stop().await {
+            log(
+                &format!("Warning: Failed to stop Bluetooth scan: {}", e),
+                json_output,
+            );
+        }
+
+        let peripherals = match peripherals {
+            Ok(peripherals) => peripherals,
+            Err(e) => return Err(check_adapter_lost(adapter, e).await),
+        };
+
+        for peripheral in peripherals {
+            let address = peripheral.address().to_string();
+            let properties = read_properties(&peripheral, json_output).await;
+            let has_name = properties.as_ref().is_some_and(|p| p.local_name.is_some());
+            let rssi = properties.as_ref().and_then(|p| p.rssi);
+
+            match seen.get_mut(&address) {
+                Some(entry) => {
+                    entry.3 += 1;
+                    if (has_name, rssi) > (entry.1, entry.2) {
+                        entry.0 = peripheral;
+                        entry.1 = has_name;
+                        entry.2 = rssi;
+                    }
+                }
+                None => {
+                    order.push(address.clone());
+                    seen.insert(address, (peripheral, has_name, rssi, 1));
+                }
+            }
+        }
+    }
+
+    let mut peripherals = Vec::with_capacity(order.len());
+    for address in order {
+        if let Some((peripheral, _, _, found_in)) = seen.remove(&address) {
+            if passes > 1 {
+                log(
+                    &format!("{} seen in {} of {} passes", address, found_in, passes),
+                    json_output,
+                );
+            }
+            peripherals.push(peripheral);
+        }
+    }
+
+    Ok(peripherals)
+}
+
+/// Wait while a scan is running until every one of `known` has advertised,
+/// or until `max_wait` has passed. With no known devices this simply waits
+/// `max_wait`, so new base stations still get the full scan.
+pub async fn wait_for_known_lighthouses(
+    adapter: &Adapter,
+    known: &[DeviceInfo],
+    max_wait: Duration,
+    json_output: bool,
+) {
+    let deadline = Instant::now() + max_wait;
+    if known.is_empty() {
+        time::sleep(max_wait).await;
+        return;
+    }
+
+    while Instant::now() < deadline {
+        time::sleep(KNOWN_DEVICE_POLL_INTERVAL.min(deadline - Instant::now())).await;
+
+        let Ok(peripherals) = adapter.peripherals().await else {
+            continue;
+        };
+        // An RSSI means the device was heard in this scan, not just remembered by the OS
+        let mut heard = Vec::new();
+        for peripheral in &peripherals {
+            if let Some(properties) = read_properties(peripheral, json_output).await {
+                if properties.rssi.is_some() {
+                    heard.push(peripheral.address().to_string());
+                }
+            }
+        }
+        if known
+            .iter()
+            .all(|device| heard.iter().any(|address| device.matches_address(address)))
+        {
+            return;
+        }
+    }
 }
 
 /// Helper function to process scan results, save devices, and optionally send commands
@@ -93,27 +562,43 @@ pub async fn process_scan_results_with_json(
     command_mode: u8,
     json_output: bool,
 ) -> Result<(), Box<dyn Error>> {
+    process_scan_results_with_opts(
+        peripherals,
+        command_mode,
+        &ScanOptions::default(),
+        json_output,
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Helper function to process scan results using the given scan options.
+/// Returns the base stations that were found.
+pub async fn process_scan_results_with_opts(
+    peripherals: Vec<Peripheral>,
+    command_mode: u8,
+    options: &ScanOptions,
+    json_output: bool,
+) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
     if peripherals.is_empty() {
-        log("No devices found", json_output);
-        return Ok(());
+        log(msg(Msg::NoDevicesFound), json_output);
+        return Ok(Vec::new());
     }
 
-    log(
-        &format!("Found {} devices:", peripherals.len()),
-        json_output,
-    );
+    log(&msgf(Msg::FoundDevices, &[&peripherals.len()]), json_output);
 
     // Create a vector to store filtered lighthouse base stations
     let mut lighthouse_stations = Vec::new();
+    let settings = load_settings().unwrap_or_default();
 
     // Print information about each discovered device
     for (i, peripheral) in peripherals.iter().enumerate() {
-        let properties = peripheral.properties().await?;
+        let properties = read_properties(peripheral, json_output).await;
         let address = peripheral.address();
         let name = properties
             .as_ref()
             .and_then(|p| p.local_name.clone())
-            .unwrap_or_else(|| "Unknown".to_string());
+            .unwrap_or_else(|| UNKNOWN_DEVICE_NAME.to_string());
 
         log(
             &format!("Device {}: {} ({})", i + 1, name, address),
@@ -121,7 +606,9 @@ pub async fn process_scan_results_with_json(
         );
 
         // Check if device matches our filter criteria
-        let mut is_lighthouse = false;
+        let is_lighthouse = properties
+            .as_ref()
+            .is_some_and(|properties| detect_lighthouse(properties, &settings));
 
         // If available, print additional details
         if let Some(properties) = properties {
@@ -130,11 +617,9 @@ pub async fn process_scan_results_with_json(
             for (id, data) in manufacturer_data.iter() {
                 log(&format!("  Manufacturer ID: {}", id), json_output);
                 log(&format!("  Manufacturer Data: {:?}", data), json_output);
-
-                // Check if this is a Lighthouse device (matches both name and manufacturer ID)
-                if name.starts_with(LHB_PREFIX) && *id == LIGHTHOUSE_MANUFACTURER_ID {
-                    is_lighthouse = true;
-                }
+            }
+            if is_lighthouse && !is_lighthouse_properties(&properties) {
+                log("  Detected by the Lighthouse service UUID", json_output);
             }
 
             // Display services if available
@@ -148,7 +633,9 @@ pub async fn process_scan_results_with_json(
         }
 
         // If this is a lighthouse device, add it to our filtered list
-        if is_lighthouse {
+        if is_lighthouse && !settings.is_address_allowed(&address.to_string()) {
+            log("  Ignored: excluded by the allowlist/denylist", json_output);
+        } else if is_lighthouse {
             lighthouse_stations.push(peripheral.clone());
         }
 
@@ -157,15 +644,12 @@ pub async fn process_scan_results_with_json(
 
     // Display information about the filtered Lighthouse devices
     if lighthouse_stations.is_empty() {
-        log("No Lighthouse Base Stations found", json_output);
-        return Ok(());
+        log(msg(Msg::NoLighthousesFound), json_output);
+        return Ok(Vec::new());
     }
 
     log(
-        &format!(
-            "Found {} Lighthouse Base Stations:",
-            lighthouse_stations.len()
-        ),
+        &msgf(Msg::FoundLighthouses, &[&lighthouse_stations.len()]),
         json_output,
     );
 
@@ -173,12 +657,12 @@ pub async fn process_scan_results_with_json(
     let mut device_info_list = Vec::new();
 
     for (i, station) in lighthouse_stations.iter().enumerate() {
-        let properties = station.properties().await?;
+        let properties = read_properties(station, json_output).await;
         let address = station.address();
         let name = properties
             .as_ref()
             .and_then(|p| p.local_name.clone())
-            .unwrap_or_else(|| "Unknown".to_string());
+            .unwrap_or_else(|| UNKNOWN_DEVICE_NAME.to_string());
 
         log(
             &format!("Lighthouse {}: {} ({})", i + 1, name, address),
@@ -186,26 +670,38 @@ pub async fn process_scan_results_with_json(
         );
 
         // Add to our device info list for caching
-        let device_info = peripheral_to_device_info(station).await?;
-        device_info_list.push(device_info);
+        device_info_list.push(DeviceInfo {
+            rssi: properties.as_ref().and_then(|p| p.rssi),
+            ..DeviceInfo::new(name, address.to_string())
+        });
     }
 
+    settings.sort_devices(&mut device_info_list);
+
     // Save the device information to the config file
-    match save_devices(&device_info_list) {
-        Ok(_) => log(
-            "Successfully saved device information to config file",
-            json_output,
-        ),
-        Err(e) => log(
-            &format!("Failed to save device information: {}", e),
+    if options.no_save {
+        log(
+            "Saving is disabled, not updating the config file",
             json_output,
-        ),
+        );
+    } else {
+        match save_scanned_devices_with_json(&device_info_list, options.replace_cache, json_output)
+        {
+            Ok(_) => log(
+                "Successfully saved device information to config file",
+                json_output,
+            ),
+            Err(e) => log(
+                &format!("Failed to save device information: {}", e),
+                json_output,
+            ),
+        }
     }
 
     // If a command mode is requested (not 0xFF), send the command to the devices
     if command_mode != 0xFF {
-        handle_device_command_with_json(&lighthouse_stations, command_mode, json_output).await?;
+        command_devices_with_opts(&lighthouse_stations, command_mode, options, json_output).await?;
     }
 
-    Ok(())
+    Ok(device_info_list)
 }
@@ -0,0 +1,18 @@
+use std::future::Future;
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Run `operation` until it finishes or `token` is cancelled, whichever comes first.
+/// Returns `None` if the operation was cancelled.
+///
+/// Cancelling drops the operation at its next await point, which also releases
+/// the adapter lock, so a hung scan no longer blocks the next request.
+pub async fn run_cancellable<F: Future>(
+    token: &CancellationToken,
+    operation: F,
+) -> Option<F::Output> {
+    tokio::select! {
+        _ = token.cancelled() => None,
+        output = operation => Some(output),
+    }
+}
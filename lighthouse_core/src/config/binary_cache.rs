@@ -0,0 +1,180 @@
+// Compact binary encoding of the device cache, for callers that load it on every invocation.
+//
+// Layout (integers little-endian):
+//   magic "LHRS", format version (u8), device count (u32), then per device:
+//   name length (u16) + UTF-8 bytes, address length (u16) + UTF-8 bytes,
+//   capability flags (u8; bit 7 set when capabilities are known),
+//   and since version 2 the recent command outcomes: count (u8; 0xFF when none
+//   are recorded) + one bit per outcome, oldest in bit 0 (u16),
+//   since version 3 the last signal strength in dBm (i16; i16::MIN when unknown),
+//   and since version 4 the channel (u8; 0xFF when unknown)
+use crate::models::{DeviceCapabilities, DeviceInfo, DeviceReliability};
+use std::error::Error;
+
+const MAGIC: &[u8; 4] = b"LHRS";
+const VERSION: u8 = 4;
+
+const NO_RELIABILITY: u8 = 0xFF;
+const NO_RSSI: i16 = i16::MIN;
+const NO_CHANNEL: u8 = 0xFF;
+const MAX_OUTCOMES: usize = 16;
+
+const CAPS_KNOWN: u8 = 0x80;
+const CAP_POWER: u8 = 0x01;
+const CAP_POWER_STATE: u8 = 0x02;
+const CAP_CHANNEL: u8 = 0x04;
+const CAP_IDENTIFY: u8 = 0x08;
+const CAP_SERIAL_NUMBER: u8 = 0x10;
+
+/// Encode devices in the binary cache format
+pub fn encode_devices(devices: &[DeviceInfo]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut bytes = Vec::with_capacity(9 + devices.len() * 42);
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+    bytes.extend_from_slice(&u32::try_from(devices.len())?.to_le_bytes());
+
+    for device in devices {
+        write_str(&mut bytes, &device.name)?;
+        write_str(&mut bytes, &device.address)?;
+        bytes.push(device.capabilities.map_or(0, encode_capabilities));
+        encode_reliability(&mut bytes, device.reliability.as_ref());
+        bytes.extend_from_slice(&device.rssi.unwrap_or(NO_RSSI).to_le_bytes());
+        bytes.push(device.channel.unwrap_or(NO_CHANNEL));
+    }
+
+    Ok(bytes)
+}
+
+/// Decode devices from the binary cache format
+pub fn decode_devices(bytes: &[u8]) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
+    let mut reader = Reader { bytes };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err("Not a lighthouse-rs binary cache".into());
+    }
+    let version = reader.take(1)?[0];
+    if version == 0 || version > VERSION {
+        return Err(format!("Unsupported binary cache version {}", version).into());
+    }
+
+    let count = u32::from_le_bytes(reader.take(4)?.try_into()?) as usize;
+    let mut devices = Vec::with_capacity(count.min(1024));
+    for _ in 0..count {
+        let name = reader.read_str()?;
+        let address = reader.read_str()?;
+        let flags = reader.take(1)?[0];
+        let reliability = if version >= 2 {
+            decode_reliability(&mut reader)?
+        } else {
+            None
+        };
+        let rssi = if version >= 3 {
+            Some(i16::from_le_bytes(reader.take(2)?.try_into()?)).filter(|rssi| *rssi != NO_RSSI)
+        } else {
+            None
+        };
+        let channel = if version >= 4 {
+            Some(reader.take(1)?[0]).filter(|channel| *channel != NO_CHANNEL)
+        } else {
+            None
+        };
+        devices.push(DeviceInfo {
+            name,
+            address,
+            capabilities: decode_capabilities(flags),
+            reliability,
+            rssi,
+            channel,
+        });
+    }
+
+    if !reader.bytes.is_empty() {
+        return Err("Trailing data after the last device".into());
+    }
+    Ok(devices)
+}
+
+fn write_str(bytes: &mut Vec<u8>, value: &str) -> Result<(), Box<dyn Error>> {
+    bytes.extend_from_slice(&u16::try_from(value.len())?.to_le_bytes());
+    bytes.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+fn encode_capabilities(capabilities: DeviceCapabilities) -> u8 {
+    [
+        (capabilities.power, CAP_POWER),
+        (capabilities.power_state, CAP_POWER_STATE),
+        (capabilities.channel, CAP_CHANNEL),
+        (capabilities.identify, CAP_IDENTIFY),
+        (capabilities.serial_number, CAP_SERIAL_NUMBER),
+    ]
+    .iter()
+    .filter(|(supported, _)| *supported)
+    .fold(CAPS_KNOWN, |flags, (_, bit)| flags | bit)
+}
+
+fn decode_capabilities(flags: u8) -> Option<DeviceCapabilities> {
+    if flags & CAPS_KNOWN == 0 {
+        return None;
+    }
+    Some(DeviceCapabilities {
+        power: flags & CAP_POWER != 0,
+        power_state: flags & CAP_POWER_STATE != 0,
+        channel: flags & CAP_CHANNEL != 0,
+        identify: flags & CAP_IDENTIFY != 0,
+        serial_number: flags & CAP_SERIAL_NUMBER != 0,
+    })
+}
+
+fn encode_reliability(bytes: &mut Vec<u8>, reliability: Option<&DeviceReliability>) {
+    let Some(reliability) = reliability else {
+        bytes.push(NO_RELIABILITY);
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        return;
+    };
+    // Only the latest 16 outcomes fit, which is more than the window keeps
+    let recent = &reliability.recent[reliability.recent.len().saturating_sub(MAX_OUTCOMES)..];
+    let bits = recent
+        .iter()
+        .enumerate()
+        .filter(|(_, success)| **success)
+        .fold(0u16, |bits, (i, _)| bits | (1 << i));
+    bytes.push(recent.len() as u8);
+    bytes.extend_from_slice(&bits.to_le_bytes());
+}
+
+fn decode_reliability(reader: &mut Reader) -> Result<Option<DeviceReliability>, Box<dyn Error>> {
+    let count = reader.take(1)?[0];
+    let bits = u16::from_le_bytes(reader.take(2)?.try_into()?);
+    if count == NO_RELIABILITY {
+        return Ok(None);
+    }
+    if count as usize > MAX_OUTCOMES {
+        return Err(format!("Invalid command outcome count {}", count).into());
+    }
+    // Recording keeps no more than the current window
+    let mut reliability = DeviceReliability::default();
+    for i in 0..count {
+        reliability.record(bits & (1 << i) != 0);
+    }
+    Ok(Some(reliability))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        if self.bytes.len() < len {
+            return Err("Binary cache is truncated".into());
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn read_str(&mut self) -> Result<String, Box<dyn Error>> {
+        let len = u16::from_le_bytes(self.take(2)?.try_into()?) as usize;
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+}
@@ -1,42 +1,222 @@
-use crate::logging::log;
-use crate::models::DeviceInfo;
+mod binary_cache;
+mod settings;
+
+pub use binary_cache::{decode_devices, encode_devices};
+pub use settings::*;
+
+use crate::logging::{error_log, log};
+use crate::models::{DeviceInfo, DeviceReliability, RawScan};
 use std::error::Error;
 use std::fs::File;
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub const CONFIG_FILENAME: &str = "lighthouse_devices.json";
 
+pub const BINARY_CONFIG_FILENAME: &str = "lighthouse_devices.bin";
+
+pub const RAW_SCAN_FILENAME: &str = "lighthouse_raw_scan.json";
+
+/// Largest raw scan file written; peripherals are dropped from the end to stay under it
+pub const MAX_RAW_SCAN_BYTES: usize = 256 * 1024;
+
+pub const CONFIG_DIR_NAME: &str = "com.github.matty.lighthouse-manager";
+
+/// Environment variable naming the device config file to use instead of the default
+pub const CONFIG_PATH_ENV_VAR: &str = "LIGHTHOUSE_CONFIG";
+
+/// Config file set with `set_config_path_override`, which wins over `CONFIG_PATH_ENV_VAR`
+static CONFIG_PATH_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Directory holding the config files, given the platform's local data directory
+pub fn config_dir_for(base: &Path) -> PathBuf {
+    base.join(CONFIG_DIR_NAME)
+}
+
+/// Use `path` as the device config file from now on, e.g. from `--config-path`.
+/// The other config files (settings, log, history) are kept next to it.
+pub fn set_config_path_override(path: PathBuf) {
+    *CONFIG_PATH_OVERRIDE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner()) = Some(path);
+}
+
+/// Path of the device config file: the override set with `set_config_path_override`,
+/// then `LIGHTHOUSE_CONFIG`, then `lighthouse_devices.json` in the local data directory.
+/// An override naming an existing directory means `lighthouse_devices.json` inside it.
 pub fn get_config_path() -> Result<PathBuf, Box<dyn Error>> {
-    let base_dirs = directories::BaseDirs::new().ok_or("Failed to get user directories")?;
+    let overridden = CONFIG_PATH_OVERRIDE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .or_else(|| {
+            std::env::var_os(CONFIG_PATH_ENV_VAR)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        });
 
-    let config_dir = base_dirs
-        .data_local_dir()
-        .join("com.github.matty.lighthouse-manager");
+    let config_path = match overridden {
+        Some(path) if path.is_dir() => path.join(CONFIG_FILENAME),
+        Some(path) => path,
+        None => {
+            let base_dirs = directories::BaseDirs::new().ok_or("Failed to get user directories")?;
+            config_dir_for(base_dirs.data_local_dir()).join(CONFIG_FILENAME)
+        }
+    };
 
     // Create the directory if it doesn't exist
-    std::fs::create_dir_all(&config_dir)?;
+    if let Some(config_dir) = config_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(config_dir)?;
+    }
 
-    Ok(config_dir.join(CONFIG_FILENAME))
+    Ok(config_path)
 }
 
-pub fn save_devices(devices: &Vec<DeviceInfo>) -> Result<(), Box<dyn Error>> {
+/// Path of the device cache in the given format
+pub fn get_device_cache_path(format: CacheFormat) -> Result<PathBuf, Box<dyn Error>> {
+    let config_path = get_config_path()?;
+    Ok(match format {
+        CacheFormat::Json => config_path,
+        CacheFormat::Binary => config_path.with_file_name(BINARY_CONFIG_FILENAME),
+    })
+}
+
+fn configured_cache_format() -> CacheFormat {
+    load_settings()
+        .map(|settings| settings.cache_format)
+        .unwrap_or_default()
+}
+
+fn other_cache_format(format: CacheFormat) -> CacheFormat {
+    match format {
+        CacheFormat::Json => CacheFormat::Binary,
+        CacheFormat::Binary => CacheFormat::Json,
+    }
+}
+
+pub fn save_devices(devices: &[DeviceInfo]) -> Result<(), Box<dyn Error>> {
     save_devices_with_json(devices, false)
 }
 
+/// Save the base stations a scan found. Unless `replace` is set they are merged into
+/// the cache, so devices that were switched off or out of range during the scan stay
+/// known. With `replace`, the cache is overwritten with only the devices found.
+pub fn save_scanned_devices_with_json(
+    found: &[DeviceInfo],
+    replace: bool,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    if replace {
+        return save_devices_with_json(found, json_output);
+    }
+    let cached = load_devices_with_json(true).unwrap_or_default();
+    save_devices_with_json(&merge_devices(&cached, found), json_output)
+}
+
+/// Union of `cached` and `found` by address. Rediscovered devices take the scanned
+/// entry, so their name and signal strength are updated; fields the scan doesn't
+/// provide are carried over from the cache when saving.
+pub fn merge_devices(cached: &[DeviceInfo], found: &[DeviceInfo]) -> Vec<DeviceInfo> {
+    let mut merged: Vec<DeviceInfo> = cached
+        .iter()
+        .map(|device| {
+            find_device(found, &device.address)
+                .unwrap_or(device)
+                .clone()
+        })
+        .collect();
+    merged.extend(
+        found
+            .iter()
+            .filter(|device| find_device(cached, &device.address).is_none())
+            .cloned(),
+    );
+    merged
+}
+
 pub fn save_devices_with_json(
-    devices: &Vec<DeviceInfo>,
+    devices: &[DeviceInfo],
     json_output: bool,
 ) -> Result<(), Box<dyn Error>> {
-    let config_path = get_config_path()?;
+    let format = configured_cache_format();
+    let config_path = get_device_cache_path(format)?;
     log(
         &format!("Saving device info to: {}", config_path.display()),
         json_output,
     );
 
-    let json = serde_json::to_string_pretty(devices)?;
-    let mut file = File::create(config_path)?;
-    file.write_all(json.as_bytes())?;
+    let mut devices = devices.to_vec();
+    let duplicates = dedupe_devices(&mut devices);
+    if duplicates > 0 {
+        log(
+            &format!("Dropped {} duplicate devices before saving", duplicates),
+            json_output,
+        );
+    }
+
+    // Keep capabilities, command outcomes, the last signal strength, the channel and
+    // the name from earlier runs for devices saved again without them
+    if devices.iter().any(|device| {
+        device.capabilities.is_none()
+            || device.reliability.is_none()
+            || device.rssi.is_none()
+            || device.channel.is_none()
+            || !device.has_name()
+    }) {
+        let cached = load_devices_with_json(true).unwrap_or_default();
+        for device in devices.iter_mut() {
+            let Some(previous) = find_device(&cached, &device.address) else {
+                continue;
+            };
+            if device.capabilities.is_none() {
+                device.capabilities = previous.capabilities;
+            }
+            if device.reliability.is_none() {
+                device.reliability = previous.reliability.clone();
+            }
+            if device.rssi.is_none() {
+                device.rssi = previous.rssi;
+            }
+            if device.channel.is_none() {
+                device.channel = previous.channel;
+            }
+            // A scan that caught the device mid-advertisement has no name for it
+            if !device.has_name() && previous.has_name() {
+                device.name = previous.name.clone();
+            }
+        }
+    }
+
+    let settings = load_settings().unwrap_or_default();
+    settings.sort_devices(&mut devices);
+    if settings.skip_unnamed_devices {
+        let count = devices.len();
+        devices.retain(DeviceInfo::has_name);
+        if devices.len() < count {
+            log(
+                &format!(
+                    "Not saving {} devices that were seen without a name",
+                    count - devices.len()
+                ),
+                json_output,
+            );
+        }
+    }
+
+    let contents = match format {
+        CacheFormat::Json => serde_json::to_string_pretty(&devices)?.into_bytes(),
+        CacheFormat::Binary => encode_devices(&devices)?,
+    };
+    write_atomically(&config_path, &contents)?;
+
+    // The data now lives in the configured format, so a leftover cache in the other
+    // format would only go stale and be picked up again after switching back
+    let other_path = get_device_cache_path(other_cache_format(format))?;
+    if other_path.exists() {
+        std::fs::remove_file(other_path)?;
+    }
 
     Ok(())
 }
@@ -46,10 +226,15 @@ pub fn load_devices() -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
 }
 
 pub fn load_devices_with_json(json_output: bool) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
-    let config_path = get_config_path()?;
-
+    // Fall back to a cache in the other format, which is migrated on the next save
+    let mut format = configured_cache_format();
+    let mut config_path = get_device_cache_path(format)?;
     if !config_path.exists() {
-        return Ok(Vec::new());
+        format = other_cache_format(format);
+        config_path = get_device_cache_path(format)?;
+        if !config_path.exists() {
+            return Ok(Vec::new());
+        }
     }
 
     log(
@@ -57,10 +242,324 @@ pub fn load_devices_with_json(json_output: bool) -> Result<Vec<DeviceInfo>, Box<
         json_output,
     );
 
+    let mut devices = read_device_cache(&config_path, format, json_output)?;
+    // Caches written before devices were kept sorted may be in any order
+    load_settings()
+        .unwrap_or_default()
+        .sort_devices(&mut devices);
+    Ok(devices)
+}
+
+/// Read the device cache at `config_path`. A file that can't be parsed is moved aside
+/// and an empty list returned, so the next scan can start from a clean cache.
+fn read_device_cache(
+    config_path: &Path,
+    format: CacheFormat,
+    json_output: bool,
+) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
     let mut file = File::open(config_path)?;
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
 
-    let devices: Vec<DeviceInfo> = serde_json::from_str(&contents)?;
-    Ok(devices)
+    let devices: Result<Vec<DeviceInfo>, Box<dyn Error>> = match format {
+        CacheFormat::Json => serde_json::from_slice(&contents).map_err(Into::into),
+        CacheFormat::Binary => decode_devices(&contents),
+    };
+    match devices {
+        Ok(devices) => Ok(devices),
+        Err(e) => {
+            let backup_path = backup_corrupt_config(config_path)?;
+            error_log(
+                &format!(
+                    "Warning: device config is corrupted ({}), moved it to {} and continuing with no known devices",
+                    e,
+                    backup_path.display()
+                ),
+                json_output,
+            );
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Path next to `path` with `suffix` added to its file name, e.g. `lighthouse_devices.json.bak`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+/// Replace the contents of `path` so that it's never left half-written, even if the
+/// process is killed. The data is written to `<name>.tmp` and renamed over `path`
+/// only once it's complete. The previous contents are kept in `<name>.bak`.
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+    let tmp_path = with_suffix(path, ".tmp");
+    let written = File::create(&tmp_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(e) = written {
+        std::fs::remove_file(&tmp_path).ok();
+        return Err(e.into());
+    }
+
+    // Copied rather than moved so there's a complete file at `path` throughout
+    if path.exists() {
+        std::fs::copy(path, with_suffix(path, ".bak"))?;
+    }
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Rename a corrupted config file to `<name>.corrupt-<unix timestamp>` and return the new path
+fn backup_corrupt_config(config_path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let file_name = config_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| CONFIG_FILENAME.to_string());
+    let backup_path = config_path.with_file_name(format!("{}.corrupt-{}", file_name, timestamp));
+
+    std::fs::rename(config_path, &backup_path)?;
+    Ok(backup_path)
+}
+
+/// Clear the recorded command outcomes of the device with `address`, or of every
+/// cached device. Returns how many devices were reset.
+pub fn reset_reliability(address: Option<&str>) -> Result<usize, Box<dyn Error>> {
+    let mut devices = load_devices_with_json(true)?;
+    let mut reset = 0;
+    for device in devices
+        .iter_mut()
+        .filter(|device| address.is_none_or(|address| device.matches_address(address)))
+    {
+        // An empty record rather than `None`, which saving would fill from the old cache
+        device.reliability = Some(DeviceReliability::default());
+        reset += 1;
+    }
+    if reset > 0 {
+        save_devices_with_json(&devices, true)?;
+    }
+    Ok(reset)
+}
+
+/// Write a raw scan next to the device cache, replacing the previous one.
+/// Peripherals at the end of the list are left out if the file would be larger
+/// than `MAX_RAW_SCAN_BYTES`. Returns the path written.
+pub fn save_raw_scan(scan: &RawScan) -> Result<PathBuf, Box<dyn Error>> {
+    let path = get_config_path()?.with_file_name(RAW_SCAN_FILENAME);
+
+    let mut scan = scan.clone();
+    let mut contents = serde_json::to_vec_pretty(&scan)?;
+    while contents.len() > MAX_RAW_SCAN_BYTES && !scan.peripherals.is_empty() {
+        // Drop in chunks, since re-serializing after every single removal is slow
+        let drop = (scan.peripherals.len() / 10).max(1);
+        scan.peripherals.truncate(scan.peripherals.len() - drop);
+        scan.omitted += drop;
+        contents = serde_json::to_vec_pretty(&scan)?;
+    }
+
+    std::fs::write(&path, contents)?;
+    Ok(path)
+}
+
+/// Find a device by address in a list of devices, ignoring case and separators
+pub fn find_device<'a>(devices: &'a [DeviceInfo], address: &str) -> Option<&'a DeviceInfo> {
+    devices
+        .iter()
+        .find(|device| device.matches_address(address))
+}
+
+/// Keep one entry per address, in the order each address was first seen. Where a scan
+/// reported a device twice, e.g. once before it advertised its name, the entry with a
+/// name wins. Returns the number of entries removed.
+pub fn dedupe_devices(devices: &mut Vec<DeviceInfo>) -> usize {
+    let count = devices.len();
+    let mut unique: Vec<DeviceInfo> = Vec::with_capacity(count);
+    for device in devices.drain(..) {
+        match unique
+            .iter_mut()
+            .find(|kept| kept.matches_address(&device.address))
+        {
+            Some(kept) if !kept.has_name() && device.has_name() => *kept = device,
+            Some(_) => {}
+            None => unique.push(device),
+        }
+    }
+    *devices = unique;
+    count - devices.len()
+}
+
+/// Get a cached device by address, or `None` if it is unknown or the cache can't be read
+pub fn get_device(address: &str) -> Option<DeviceInfo> {
+    let devices = load_devices_with_json(true).ok()?;
+    find_device(&devices, address).cloned()
+}
+
+/// Check whether the given address belongs to a cached lighthouse
+pub fn is_known_device(address: &str) -> bool {
+    get_device(address).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh directory under the system temp dir for a test's files
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("lighthouse-core-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn config_dir_for_appends_the_config_dir_name() {
+        assert_eq!(
+            config_dir_for(Path::new("/base")),
+            Path::new("/base").join(CONFIG_DIR_NAME)
+        );
+    }
+
+    #[test]
+    fn find_device_finds_a_cached_address() {
+        let devices = [
+            DeviceInfo::new("LHB-1", "AA:BB:CC:DD:EE:01"),
+            DeviceInfo::new("LHB-2", "AA:BB:CC:DD:EE:02"),
+        ];
+
+        let found = find_device(&devices, "AA:BB:CC:DD:EE:02").map(|d| d.name.as_str());
+        assert_eq!(found, Some("LHB-2"));
+    }
+
+    #[test]
+    fn find_device_returns_none_for_an_unknown_address() {
+        let devices = [DeviceInfo::new("LHB-1", "AA:BB:CC:DD:EE:01")];
+
+        assert!(find_device(&devices, "AA:BB:CC:DD:EE:99").is_none());
+        assert!(find_device(&[], "AA:BB:CC:DD:EE:01").is_none());
+    }
+
+    #[test]
+    fn find_device_ignores_case_and_separators() {
+        let devices = [DeviceInfo::new("LHB-1", "aa:bb:cc:dd:ee:0f")];
+
+        for address in [
+            "AA:BB:CC:DD:EE:0F",
+            "aa-bb-cc-dd-ee-0f",
+            " Aa:Bb:Cc:Dd:Ee:0F ",
+        ] {
+            assert!(find_device(&devices, address).is_some(), "{}", address);
+        }
+    }
+
+    #[test]
+    fn malformed_cache_is_moved_aside_and_read_as_empty() {
+        let dir = test_dir("malformed-cache");
+        let path = dir.join(CONFIG_FILENAME);
+        std::fs::write(&path, b"[{\"name\": \"LHB-1\", \"addr").unwrap();
+
+        let devices = read_device_cache(&path, CacheFormat::Json, true).unwrap();
+
+        assert!(devices.is_empty());
+        assert!(!path.exists());
+        let moved: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(moved.len(), 1);
+        let prefix = format!("{}.corrupt-", CONFIG_FILENAME);
+        let timestamp = moved[0].strip_prefix(&prefix).expect(&moved[0]);
+        assert!(timestamp.parse::<u64>().is_ok(), "{}", moved[0]);
+        assert_eq!(
+            std::fs::read(dir.join(&moved[0])).unwrap(),
+            b"[{\"name\": \"LHB-1\", \"addr"
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn write_atomically_keeps_the_previous_contents_in_a_backup() {
+        let dir = test_dir("atomic-write");
+        let path = dir.join(CONFIG_FILENAME);
+        std::fs::write(&path, "old").unwrap();
+
+        write_atomically(&path, b"new").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(
+            std::fs::read_to_string(with_suffix(&path, ".bak")).unwrap(),
+            "old"
+        );
+        assert!(!with_suffix(&path, ".tmp").exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn failed_write_leaves_the_file_and_backup_untouched() {
+        let dir = test_dir("failed-atomic-write");
+        let path = dir.join(CONFIG_FILENAME);
+        let backup_path = with_suffix(&path, ".bak");
+        std::fs::write(&path, "current").unwrap();
+        std::fs::write(&backup_path, "previous").unwrap();
+        // A directory where the temporary file should go makes creating it fail
+        std::fs::create_dir(with_suffix(&path, ".tmp")).unwrap();
+
+        assert!(write_atomically(&path, b"new").is_err());
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "current");
+        assert_eq!(std::fs::read_to_string(&backup_path).unwrap(), "previous");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn dedupe_keeps_one_named_entry_per_address_in_first_seen_order() {
+        let mut devices = vec![
+            DeviceInfo::new("Unknown", "aa:bb:cc:dd:ee:01"),
+            DeviceInfo::new("LHB-2", "AA:BB:CC:DD:EE:02"),
+            DeviceInfo::new("LHB-1", "AA-BB-CC-DD-EE-01"),
+            DeviceInfo::new("LHB-2-again", "aa:bb:cc:dd:ee:02"),
+        ];
+
+        assert_eq!(dedupe_devices(&mut devices), 2);
+
+        let names: Vec<&str> = devices.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["LHB-1", "LHB-2"]);
+        assert_eq!(devices[0].address, "AA-BB-CC-DD-EE-01");
+        assert_eq!(devices[1].address, "AA:BB:CC:DD:EE:02");
+    }
+
+    #[test]
+    fn get_device_and_is_known_device_look_up_the_cache() {
+        let dir = test_dir("known-device");
+        set_config_path_override(dir.join(CONFIG_FILENAME));
+        save_devices_with_json(&[DeviceInfo::new("LHB-1", "AA:BB:CC:DD:EE:01")], true).unwrap();
+
+        assert_eq!(
+            get_device("AA:BB:CC:DD:EE:01").map(|d| d.name),
+            Some("LHB-1".to_string())
+        );
+        assert!(is_known_device("AA:BB:CC:DD:EE:01"));
+
+        assert!(get_device("AA:BB:CC:DD:EE:99").is_none());
+        assert!(!is_known_device("AA:BB:CC:DD:EE:99"));
+
+        for address in [
+            "aa:bb:cc:dd:ee:01",
+            "AA-BB-CC-DD-EE-01",
+            "aA-bB-cC-dD-eE-01",
+        ] {
+            assert!(get_device(address).is_some(), "{}", address);
+            assert!(is_known_device(address), "{}", address);
+        }
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
 }
@@ -0,0 +1,389 @@
+use crate::config::get_config_path;
+use crate::models::{
+    normalize_address, render_display_template, DeviceInfo, DisplayValues, StateBytes,
+    DEFAULT_DISPLAY_TEMPLATE,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub const SETTINGS_FILENAME: &str = "lighthouse_settings.json";
+
+/// Settings that `Settings::set` can change. Lists and maps such as the allowlist,
+/// groups and aliases have their own commands or are edited in the file.
+pub const SETTABLE_KEYS: &[&str] = &[
+    "default_adapter",
+    "display_template",
+    "cache_format",
+    "event_log_capacity",
+    "weak_signal_threshold",
+    "detect_by_service_uuid",
+    "skip_unnamed_devices",
+    "desktop_notifications",
+];
+
+/// Largest accepted `event_log_capacity`
+const MAX_EVENT_LOG_CAPACITY: usize = 10_000;
+
+/// Signal strength, in dBm, below which a base station is considered too far away to connect reliably
+pub const DEFAULT_WEAK_SIGNAL_THRESHOLD: i16 = -90;
+
+/// On-disk format of the device cache
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheFormat {
+    /// Human-editable `lighthouse_devices.json`
+    #[default]
+    Json,
+    /// Compact `lighthouse_devices.bin`, quicker to load
+    Binary,
+}
+
+/// User preferences, stored next to the device cache
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Settings {
+    /// Adapter to use when none is given explicitly, matched by index or name
+    pub default_adapter: Option<String>,
+    /// If non-empty, only these addresses are ever treated as lighthouses
+    pub allowlist: Vec<String>,
+    /// Addresses that are never treated as lighthouses
+    pub denylist: Vec<String>,
+    /// Named groups of device addresses, e.g. imported from SteamVR playspaces
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// Addresses and group names that automatic standby leaves alone, e.g. a base
+    /// station shared with a setup that has to stay on
+    pub standby_exclusions: Vec<String>,
+    /// User-assigned nicknames, keyed by normalized device address
+    pub aliases: BTreeMap<String, String>,
+    /// How devices are listed, e.g. `"{nickname} [{channel}] {short_addr}"`
+    pub display_template: Option<String>,
+    /// Format of the device cache; switching migrates the cache on its next save
+    pub cache_format: CacheFormat,
+    /// Number of events kept in the in-memory event log (default: 200)
+    pub event_log_capacity: Option<usize>,
+    /// Warn before commanding devices whose signal is below this many dBm (default: -90)
+    pub weak_signal_threshold: Option<i16>,
+    /// Also treat devices advertising the Lighthouse service UUID as base stations,
+    /// whatever their name
+    pub detect_by_service_uuid: bool,
+    /// Leave devices that have never been seen with a name out of the device cache
+    pub skip_unnamed_devices: bool,
+    /// Show a desktop notification when the SteamVR handlers power the base stations
+    /// on or off. Needs a build with the `notifications` feature.
+    pub desktop_notifications: bool,
+    /// Power state values by firmware revision, for `--verify` on firmware that
+    /// reports its states differently. A key matches every revision starting with it.
+    pub state_bytes: BTreeMap<String, StateBytes>,
+}
+
+impl Settings {
+    /// Check an address against the allowlist and denylist.
+    /// The denylist always wins; an empty allowlist allows every other address.
+    pub fn is_address_allowed(&self, address: &str) -> bool {
+        let address = normalize_address(address);
+        let listed = |list: &[String]| list.iter().any(|a| normalize_address(a) == address);
+
+        !listed(&self.denylist) && (self.allowlist.is_empty() || listed(&self.allowlist))
+    }
+
+    /// Whether the device is in `standby_exclusions`, by its address or a group it belongs to
+    pub fn is_standby_excluded(&self, address: &str) -> bool {
+        let address = normalize_address(address);
+        self.standby_exclusions.iter().any(|entry| {
+            normalize_address(entry) == address
+                || self.groups.get(entry).is_some_and(|members| {
+                    members
+                        .iter()
+                        .any(|member| normalize_address(member) == address)
+                })
+        })
+    }
+
+    /// Power state values to verify commands with on the given firmware revision.
+    /// The longest matching key wins, and revisions that match none use the V2 values.
+    pub fn state_bytes_for(&self, firmware_revision: Option<&str>) -> StateBytes {
+        firmware_revision
+            .and_then(|revision| {
+                self.state_bytes
+                    .iter()
+                    .filter(|(key, _)| revision.starts_with(key.as_str()))
+                    .max_by_key(|(key, _)| key.len())
+            })
+            .map(|(_, state_bytes)| state_bytes.clone())
+            .unwrap_or_default()
+    }
+
+    /// Name to show for a device: its nickname if it has one, otherwise its advertised name
+    pub fn display_name<'a>(&'a self, device: &'a DeviceInfo) -> &'a str {
+        self.aliases
+            .get(&normalize_address(&device.address))
+            .map(String::as_str)
+            .unwrap_or(&device.name)
+    }
+
+    /// Sort devices by display name, then address, so lists come out in the same
+    /// order every time whatever order the platform reported them in
+    pub fn sort_devices(&self, devices: &mut [DeviceInfo]) {
+        devices.sort_by_cached_key(|device| {
+            (
+                self.display_name(device).to_lowercase(),
+                normalize_address(&device.address),
+            )
+        });
+    }
+
+    /// Values for the display template that can be taken from a cached device
+    pub fn display_values<'a>(&'a self, device: &'a DeviceInfo) -> DisplayValues<'a> {
+        DisplayValues {
+            nickname: self
+                .aliases
+                .get(&normalize_address(&device.address))
+                .map(String::as_str),
+            name: &device.name,
+            address: &device.address,
+            channel: device.channel,
+            ..Default::default()
+        }
+    }
+
+    /// Signal strength below which devices get a warning before being commanded
+    pub fn weak_signal_threshold(&self) -> i16 {
+        self.weak_signal_threshold
+            .unwrap_or(DEFAULT_WEAK_SIGNAL_THRESHOLD)
+    }
+
+    /// Current value of a setting as JSON, or `None` if there's no setting called `key`
+    pub fn get(&self, key: &str) -> Option<serde_json::Value> {
+        match serde_json::to_value(self).ok()? {
+            serde_json::Value::Object(mut settings) => settings.remove(key),
+            _ => None,
+        }
+    }
+
+    /// Parse `value` for the setting `key`, check it and store it.
+    /// An empty value or `none` resets an optional setting to its default.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        let value = value.trim();
+        let cleared = value.is_empty() || value.eq_ignore_ascii_case("none");
+        match key {
+            "default_adapter" => {
+                self.default_adapter = Some(value.to_string()).filter(|_| !cleared);
+            }
+            "display_template" => {
+                self.display_template = Some(value.to_string()).filter(|_| !cleared);
+            }
+            "cache_format" => {
+                self.cache_format = match value.to_ascii_lowercase().as_str() {
+                    "json" => CacheFormat::Json,
+                    "binary" => CacheFormat::Binary,
+                    _ => return Err("cache_format must be json or binary".into()),
+                };
+            }
+            "event_log_capacity" => {
+                self.event_log_capacity = match cleared {
+                    true => None,
+                    false => Some(parse_in_range(key, value, 0..=MAX_EVENT_LOG_CAPACITY)?),
+                };
+            }
+            "weak_signal_threshold" => {
+                self.weak_signal_threshold = match cleared {
+                    true => None,
+                    false => Some(parse_in_range(key, value, -127..=0)?),
+                };
+            }
+            "detect_by_service_uuid" => self.detect_by_service_uuid = parse_bool(key, value)?,
+            "skip_unnamed_devices" => self.skip_unnamed_devices = parse_bool(key, value)?,
+            "desktop_notifications" => self.desktop_notifications = parse_bool(key, value)?,
+            _ if self.get(key).is_some() => {
+                return Err(
+                    format!("{} can't be set directly, see --help for its commands", key).into(),
+                );
+            }
+            _ => {
+                return Err(format!(
+                    "Unknown setting '{}', expected one of: {}",
+                    key,
+                    SETTABLE_KEYS.join(", ")
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Render the configured display template, or the default one, with the given values
+    pub fn format_display(&self, values: &DisplayValues) -> String {
+        let template = self
+            .display_template
+            .as_deref()
+            .unwrap_or(DEFAULT_DISPLAY_TEMPLATE);
+        render_display_template(template, values)
+    }
+
+    /// Render the display template for a cached device
+    pub fn format_device(&self, device: &DeviceInfo) -> String {
+        self.format_display(&self.display_values(device))
+    }
+}
+
+/// Parse an on/off setting
+fn parse_bool(key: &str, value: &str) -> Result<bool, Box<dyn Error>> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(format!("{} must be true or false", key).into()),
+    }
+}
+
+/// Parse a number and check that it's within `range`
+fn parse_in_range<T>(key: &str, value: &str, range: RangeInclusive<T>) -> Result<T, Box<dyn Error>>
+where
+    T: FromStr + PartialOrd + Display,
+{
+    match value.parse::<T>() {
+        Ok(number) if range.contains(&number) => Ok(number),
+        _ => Err(format!(
+            "{} must be a number from {} to {}",
+            key,
+            range.start(),
+            range.end()
+        )
+        .into()),
+    }
+}
+
+pub fn get_settings_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(get_config_path()?.with_file_name(SETTINGS_FILENAME))
+}
+
+/// Load settings, falling back to defaults if the file doesn't exist yet
+pub fn load_settings() -> Result<Settings, Box<dyn Error>> {
+    let path = get_settings_path()?;
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+
+    let contents = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+pub fn save_settings(settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let path = get_settings_path()?;
+    let json = serde_json::to_string_pretty(settings)?;
+    fs::write(path, json)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(addresses: &[&str]) -> Vec<String> {
+        addresses.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn empty_lists_allow_every_address() {
+        let settings = Settings::default();
+
+        assert!(settings.is_address_allowed("AA:BB:CC:DD:EE:01"));
+    }
+
+    #[test]
+    fn allowlist_only_allows_listed_addresses() {
+        let settings = Settings {
+            allowlist: list(&["AA:BB:CC:DD:EE:01"]),
+            ..Default::default()
+        };
+
+        assert!(settings.is_address_allowed("AA:BB:CC:DD:EE:01"));
+        assert!(!settings.is_address_allowed("AA:BB:CC:DD:EE:02"));
+    }
+
+    #[test]
+    fn denylist_only_blocks_listed_addresses() {
+        let settings = Settings {
+            denylist: list(&["AA:BB:CC:DD:EE:01"]),
+            ..Default::default()
+        };
+
+        assert!(!settings.is_address_allowed("AA:BB:CC:DD:EE:01"));
+        assert!(settings.is_address_allowed("AA:BB:CC:DD:EE:02"));
+    }
+
+    #[test]
+    fn denylist_wins_over_allowlist() {
+        let settings = Settings {
+            allowlist: list(&["AA:BB:CC:DD:EE:01", "AA:BB:CC:DD:EE:02"]),
+            denylist: list(&["AA:BB:CC:DD:EE:02"]),
+            ..Default::default()
+        };
+
+        assert!(settings.is_address_allowed("AA:BB:CC:DD:EE:01"));
+        assert!(!settings.is_address_allowed("AA:BB:CC:DD:EE:02"));
+        assert!(!settings.is_address_allowed("AA:BB:CC:DD:EE:03"));
+    }
+
+    #[test]
+    fn lists_match_addresses_in_any_case_and_separator() {
+        let settings = Settings {
+            allowlist: list(&["aa:bb:cc:dd:ee:0f", "AA-BB-CC-DD-EE-10"]),
+            denylist: list(&["aa-bb-cc-dd-ee-10"]),
+            ..Default::default()
+        };
+
+        assert!(settings.is_address_allowed("AA:BB:CC:DD:EE:0F"));
+        assert!(settings.is_address_allowed("aa-bb-cc-dd-ee-0f"));
+        assert!(!settings.is_address_allowed("AA:BB:CC:DD:EE:10"));
+    }
+
+    #[test]
+    fn sort_devices_gives_the_same_order_whatever_the_input_order() {
+        let mut settings = Settings::default();
+        settings
+            .aliases
+            .insert("AA:BB:CC:DD:EE:04".to_string(), "Attic".to_string());
+        let devices = [
+            DeviceInfo::new("LHB-B", "AA:BB:CC:DD:EE:01"),
+            // Same name as the next two, so the address breaks the tie
+            DeviceInfo::new("lhb-a", "AA:BB:CC:DD:EE:03"),
+            DeviceInfo::new("LHB-A", "aa:bb:cc:dd:ee:02"),
+            DeviceInfo::new("LHB-A", "AA-BB-CC-DD-EE-05"),
+            // Sorted by its nickname rather than its name
+            DeviceInfo::new("LHB-Z", "AA:BB:CC:DD:EE:04"),
+        ];
+        let expected = [
+            "AA:BB:CC:DD:EE:04",
+            "aa:bb:cc:dd:ee:02",
+            "AA:BB:CC:DD:EE:03",
+            "AA-BB-CC-DD-EE-05",
+            "AA:BB:CC:DD:EE:01",
+        ];
+
+        // Every rotation of the input, forwards and reversed
+        for rotation in 0..devices.len() {
+            for reverse in [false, true] {
+                let mut shuffled = devices.to_vec();
+                shuffled.rotate_left(rotation);
+                if reverse {
+                    shuffled.reverse();
+                }
+
+                settings.sort_devices(&mut shuffled);
+
+                let addresses: Vec<&str> = shuffled.iter().map(|d| d.address.as_str()).collect();
+                assert_eq!(
+                    addresses, expected,
+                    "rotation {}, reversed {}",
+                    rotation, reverse
+                );
+            }
+        }
+    }
+}
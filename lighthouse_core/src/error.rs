@@ -0,0 +1,87 @@
+// Error types for conditions callers may want to handle or explain specifically
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum LighthouseError {
+    /// The platform reports no Bluetooth adapters at all
+    #[cfg_attr(
+        windows,
+        error("No Bluetooth adapters found. Check that Bluetooth is listed in Device Manager and that its driver is installed.")
+    )]
+    #[cfg_attr(
+        not(windows),
+        error(
+            "No Bluetooth adapters found. Check that a Bluetooth adapter is connected and enabled."
+        )
+    )]
+    NoAdapter,
+
+    /// No adapter matches the requested index or name
+    #[error("No Bluetooth adapter matches '{0}'")]
+    AdapterNotFound(String),
+
+    /// An adapter exists but can't perform Bluetooth Low Energy scans
+    #[error("A Bluetooth adapter was found but it does not support Bluetooth Low Energy ({0}). Lighthouse base stations need a Bluetooth 4.0+ (LE) adapter; an older dongle or outdated driver may be the cause.")]
+    AdapterNotLeCapable(String),
+
+    /// The adapter went away mid-operation, e.g. a USB dongle was unplugged
+    #[error(
+        "The Bluetooth adapter was disconnected during the operation. Reconnect it and try again."
+    )]
+    AdapterLost,
+
+    /// An adapter exists but the system's Bluetooth is turned off
+    #[error("Bluetooth adapter is present but powered off; please enable Bluetooth.")]
+    BluetoothOff,
+
+    /// The command didn't go through on some of the devices it was meant for,
+    /// either because it couldn't be sent or because --verify timed out
+    #[error("{} of {attempted} devices did not complete the command: {}", failed.len(), failed.join(", "))]
+    DevicesFailed {
+        /// Addresses of the devices that failed
+        failed: Vec<String>,
+        /// Number of devices the command was meant for, including skipped ones
+        attempted: usize,
+    },
+
+    /// The device took the write but reported through the command's status
+    /// characteristic that it didn't accept the command
+    #[error("{device} rejected the {command} command (status: {status})")]
+    CommandRejected {
+        device: String,
+        command: &'static str,
+        /// What the status characteristic returned, as hex
+        status: String,
+    },
+
+    /// No SteamVR installation could be found
+    #[error("SteamVR installation not found. Install SteamVR from Steam and start it once so it can be found.")]
+    SteamVrNotFound,
+
+    /// SteamVR was found but its vrpathreg tool is missing, so the install is incomplete
+    #[error("SteamVR was found but its vrpathreg tool is missing at {}. The SteamVR install looks incomplete; verify its files in Steam or reinstall it.", .0.display())]
+    VrPathRegMissing(PathBuf),
+}
+
+impl LighthouseError {
+    /// Stable identifier for the kind of error, for scripts that branch on it
+    pub fn reason(&self) -> &'static str {
+        match self {
+            LighthouseError::NoAdapter => "no_adapter",
+            LighthouseError::AdapterNotFound(_) => "adapter_not_found",
+            LighthouseError::AdapterNotLeCapable(_) => "adapter_not_le_capable",
+            LighthouseError::AdapterLost => "adapter_lost",
+            LighthouseError::BluetoothOff => "bluetooth_off",
+            LighthouseError::DevicesFailed { .. } => "devices_failed",
+            LighthouseError::CommandRejected { .. } => "command_rejected",
+            LighthouseError::SteamVrNotFound => "steamvr_not_found",
+            LighthouseError::VrPathRegMissing(_) => "vrpathreg_missing",
+        }
+    }
+
+    /// Whether this is a `DevicesFailed` where at least one device succeeded
+    pub fn is_partial_failure(&self) -> bool {
+        matches!(self, LighthouseError::DevicesFailed { failed, attempted } if failed.len() < *attempted)
+    }
+}
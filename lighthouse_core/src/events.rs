@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of events kept when no capacity has been configured
+pub const DEFAULT_EVENT_LOG_CAPACITY: usize = 200;
+
+/// What an event in the in-memory event log is about
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Scan,
+    Command,
+    Error,
+}
+
+/// A single entry in the in-memory event log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LogEvent {
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    pub kind: EventKind,
+    pub message: String,
+}
+
+static EVENTS: Mutex<VecDeque<LogEvent>> = Mutex::new(VecDeque::new());
+static CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_EVENT_LOG_CAPACITY);
+
+/// Set how many events are kept. Older events are dropped once the log is
+/// full; a capacity of 0 disables the log.
+pub fn set_event_log_capacity(capacity: usize) {
+    CAPACITY.store(capacity, Ordering::Relaxed);
+    let mut events = EVENTS.lock().unwrap_or_else(|e| e.into_inner());
+    while events.len() > capacity {
+        events.pop_front();
+    }
+}
+
+/// Add an event to the in-memory event log
+pub fn record_event(kind: EventKind, message: impl Into<String>) {
+    let capacity = CAPACITY.load(Ordering::Relaxed);
+    if capacity == 0 {
+        return;
+    }
+
+    let timestamp_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    let mut events = EVENTS.lock().unwrap_or_else(|e| e.into_inner());
+    while events.len() >= capacity {
+        events.pop_front();
+    }
+    events.push_back(LogEvent {
+        timestamp_ms,
+        kind,
+        message: message.into(),
+    });
+}
+
+/// The events currently in the log, oldest first
+pub fn recent_events() -> Vec<LogEvent> {
+    let events = EVENTS.lock().unwrap_or_else(|e| e.into_inner());
+    events.iter().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The log is global, so tests that change it take turns
+    static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    fn messages() -> Vec<String> {
+        recent_events()
+            .into_iter()
+            .map(|event| event.message)
+            .collect()
+    }
+
+    #[test]
+    fn full_log_drops_the_oldest_events_first() {
+        let _lock = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        set_event_log_capacity(0);
+        set_event_log_capacity(3);
+
+        for i in 1..=5 {
+            record_event(EventKind::Command, format!("event {}", i));
+        }
+        assert_eq!(messages(), ["event 3", "event 4", "event 5"]);
+
+        set_event_log_capacity(2);
+        assert_eq!(messages(), ["event 4", "event 5"]);
+
+        set_event_log_capacity(DEFAULT_EVENT_LOG_CAPACITY);
+    }
+
+    #[test]
+    fn zero_capacity_disables_the_log() {
+        let _lock = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        set_event_log_capacity(3);
+        record_event(EventKind::Scan, "before");
+
+        set_event_log_capacity(0);
+        assert!(recent_events().is_empty());
+        record_event(EventKind::Error, "while disabled");
+        assert!(recent_events().is_empty());
+
+        set_event_log_capacity(DEFAULT_EVENT_LOG_CAPACITY);
+    }
+}
@@ -0,0 +1,150 @@
+// C-compatible FFI layer for the core operations
+//
+// Build a shared library with:
+//   cargo rustc -p lighthouse_core --release --features ffi --crate-type cdylib
+//
+// Every call blocks the calling thread while the async core runs on an internal
+// Tokio runtime. Calls are serialized, so it is safe to call these functions from
+// multiple threads, but they must not be called from inside an async runtime.
+//
+// String ownership: strings returned by this module are allocated by Rust and
+// owned by the caller. Release them with `lighthouse_free_string` exactly once
+// and never with the C allocator (`free`).
+
+use crate::bluetooth::{
+    describe_commands, power_on_lighthouses_with_json, scan_with_report,
+    standby_lighthouses_with_json, ScanOptions,
+};
+use crate::error::LighthouseError;
+use crate::models::DeviceInfo;
+use serde_json::json;
+use std::error::Error;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::sync::{Mutex, OnceLock};
+use tokio::runtime::Runtime;
+
+// Status codes, aligned with the CLI exit codes
+pub const LIGHTHOUSE_OK: i32 = 0;
+pub const LIGHTHOUSE_ERROR: i32 = 1;
+pub const LIGHTHOUSE_BLUETOOTH_ERROR: i32 = 2;
+pub const LIGHTHOUSE_NO_DEVICES_FOUND: i32 = 3;
+pub const LIGHTHOUSE_COMMAND_FAILED: i32 = 4;
+pub const LIGHTHOUSE_BLUETOOTH_OFF: i32 = 8;
+
+/// Serializes calls so concurrent callers don't fight over the Bluetooth adapter
+static CALL_LOCK: Mutex<()> = Mutex::new(());
+
+fn runtime() -> Option<&'static Runtime> {
+    static RUNTIME: OnceLock<Option<Runtime>> = OnceLock::new();
+    RUNTIME.get_or_init(|| Runtime::new().ok()).as_ref()
+}
+
+/// Run a power command and map its outcome to a status code
+fn run_power_command<F>(command: F) -> i32
+where
+    F: std::future::Future<Output = Result<Vec<DeviceInfo>, Box<dyn Error>>>,
+{
+    let Some(rt) = runtime() else {
+        return LIGHTHOUSE_ERROR;
+    };
+    let _guard = CALL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    match rt.block_on(command) {
+        Ok(devices) if devices.is_empty() => LIGHTHOUSE_NO_DEVICES_FOUND,
+        Ok(_) => LIGHTHOUSE_OK,
+        Err(e) => status_for_error(e.as_ref()),
+    }
+}
+
+/// Status code for a failed power command. Adapter and Bluetooth failures get their
+/// own codes, so callers can tell them from devices that didn't take the command.
+fn status_for_error(error: &(dyn Error + 'static)) -> i32 {
+    match error.downcast_ref::<LighthouseError>() {
+        Some(LighthouseError::BluetoothOff) => LIGHTHOUSE_BLUETOOTH_OFF,
+        Some(
+            LighthouseError::NoAdapter
+            | LighthouseError::AdapterNotFound(_)
+            | LighthouseError::AdapterNotLeCapable(_)
+            | LighthouseError::AdapterLost,
+        ) => LIGHTHOUSE_BLUETOOTH_ERROR,
+        Some(_) => LIGHTHOUSE_COMMAND_FAILED,
+        None if error.is::<btleplug::Error>() => LIGHTHOUSE_BLUETOOTH_ERROR,
+        None => LIGHTHOUSE_COMMAND_FAILED,
+    }
+}
+
+/// Power on all detected base stations. Returns a `LIGHTHOUSE_*` status code.
+#[no_mangle]
+pub extern "C" fn lighthouse_power_on() -> i32 {
+    run_power_command(power_on_lighthouses_with_json(true))
+}
+
+/// Put all detected base stations in standby. Returns a `LIGHTHOUSE_*` status code.
+#[no_mangle]
+pub extern "C" fn lighthouse_standby() -> i32 {
+    run_power_command(standby_lighthouses_with_json(true))
+}
+
+/// Scan for base stations and return the ones this scan found as a JSON string:
+/// `{ "success": bool, "message": string, "devices": [ DeviceInfo ] }`
+///
+/// Each device has a `name` and `address`, and the `capabilities`, `reliability`,
+/// `rssi` and `channel` fields the device cache keeps for it when they are known.
+///
+/// The returned string is owned by the caller and must be released with
+/// `lighthouse_free_string`. Returns NULL only if the string could not be allocated.
+#[no_mangle]
+pub extern "C" fn lighthouse_scan_json() -> *mut c_char {
+    let result = match runtime() {
+        Some(rt) => {
+            let _guard = CALL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            rt.block_on(scan_with_report(&ScanOptions::default(), true))
+                .map(|report| report.devices)
+        }
+        None => Err("Failed to create Tokio runtime".into()),
+    };
+
+    let response = match result {
+        Ok(devices) => json!({
+            "success": true,
+            "message": format!("Found {} devices", devices.len()),
+            "devices": devices,
+        }),
+        Err(e) => json!({
+            "success": false,
+            "message": e.to_string(),
+            "devices": [],
+        }),
+    };
+
+    CString::new(response.to_string())
+        .map(CString::into_raw)
+        .unwrap_or(std::ptr::null_mut())
+}
+
+/// List the operations a base station supports as a JSON string:
+/// `{ "commands": [ { "id", "name", "description", "verifiable" } ] }`
+///
+/// This doesn't touch Bluetooth, so it returns immediately. The returned string is
+/// owned by the caller and must be released with `lighthouse_free_string`.
+#[no_mangle]
+pub extern "C" fn lighthouse_list_commands_json() -> *mut c_char {
+    let response = json!({ "commands": describe_commands() });
+    CString::new(response.to_string())
+        .map(CString::into_raw)
+        .unwrap_or(std::ptr::null_mut())
+}
+
+/// Release a string returned by this library. Passing NULL is a no-op.
+///
+/// # Safety
+///
+/// `s` must be NULL or a pointer previously returned by this library that has
+/// not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn lighthouse_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
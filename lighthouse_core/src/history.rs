@@ -0,0 +1,305 @@
+use crate::config::get_config_path;
+use crate::models::{normalize_address, DeviceInfo};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const HISTORY_FILENAME: &str = "lighthouse_history.jsonl";
+
+/// Most entries the history log keeps; the oldest are dropped when appending beyond it
+pub const MAX_HISTORY_ENTRIES: usize = 1000;
+
+/// One power command recorded in the history log
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    /// `poweron`, `standby` or `sleep`
+    pub operation: String,
+    pub success: bool,
+    pub exit_code: i32,
+    /// Devices that were commanded
+    #[serde(default)]
+    pub devices: Vec<DeviceInfo>,
+}
+
+impl HistoryEntry {
+    /// Create an entry stamped with the current time
+    pub fn now(operation: &str, success: bool, exit_code: i32, devices: &[DeviceInfo]) -> Self {
+        HistoryEntry {
+            timestamp_ms: now_ms(),
+            operation: operation.to_string(),
+            success,
+            exit_code,
+            devices: devices.to_vec(),
+        }
+    }
+}
+
+/// Criteria for `filter_history`; unset fields match everything
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    /// Only entries at most this old
+    pub since: Option<Duration>,
+    pub operation: Option<String>,
+    pub failed_only: bool,
+    /// Keep only the most recent N matching entries
+    pub limit: Option<usize>,
+}
+
+pub fn get_history_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(get_config_path()?.with_file_name(HISTORY_FILENAME))
+}
+
+/// Append an entry to the history log as one JSON line, keeping at most
+/// `MAX_HISTORY_ENTRIES` entries
+pub fn append_history(entry: &HistoryEntry) -> Result<(), Box<dyn Error>> {
+    append_history_to(&get_history_path()?, entry, MAX_HISTORY_ENTRIES)
+}
+
+fn append_history_to(
+    path: &Path,
+    entry: &HistoryEntry,
+    max_entries: usize,
+) -> Result<(), Box<dyn Error>> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    drop(file);
+
+    let contents = fs::read_to_string(path)?;
+    let lines: Vec<&str> = contents.lines().collect();
+    if lines.len() > max_entries {
+        let mut kept = lines[lines.len() - max_entries..].join("\n");
+        kept.push('\n');
+        fs::write(path, kept)?;
+    }
+    Ok(())
+}
+
+/// Load the history log, oldest first. A missing file is an empty history,
+/// and lines that can't be parsed are skipped.
+pub fn load_history() -> Result<Vec<HistoryEntry>, Box<dyn Error>> {
+    let path = get_history_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let contents = fs::read_to_string(path)?;
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Apply a filter to history entries, keeping their order
+pub fn filter_history(entries: Vec<HistoryEntry>, filter: &HistoryFilter) -> Vec<HistoryEntry> {
+    let cutoff = filter
+        .since
+        .map(|since| now_ms().saturating_sub(since.as_millis() as u64));
+
+    let mut entries: Vec<HistoryEntry> = entries
+        .into_iter()
+        .filter(|entry| cutoff.is_none_or(|cutoff| entry.timestamp_ms >= cutoff))
+        .filter(|entry| {
+            filter
+                .operation
+                .as_ref()
+                .is_none_or(|operation| entry.operation.eq_ignore_ascii_case(operation))
+        })
+        .filter(|entry| !filter.failed_only || !entry.success)
+        .collect();
+
+    if let Some(limit) = filter.limit {
+        let skip = entries.len().saturating_sub(limit);
+        entries.drain(..skip);
+    }
+    entries
+}
+
+/// The most recent record of every device the history mentions, enough to rebuild
+/// a lost device cache. Entries must be oldest first, as `load_history` returns them.
+pub fn devices_from_history(entries: &[HistoryEntry]) -> Vec<DeviceInfo> {
+    let mut devices: Vec<DeviceInfo> = Vec::new();
+    for device in entries.iter().flat_map(|entry| &entry.devices) {
+        let address = normalize_address(&device.address);
+        match devices
+            .iter_mut()
+            .find(|known| normalize_address(&known.address) == address)
+        {
+            Some(known) => *known = device.clone(),
+            None => devices.push(device.clone()),
+        }
+    }
+    devices
+}
+
+/// Parse a duration such as `90s`, `30m`, `12h` or `7d`. A bare number is seconds.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse().ok()?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(amount.checked_mul(seconds)?))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(operation: &str, success: bool, age: Duration) -> HistoryEntry {
+        HistoryEntry {
+            timestamp_ms: now_ms() - age.as_millis() as u64,
+            operation: operation.to_string(),
+            success,
+            exit_code: if success { 0 } else { 4 },
+            devices: Vec::new(),
+        }
+    }
+
+    fn timestamps(entries: &[HistoryEntry]) -> Vec<u64> {
+        entries.iter().map(|entry| entry.timestamp_ms).collect()
+    }
+
+    #[test]
+    fn parse_duration_reads_each_unit() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Some(Duration::from_secs(30 * 60)));
+        assert_eq!(
+            parse_duration(" 12h "),
+            Some(Duration::from_secs(12 * 60 * 60))
+        );
+        assert_eq!(
+            parse_duration("7d"),
+            Some(Duration::from_secs(7 * 24 * 60 * 60))
+        );
+        assert_eq!(
+            parse_duration("2w"),
+            Some(Duration::from_secs(14 * 24 * 60 * 60))
+        );
+    }
+
+    #[test]
+    fn parse_duration_rejects_malformed_values() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("-5m"), None);
+        assert_eq!(parse_duration("5y"), None);
+        assert_eq!(parse_duration("5mm"), None);
+        assert_eq!(parse_duration(&format!("{}d", u64::MAX)), None);
+    }
+
+    #[test]
+    fn filter_history_keeps_entries_since_the_cutoff() {
+        let entries = vec![
+            entry("poweron", true, Duration::from_secs(3 * 60 * 60)),
+            entry("standby", true, Duration::from_secs(30 * 60)),
+            entry("poweron", true, Duration::from_secs(60)),
+        ];
+        let filter = HistoryFilter {
+            since: Some(Duration::from_secs(60 * 60)),
+            ..HistoryFilter::default()
+        };
+
+        let filtered = filter_history(entries.clone(), &filter);
+
+        assert_eq!(timestamps(&filtered), timestamps(&entries[1..]));
+    }
+
+    #[test]
+    fn filter_history_matches_the_operation_in_any_case() {
+        let entries = vec![
+            entry("poweron", true, Duration::from_secs(3)),
+            entry("standby", true, Duration::from_secs(2)),
+            entry("poweron", false, Duration::from_secs(1)),
+        ];
+        let filter = HistoryFilter {
+            operation: Some("PowerOn".to_string()),
+            ..HistoryFilter::default()
+        };
+
+        let filtered = filter_history(entries, &filter);
+
+        assert_eq!(filtered.len(), 2);
+        assert!(filtered.iter().all(|entry| entry.operation == "poweron"));
+    }
+
+    #[test]
+    fn filter_history_keeps_only_failures_when_asked() {
+        let entries = vec![
+            entry("poweron", true, Duration::from_secs(3)),
+            entry("standby", false, Duration::from_secs(2)),
+            entry("poweron", true, Duration::from_secs(1)),
+        ];
+        let filter = HistoryFilter {
+            failed_only: true,
+            ..HistoryFilter::default()
+        };
+
+        let filtered = filter_history(entries.clone(), &filter);
+
+        assert_eq!(timestamps(&filtered), timestamps(&entries[1..2]));
+    }
+
+    #[test]
+    fn filter_history_limit_keeps_the_newest_entries_in_order() {
+        let entries: Vec<HistoryEntry> = (1..=5)
+            .rev()
+            .map(|age| entry("poweron", true, Duration::from_secs(age)))
+            .collect();
+        let filter = HistoryFilter {
+            limit: Some(2),
+            ..HistoryFilter::default()
+        };
+
+        let filtered = filter_history(entries.clone(), &filter);
+
+        assert_eq!(timestamps(&filtered), timestamps(&entries[3..]));
+    }
+
+    #[test]
+    fn append_drops_the_oldest_entries_beyond_the_cap() {
+        let path = std::env::temp_dir().join(format!(
+            "lighthouse-core-history-cap-{}.jsonl",
+            std::process::id()
+        ));
+        fs::remove_file(&path).ok();
+        let entries: Vec<HistoryEntry> = (1..=5)
+            .rev()
+            .map(|age| entry("poweron", true, Duration::from_secs(age)))
+            .collect();
+
+        for entry in &entries {
+            append_history_to(&path, entry, 3).unwrap();
+        }
+
+        let kept: Vec<HistoryEntry> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(timestamps(&kept), timestamps(&entries[2..]));
+
+        fs::remove_file(&path).ok();
+    }
+}
@@ -1,6 +1,14 @@
 pub mod bluetooth;
+pub mod cancel;
 pub mod config;
+pub mod error;
+pub mod events;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod history;
 pub mod logging;
+pub mod messages;
 pub use btleplug;
 pub mod models;
+pub mod notifications;
 pub mod steamvr_integration;
@@ -1,8 +1,28 @@
+use crate::config::get_config_path;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const LOG_FILENAME: &str = "lighthouse.log";
+
+/// Size at which the log file is rotated
+pub const MAX_LOG_FILE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Number of rotated log files kept, from `lighthouse.log.1` (newest) up
+pub const KEPT_LOG_FILES: u32 = 3;
+
+/// File that messages are mirrored to, once `init_file_logging` has been called
+static LOG_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
 /// Conditionally print messages when not in JSON mode
 pub fn log(message: &str, json_output: bool) {
     if !json_output {
         println!("{}", message);
     }
+    write_to_log_file("INFO", message);
 }
 
 /// Conditionally print error messages when not in JSON mode
@@ -10,4 +30,84 @@ pub fn error_log(message: &str, json_output: bool) {
     if !json_output {
         eprintln!("{}", message);
     }
+    write_to_log_file("ERROR", message);
+}
+
+pub fn get_log_path() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(get_config_path()?.with_file_name(LOG_FILENAME))
+}
+
+/// Mirror every `log` and `error_log` message to `path` from now on, whether or not
+/// it's printed. The file is appended to and rotated once it reaches `MAX_LOG_FILE_BYTES`.
+pub fn init_file_logging(path: PathBuf) -> Result<(), Box<dyn Error>> {
+    // Fail here rather than silently on every message if the file can't be written
+    OpenOptions::new().create(true).append(true).open(&path)?;
+    *LOG_FILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(path);
+    Ok(())
+}
+
+/// Append a timestamped line to the log file, if file logging is on. Failures are
+/// ignored, since there's nowhere left to report them.
+fn write_to_log_file(level: &str, message: &str) {
+    let log_file = LOG_FILE.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(path) = log_file.as_ref() else {
+        return;
+    };
+
+    let line = format!("{} {:<5} {}\n", timestamp(), level, message);
+    let size = fs::metadata(path).map(|m| m.len()).unwrap_or_default();
+    if size > 0 && size + line.len() as u64 > MAX_LOG_FILE_BYTES {
+        rotate_log_files(path).ok();
+    }
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        file.write_all(line.as_bytes()).ok();
+    }
+}
+
+/// Shift each `lighthouse.log.N` to `.N+1`, dropping the oldest, and move the
+/// current file to `.1`
+fn rotate_log_files(path: &Path) -> std::io::Result<()> {
+    let rotated = |n: u32| PathBuf::from(format!("{}.{}", path.display(), n));
+    for n in (1..KEPT_LOG_FILES).rev() {
+        if rotated(n).exists() {
+            fs::rename(rotated(n), rotated(n + 1))?;
+        }
+    }
+    fs::rename(path, rotated(1))
+}
+
+/// Current UTC time, e.g. `2024-05-01 12:34:56.789Z`
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs();
+    let (days, time) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since the epoch, after Howard Hinnant's `civil_from_days`
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        now.subsec_millis()
+    )
 }
@@ -0,0 +1,122 @@
+use std::env;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// Languages the message catalog has translations for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+}
+
+impl Language {
+    /// Parse a language code such as `de`, `de_DE` or `de_DE.UTF-8`
+    pub fn from_code(code: &str) -> Option<Self> {
+        let code = code
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match code.as_str() {
+            "en" | "c" | "posix" => Some(Language::English),
+            "de" => Some(Language::German),
+            _ => None,
+        }
+    }
+
+    /// Detect the language from the usual locale environment variables,
+    /// defaulting to English when none of them name a supported language
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Language::from_code(&value))
+            .unwrap_or_default()
+    }
+}
+
+/// Identifiers for user-facing messages.
+/// Placeholders in the templates are written as `{0}`, `{1}`, ...
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msg {
+    ScanningBluetooth,
+    ScanningLighthouses,
+    ScanCompleted,
+    NoDevicesFound,
+    FoundDevices,
+    NoLighthousesFound,
+    FoundLighthouses,
+    FoundLighthouse,
+    SendingCommand,
+    CommandSent,
+    CommandFailed,
+}
+
+static LANGUAGE: OnceLock<Language> = OnceLock::new();
+
+/// Set the language used for messages. Only the first call has an effect,
+/// so this should happen before anything is logged.
+pub fn set_language(language: Language) {
+    let _ = LANGUAGE.set(language);
+}
+
+/// The language used for messages, detected from the locale if it wasn't set
+pub fn current_language() -> Language {
+    *LANGUAGE.get_or_init(Language::from_env)
+}
+
+/// Look up the template for a message in the given language
+pub fn template(key: Msg, language: Language) -> &'static str {
+    match language {
+        Language::English => english(key),
+        Language::German => german(key),
+    }
+}
+
+/// Look up a message without placeholders in the current language
+pub fn msg(key: Msg) -> &'static str {
+    template(key, current_language())
+}
+
+/// Look up a message in the current language and fill in its placeholders
+pub fn msgf(key: Msg, args: &[&dyn Display]) -> String {
+    let mut message = msg(key).to_string();
+    for (i, arg) in args.iter().enumerate() {
+        message = message.replace(&format!("{{{}}}", i), &arg.to_string());
+    }
+    message
+}
+
+fn english(key: Msg) -> &'static str {
+    match key {
+        Msg::ScanningBluetooth => "Scanning for Bluetooth devices...",
+        Msg::ScanningLighthouses => "Scanning for Lighthouse devices...",
+        Msg::ScanCompleted => "Scanning completed",
+        Msg::NoDevicesFound => "No devices found",
+        Msg::FoundDevices => "Found {0} devices:",
+        Msg::NoLighthousesFound => "No Lighthouse Base Stations found",
+        Msg::FoundLighthouses => "Found {0} Lighthouse Base Stations:",
+        Msg::FoundLighthouse => "Found lighthouse: {0} ({1})",
+        Msg::SendingCommand => "Sending {0} command to {1} Lighthouse devices...",
+        Msg::CommandSent => "Successfully sent {0} command to device {1}",
+        Msg::CommandFailed => "Failed to send {0} command to device {1}: {2}",
+    }
+}
+
+fn german(key: Msg) -> &'static str {
+    match key {
+        Msg::ScanningBluetooth => "Suche nach Bluetooth-Geräten...",
+        Msg::ScanningLighthouses => "Suche nach Lighthouse-Geräten...",
+        Msg::ScanCompleted => "Suche abgeschlossen",
+        Msg::NoDevicesFound => "Keine Geräte gefunden",
+        Msg::FoundDevices => "{0} Geräte gefunden:",
+        Msg::NoLighthousesFound => "Keine Lighthouse-Basisstationen gefunden",
+        Msg::FoundLighthouses => "{0} Lighthouse-Basisstationen gefunden:",
+        Msg::FoundLighthouse => "Lighthouse gefunden: {0} ({1})",
+        Msg::SendingCommand => "Sende {0}-Befehl an {1} Lighthouse-Geräte...",
+        Msg::CommandSent => "{0}-Befehl erfolgreich an Gerät {1} gesendet",
+        Msg::CommandFailed => "{0}-Befehl an Gerät {1} fehlgeschlagen: {2}",
+    }
+}
@@ -0,0 +1,56 @@
+use crate::models::DeviceInfo;
+use serde::{Deserialize, Serialize};
+
+/// Spread of one measurement over the iterations of a benchmark, in milliseconds
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingStats {
+    pub samples: usize,
+    pub min_ms: u64,
+    pub median_ms: u64,
+    pub mean_ms: u64,
+    pub max_ms: u64,
+}
+
+impl TimingStats {
+    /// Summarize the samples, or `None` if there are none
+    pub fn from_samples(samples: &[u64]) -> Option<Self> {
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let (&min_ms, &max_ms) = (sorted.first()?, sorted.last()?);
+        Some(TimingStats {
+            samples: sorted.len(),
+            min_ms,
+            median_ms: sorted[sorted.len() / 2],
+            mean_ms: sorted.iter().sum::<u64>() / sorted.len() as u64,
+            max_ms,
+        })
+    }
+}
+
+/// Timings of a single base station over a benchmark
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeviceBenchmark {
+    pub device: DeviceInfo,
+    /// Time to open a connection
+    pub connect: Option<TimingStats>,
+    /// Time to discover services, read the power state and write a command
+    pub command: Option<TimingStats>,
+    /// Iterations in which the device wasn't found or couldn't be timed
+    pub failures: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// Outcome of a latency benchmark
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BenchmarkReport {
+    pub iterations: u32,
+    /// Time each scan took, as reported by the scan itself
+    pub scan: Option<TimingStats>,
+    /// Connect and command times of every base station together
+    pub connect: Option<TimingStats>,
+    pub command: Option<TimingStats>,
+    /// Time each iteration took from the start of its scan to the last disconnect
+    pub end_to_end: Option<TimingStats>,
+    pub devices: Vec<DeviceBenchmark>,
+}
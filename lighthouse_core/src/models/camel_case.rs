@@ -0,0 +1,38 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Serialize a value to JSON with camelCase object keys.
+/// The models serialize with snake_case keys, which the CLI output keeps for
+/// backward compatibility; this is for JS/TS consumers such as the Tauri app.
+pub fn to_camel_case_json<T: Serialize>(value: &T) -> serde_json::Result<Value> {
+    Ok(camel_case_keys(serde_json::to_value(value)?))
+}
+
+/// Recursively rename the object keys of a JSON value from snake_case to camelCase
+pub fn camel_case_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (snake_to_camel(&key), camel_case_keys(value)))
+                .collect::<Map<String, Value>>(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(camel_case_keys).collect()),
+        other => other,
+    }
+}
+
+fn snake_to_camel(key: &str) -> String {
+    let mut result = String::with_capacity(key.len());
+    let mut upper_next = false;
+    for c in key.chars() {
+        if c == '_' {
+            upper_next = !result.is_empty();
+        } else if upper_next {
+            result.extend(c.to_uppercase());
+            upper_next = false;
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
@@ -1,7 +1,172 @@
+use super::DeviceReliability;
 use serde::{Deserialize, Serialize};
 
+/// Name given to a device that was seen without advertising a name
+pub const UNKNOWN_DEVICE_NAME: &str = "Unknown";
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DeviceInfo {
     pub name: String,
     pub address: String,
+    /// Supported operations, once the device has been probed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<DeviceCapabilities>,
+    /// Outcomes of the last few commands sent to the device
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reliability: Option<DeviceReliability>,
+    /// Signal strength when the device was last seen, in dBm
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rssi: Option<i16>,
+    /// Channel the base station was last read to be on, see `read_channel`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<u8>,
+}
+
+/// Operations a base station supports, based on the GATT characteristics it exposes
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeviceCapabilities {
+    /// The power characteristic is writable, so power on/standby can be sent
+    pub power: bool,
+    /// The power characteristic is readable, so the current power state can be read
+    pub power_state: bool,
+    /// The channel characteristic is present
+    pub channel: bool,
+    /// The identify characteristic is present, so the LED can be blinked
+    pub identify: bool,
+    /// The Device Information Service exposes a serial number
+    pub serial_number: bool,
+}
+
+impl DeviceInfo {
+    /// A device with just a name and address, before anything else is known about it
+    pub fn new(name: impl Into<String>, address: impl Into<String>) -> Self {
+        DeviceInfo {
+            name: name.into(),
+            address: address.into(),
+            capabilities: None,
+            reliability: None,
+            rssi: None,
+            channel: None,
+        }
+    }
+
+    /// Check whether this device has the given address, ignoring case and separator style
+    pub fn matches_address(&self, address: &str) -> bool {
+        normalize_address(&self.address) == normalize_address(address)
+    }
+
+    /// Whether the device has a real name rather than a placeholder for a missing one
+    pub fn has_name(&self) -> bool {
+        !self.name.is_empty() && self.name != UNKNOWN_DEVICE_NAME
+    }
+
+    /// Recent command outcomes, if any commands have been recorded since the last reset
+    pub fn recorded_reliability(&self) -> Option<&DeviceReliability> {
+        self.reliability
+            .as_ref()
+            .filter(|reliability| reliability.attempts() > 0)
+    }
+}
+
+/// Match scanned devices, given with their addresses, against the cached devices.
+/// Returns the scanned devices that are cached, in scan order, and the cached
+/// devices that weren't seen. Addresses are compared with `normalize_address`.
+pub fn match_peripherals<T: Clone>(
+    cached: &[DeviceInfo],
+    scanned: &[(T, String)],
+) -> (Vec<T>, Vec<DeviceInfo>) {
+    let found = scanned
+        .iter()
+        .filter(|(_, address)| cached.iter().any(|device| device.matches_address(address)))
+        .map(|(peripheral, _)| peripheral.clone())
+        .collect();
+    let missing = cached
+        .iter()
+        .filter(|device| {
+            !scanned
+                .iter()
+                .any(|(_, address)| device.matches_address(address))
+        })
+        .cloned()
+        .collect();
+    (found, missing)
+}
+
+/// Normalize a Bluetooth address for comparison, e.g. `aa-bb-cc-dd-ee-ff` -> `AA:BB:CC:DD:EE:FF`
+pub fn normalize_address(address: &str) -> String {
+    address.trim().replace('-', ":").to_ascii_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scanned(addresses: &[&str]) -> Vec<(usize, String)> {
+        addresses
+            .iter()
+            .enumerate()
+            .map(|(i, address)| (i, address.to_string()))
+            .collect()
+    }
+
+    fn names(devices: &[DeviceInfo]) -> Vec<&str> {
+        devices.iter().map(|device| device.name.as_str()).collect()
+    }
+
+    #[test]
+    fn every_cached_device_found() {
+        let cached = [
+            DeviceInfo::new("LHB-1", "AA:BB:CC:DD:EE:01"),
+            DeviceInfo::new("LHB-2", "AA:BB:CC:DD:EE:02"),
+        ];
+        let scan = scanned(&["AA:BB:CC:DD:EE:02", "AA:BB:CC:DD:EE:01"]);
+
+        let (found, missing) = match_peripherals(&cached, &scan);
+
+        assert_eq!(found, vec![0, 1]);
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn some_cached_devices_found() {
+        let cached = [
+            DeviceInfo::new("LHB-1", "AA:BB:CC:DD:EE:01"),
+            DeviceInfo::new("LHB-2", "AA:BB:CC:DD:EE:02"),
+            DeviceInfo::new("LHB-3", "AA:BB:CC:DD:EE:03"),
+        ];
+        // The scan also sees a device that isn't cached
+        let scan = scanned(&["11:22:33:44:55:66", "AA:BB:CC:DD:EE:03"]);
+
+        let (found, missing) = match_peripherals(&cached, &scan);
+
+        assert_eq!(found, vec![1]);
+        assert_eq!(names(&missing), ["LHB-1", "LHB-2"]);
+    }
+
+    #[test]
+    fn no_cached_devices_found() {
+        let cached = [DeviceInfo::new("LHB-1", "AA:BB:CC:DD:EE:01")];
+
+        let (found, missing) = match_peripherals(&cached, &scanned(&["11:22:33:44:55:66"]));
+        assert!(found.is_empty());
+        assert_eq!(names(&missing), ["LHB-1"]);
+
+        let (found, missing) = match_peripherals(&cached, &scanned(&[]));
+        assert!(found.is_empty());
+        assert_eq!(names(&missing), ["LHB-1"]);
+    }
+
+    #[test]
+    fn addresses_match_in_any_case_and_separator() {
+        let cached = [
+            DeviceInfo::new("LHB-1", "aa:bb:cc:dd:ee:0f"),
+            DeviceInfo::new("LHB-2", "AA-BB-CC-DD-EE-10"),
+        ];
+        let scan = scanned(&["AA:BB:CC:DD:EE:0F", "aa:bb:cc:dd:ee:10"]);
+
+        let (found, missing) = match_peripherals(&cached, &scan);
+
+        assert_eq!(found, vec![0, 1]);
+        assert!(missing.is_empty());
+    }
 }
@@ -0,0 +1,54 @@
+use crate::config::find_device;
+use crate::models::DeviceInfo;
+use serde::{Deserialize, Serialize};
+
+/// How the base stations found by a scan differ from the cached ones
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DeviceDiff {
+    /// Found by the scan but not in the cache
+    pub added: Vec<DeviceInfo>,
+    /// In the cache but not found by the scan
+    pub missing: Vec<DeviceInfo>,
+    /// Found with a different name than the cached one
+    pub renamed: Vec<RenamedDevice>,
+}
+
+/// A base station that advertised a different name than the one cached
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RenamedDevice {
+    pub address: String,
+    pub cached_name: String,
+    pub scanned_name: String,
+}
+
+impl DeviceDiff {
+    /// Compare scanned devices with the cached ones by address. A device seen
+    /// without a name doesn't count as renamed.
+    pub fn between(cached: &[DeviceInfo], scanned: &[DeviceInfo]) -> Self {
+        let mut diff = DeviceDiff::default();
+        for device in scanned {
+            match find_device(cached, &device.address) {
+                None => diff.added.push(device.clone()),
+                Some(previous) if device.has_name() && device.name != previous.name => {
+                    diff.renamed.push(RenamedDevice {
+                        address: device.address.clone(),
+                        cached_name: previous.name.clone(),
+                        scanned_name: device.name.clone(),
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+        diff.missing = cached
+            .iter()
+            .filter(|device| find_device(scanned, &device.address).is_none())
+            .cloned()
+            .collect();
+        diff
+    }
+
+    /// Whether the scan found exactly the cached devices under their cached names
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.missing.is_empty() && self.renamed.is_empty()
+    }
+}
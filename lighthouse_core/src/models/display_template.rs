@@ -0,0 +1,56 @@
+use crate::models::PowerState;
+
+/// Template used when none is configured: the nickname (or advertised name) and the address
+pub const DEFAULT_DISPLAY_TEMPLATE: &str = "{nickname} — {address}";
+
+/// Values available to a display template. Anything not known is left as `None`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DisplayValues<'a> {
+    pub nickname: Option<&'a str>,
+    pub name: &'a str,
+    pub address: &'a str,
+    pub channel: Option<u8>,
+    pub state: Option<PowerState>,
+    pub rssi: Option<i16>,
+}
+
+/// Render a device display template.
+///
+/// Supported placeholders are `{nickname}`, `{name}`, `{address}`, `{short_addr}`,
+/// `{channel}`, `{state}` and `{rssi}`. `{nickname}` falls back to the advertised
+/// name; other missing values render as nothing, and brackets left empty by them
+/// are dropped. Unknown placeholders are kept as written.
+pub fn render_display_template(template: &str, values: &DisplayValues) -> String {
+    let short_addr = short_address(values.address);
+    let replacements = [
+        (
+            "{nickname}",
+            Some(values.nickname.unwrap_or(values.name).to_string()),
+        ),
+        ("{name}", Some(values.name.to_string())),
+        ("{address}", Some(values.address.to_string())),
+        ("{short_addr}", Some(short_addr)),
+        ("{channel}", values.channel.map(|c| c.to_string())),
+        ("{state}", values.state.map(|s| s.to_string())),
+        ("{rssi}", values.rssi.map(|r| format!("{} dBm", r))),
+    ];
+
+    let mut rendered = template.to_string();
+    for (placeholder, value) in replacements {
+        rendered = rendered.replace(placeholder, value.as_deref().unwrap_or_default());
+    }
+
+    for empty in ["[]", "()", "{}"] {
+        rendered = rendered.replace(empty, "");
+    }
+    rendered.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The last two octets of an address, e.g. `AA:BB:CC:DD:EE:FF` -> `EE:FF`
+fn short_address(address: &str) -> String {
+    let octets: Vec<&str> = address.split([':', '-']).collect();
+    if octets.len() < 2 {
+        return address.to_string();
+    }
+    octets[octets.len() - 2..].join(":").to_ascii_uppercase()
+}
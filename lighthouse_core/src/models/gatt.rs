@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// A GATT service and its characteristics, as listed by `--inspect`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GattService {
+    pub uuid: String,
+    pub primary: bool,
+    pub characteristics: Vec<GattCharacteristic>,
+}
+
+/// A characteristic with its current value, if it could be read
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GattCharacteristic {
+    pub uuid: String,
+    /// Property flags such as `READ` or `WRITE_WITHOUT_RESPONSE`
+    pub properties: Vec<String>,
+    /// Value as hex, for readable characteristics
+    pub value: Option<String>,
+    /// The value as text, if it is printable UTF-8
+    pub text: Option<String>,
+    /// Why a readable characteristic couldn't be read
+    pub read_error: Option<String>,
+    pub descriptors: Vec<String>,
+}
+
+impl GattCharacteristic {
+    /// Fill in `value` and `text` from the bytes read from the characteristic
+    pub fn set_value(&mut self, bytes: &[u8]) {
+        self.value = Some(bytes.iter().map(|b| format!("{:02x}", b)).collect());
+        self.text = std::str::from_utf8(bytes)
+            .ok()
+            .filter(|text| !text.is_empty() && !text.chars().any(char::is_control))
+            .map(String::from);
+    }
+}
@@ -1,2 +1,24 @@
+mod benchmark_report;
+mod camel_case;
 mod device;
-pub use device::DeviceInfo;
+mod device_diff;
+mod display_template;
+mod gatt;
+mod power_state;
+mod raw_scan;
+mod recovery_report;
+mod reliability;
+mod scan_report;
+pub use benchmark_report::{BenchmarkReport, DeviceBenchmark, TimingStats};
+pub use camel_case::{camel_case_keys, to_camel_case_json};
+pub use device::{
+    match_peripherals, normalize_address, DeviceCapabilities, DeviceInfo, UNKNOWN_DEVICE_NAME,
+};
+pub use device_diff::{DeviceDiff, RenamedDevice};
+pub use display_template::{render_display_template, DisplayValues, DEFAULT_DISPLAY_TEMPLATE};
+pub use gatt::{GattCharacteristic, GattService};
+pub use power_state::{PowerState, StateBytes};
+pub use raw_scan::{RawPeripheral, RawScan};
+pub use recovery_report::RecoveryReport;
+pub use reliability::{DeviceReliability, RELIABILITY_WARNING_FAILURES, RELIABILITY_WINDOW};
+pub use scan_report::ScanReport;
@@ -0,0 +1,91 @@
+use crate::bluetooth::power_command;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Power state reported by a base station's power characteristic
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerState {
+    On,
+    Standby,
+    Sleep,
+    Booting,
+    Unknown(u8),
+}
+
+impl PowerState {
+    /// Decode the byte read from the power characteristic
+    pub fn from_byte(value: u8) -> Self {
+        match value {
+            0x00 => PowerState::Standby,
+            0x02 => PowerState::Sleep,
+            0x01 | 0x08 | 0x09 => PowerState::Booting,
+            0x0b => PowerState::On,
+            other => PowerState::Unknown(other),
+        }
+    }
+
+    /// Whether this is a resting state rather than one the device passes through
+    /// on its way somewhere else
+    pub fn is_settled(self) -> bool {
+        matches!(
+            self,
+            PowerState::On | PowerState::Standby | PowerState::Sleep
+        )
+    }
+
+    /// Check whether the device is already where the given power command would put it
+    pub fn satisfies(self, command: u8) -> bool {
+        power_command(command).is_some_and(|command| command.is_reached_by(self))
+    }
+}
+
+/// Power characteristic values for each state, for verifying commands on firmware
+/// that reports its states with other values. Defaults to the V2 values that
+/// `PowerState::from_byte` decodes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct StateBytes {
+    pub on: Vec<u8>,
+    pub standby: Vec<u8>,
+    pub sleep: Vec<u8>,
+    pub booting: Vec<u8>,
+}
+
+impl Default for StateBytes {
+    fn default() -> Self {
+        StateBytes {
+            on: vec![0x0b],
+            standby: vec![0x00],
+            sleep: vec![0x02],
+            booting: vec![0x01, 0x08, 0x09],
+        }
+    }
+}
+
+impl StateBytes {
+    /// Decode the byte read from the power characteristic with these values
+    pub fn decode(&self, value: u8) -> PowerState {
+        [
+            (&self.on, PowerState::On),
+            (&self.standby, PowerState::Standby),
+            (&self.sleep, PowerState::Sleep),
+            (&self.booting, PowerState::Booting),
+        ]
+        .into_iter()
+        .find(|(values, _)| values.contains(&value))
+        .map_or(PowerState::Unknown(value), |(_, state)| state)
+    }
+}
+
+impl fmt::Display for PowerState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerState::On => write!(f, "on"),
+            PowerState::Standby => write!(f, "standby"),
+            PowerState::Sleep => write!(f, "sleep"),
+            PowerState::Booting => write!(f, "booting"),
+            PowerState::Unknown(value) => write!(f, "unknown (0x{:02x})", value),
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Everything a scan saw, written by `--save-raw-scan` for diagnosing detection problems
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RawScan {
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    /// Version of lighthouse_core that made the scan
+    pub version: String,
+    pub adapter: String,
+    pub peripherals: Vec<RawPeripheral>,
+    /// Peripherals left out to keep the file under its size cap
+    pub omitted: usize,
+}
+
+/// Advertised data of one peripheral, as reported by the OS
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RawPeripheral {
+    pub address: String,
+    pub name: Option<String>,
+    pub rssi: Option<i16>,
+    pub tx_power_level: Option<i16>,
+    /// Manufacturer ID -> data as hex
+    pub manufacturer_data: BTreeMap<u16, String>,
+    /// Service UUID -> data as hex
+    pub service_data: BTreeMap<String, String>,
+    pub services: Vec<String>,
+    /// Whether the name prefix and manufacturer ID identify it as a base station
+    pub is_lighthouse: bool,
+}
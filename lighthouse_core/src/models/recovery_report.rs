@@ -0,0 +1,13 @@
+use crate::models::DeviceInfo;
+use serde::{Deserialize, Serialize};
+
+/// Outcome of a power cycle recovery
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecoveryReport {
+    /// Base stations confirmed to be back on
+    pub recovered: Vec<DeviceInfo>,
+    /// Base stations that didn't report being on after the last attempt
+    pub failed: Vec<DeviceInfo>,
+    /// Number of power on attempts made
+    pub attempts: u32,
+}
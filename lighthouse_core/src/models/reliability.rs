@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Number of recent commands kept per device
+pub const RELIABILITY_WINDOW: usize = 10;
+
+/// Consecutive failures after which a device is reported as failing
+pub const RELIABILITY_WARNING_FAILURES: usize = 3;
+
+/// Outcomes of the most recent commands sent to a device, oldest first
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceReliability {
+    #[serde(default)]
+    pub recent: Vec<bool>,
+}
+
+impl DeviceReliability {
+    /// Add the outcome of a command, dropping the oldest beyond the window
+    pub fn record(&mut self, success: bool) {
+        self.recent.push(success);
+        if self.recent.len() > RELIABILITY_WINDOW {
+            let excess = self.recent.len() - RELIABILITY_WINDOW;
+            self.recent.drain(..excess);
+        }
+    }
+
+    pub fn attempts(&self) -> usize {
+        self.recent.len()
+    }
+
+    pub fn successes(&self) -> usize {
+        self.recent.iter().filter(|success| **success).count()
+    }
+
+    /// Failures since the last success
+    pub fn consecutive_failures(&self) -> usize {
+        self.recent
+            .iter()
+            .rev()
+            .take_while(|success| !**success)
+            .count()
+    }
+
+    /// Whether the device has failed often enough in a row to warn about it
+    pub fn is_failing(&self) -> bool {
+        self.consecutive_failures() >= RELIABILITY_WARNING_FAILURES
+    }
+}
+
+impl fmt::Display for DeviceReliability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{} ok", self.successes(), self.attempts())?;
+        if self.is_failing() {
+            write!(f, ", failing")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,12 @@
+use crate::models::DeviceInfo;
+use serde::{Deserialize, Serialize};
+
+/// Summary of a single scan, for front-ends that want to explain the result
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScanReport {
+    /// Lighthouse base stations found by the scan
+    pub devices: Vec<DeviceInfo>,
+    /// Number of BLE devices seen, including ones that aren't base stations
+    pub total_seen: usize,
+    pub duration_ms: u64,
+}
@@ -0,0 +1,61 @@
+// Desktop notifications for power changes made without a console, such as the
+// SteamVR start and stop handlers. They are only shown in builds with the
+// `notifications` feature, and only when `desktop_notifications` is enabled.
+use crate::config::load_settings;
+use crate::models::DeviceInfo;
+
+/// Whether this build can show desktop notifications
+pub const NOTIFICATIONS_SUPPORTED: bool = cfg!(feature = "notifications");
+
+/// Show a desktop notification
+#[cfg(feature = "notifications")]
+pub fn show_notification(summary: &str, body: &str) -> Result<(), String> {
+    notify_rust::Notification::new()
+        .appname("Lighthouse Manager")
+        .summary(summary)
+        .body(body)
+        .show()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Without notification support there is nothing to show
+#[cfg(not(feature = "notifications"))]
+pub fn show_notification(_summary: &str, _body: &str) -> Result<(), String> {
+    Err("this build has no desktop notification support".to_string())
+}
+
+/// Report the outcome of powering the base stations on or putting them in standby,
+/// if notifications are enabled. Only fails if a notification couldn't be shown.
+pub fn notify_power_result(
+    power_on: bool,
+    result: Result<&[DeviceInfo], String>,
+) -> Result<(), String> {
+    let settings = load_settings().unwrap_or_default();
+    if !settings.desktop_notifications {
+        return Ok(());
+    }
+
+    let (summary, body) = match result {
+        Ok([]) => (
+            "No base stations found".to_string(),
+            "Nothing was powered on or put in standby".to_string(),
+        ),
+        Ok(devices) => {
+            let summary = match power_on {
+                true => "Base stations powered on",
+                false => "Base stations in standby",
+            };
+            let names: Vec<&str> = devices.iter().map(|d| settings.display_name(d)).collect();
+            (summary.to_string(), names.join(", "))
+        }
+        Err(e) => {
+            let summary = match power_on {
+                true => "Couldn't power on the base stations",
+                false => "Couldn't put the base stations in standby",
+            };
+            (summary.to_string(), e)
+        }
+    };
+    show_notification(&summary, &body)
+}
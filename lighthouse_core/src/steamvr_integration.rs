@@ -1,10 +1,20 @@
 // SteamVR integration module for Lighthouse-rs
+use crate::bluetooth::{read_properties, read_serial_number, LHB_PREFIX};
+use crate::config::get_config_path;
+use crate::error::LighthouseError;
+use crate::logging::log;
+use crate::models::UNKNOWN_DEVICE_NAME;
+use btleplug::api::Peripheral as _;
+use btleplug::platform::Peripheral;
+use serde::Serialize;
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::env;
 use std::error::Error;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{Duration, Instant};
 
 // Windows-specific imports for hiding console window
 #[cfg(windows)]
@@ -18,9 +28,27 @@ const CREATE_NO_WINDOW: u32 = 0x08000000;
 const STEAMVR_VR_PATH_ENV_VAR: &str = "VR_OVERRIDE";
 const STEAMVR_MANIFEST_FILENAME: &str = "lighthouse-rs.vrmanifest";
 const MANIFEST_TEMPLATE: &str = include_str!("../steamvr/lighthouse-rs.vrmanifest");
+const LIGHTHOUSE_DB_FILENAME: &str = "lighthousedb.json";
+
+// SteamVR's binaries live in a per-platform directory under `bin`
+#[cfg(windows)]
+const STEAMVR_BIN_DIR: &str = "win64";
+#[cfg(not(windows))]
+const STEAMVR_BIN_DIR: &str = "linux64";
+
+#[cfg(windows)]
+const VRPATHREG_BINARY: &str = "vrpathreg.exe";
+#[cfg(not(windows))]
+const VRPATHREG_BINARY: &str = "vrpathreg";
+
+// Manifest key SteamVR reads the helper's binary path from on this platform
+#[cfg(windows)]
+const MANIFEST_BINARY_PATH_KEY: &str = "binary_path_windows";
+#[cfg(not(windows))]
+const MANIFEST_BINARY_PATH_KEY: &str = "binary_path_linux";
 
 /// Gets the path to the SteamVR manifest file in the application directory
-pub fn get_manifest_path() -> Result<PathBuf, Box<dyn Error>> {
+fn exe_manifest_path() -> Result<PathBuf, Box<dyn Error>> {
     let exe_path = env::current_exe()?;
     let exe_dir = exe_path
         .parent()
@@ -28,13 +56,73 @@ pub fn get_manifest_path() -> Result<PathBuf, Box<dyn Error>> {
     Ok(exe_dir.join("steamvr").join(STEAMVR_MANIFEST_FILENAME))
 }
 
+/// Gets the path to the SteamVR manifest file in the app-data directory, next to the config
+fn app_data_manifest_path() -> Result<PathBuf, Box<dyn Error>> {
+    let config_path = get_config_path()?;
+    let config_dir = config_path
+        .parent()
+        .ok_or("Failed to get config directory")?;
+    Ok(config_dir.join("steamvr").join(STEAMVR_MANIFEST_FILENAME))
+}
+
+/// Gets the path to the SteamVR manifest file.
+/// An existing manifest in the app-data directory (written when the application
+/// directory wasn't writable) takes precedence over the application directory.
+pub fn get_manifest_path() -> Result<PathBuf, Box<dyn Error>> {
+    if let Ok(path) = app_data_manifest_path() {
+        if path.exists() {
+            return Ok(path);
+        }
+    }
+    exe_manifest_path()
+}
+
+/// Write the manifest, creating its directory if needed
+fn write_manifest(path: &Path, contents: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, contents)
+}
+
+/// Gets the path to OpenVR's paths file, which records the SteamVR runtime in use.
+/// It's in `%LOCALAPPDATA%\openvr` on Windows and `~/.config/openvr` on Linux.
+fn openvr_paths_file() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let openvr_dir = Path::new(&env::var("LOCALAPPDATA").ok()?).join("openvr");
+
+    #[cfg(not(windows))]
+    let openvr_dir = directories::BaseDirs::new()?.config_dir().join("openvr");
+
+    Some(openvr_dir.join("openvrpaths.vrpath"))
+}
+
+/// Default SteamVR installation directories for this platform
+fn default_steamvr_dirs() -> Vec<PathBuf> {
+    #[cfg(windows)]
+    return vec![
+        // Steam default installation path on 64-bit Windows
+        PathBuf::from("C:\\Program Files (x86)\\Steam\\steamapps\\common\\SteamVR"),
+    ];
+
+    #[cfg(not(windows))]
+    return directories::BaseDirs::new()
+        .map(|dirs| {
+            let home = dirs.home_dir();
+            vec![
+                // Where the Steam client links its data directory
+                home.join(".steam/steam/steamapps/common/SteamVR"),
+                // Steam's actual data directory on most distributions
+                home.join(".local/share/Steam/steamapps/common/SteamVR"),
+            ]
+        })
+        .unwrap_or_default();
+}
+
 /// Gets the SteamVR installation directory
 pub fn get_steamvr_dir() -> Option<PathBuf> {
-    // 1) Try OpenVR paths file in LOCALAPPDATA
-    if let Ok(local_app_data) = env::var("LOCALAPPDATA") {
-        let ovr_paths = Path::new(&local_app_data)
-            .join("openvr")
-            .join("openvrpaths.vrpath");
+    // 1) Try OpenVR paths file
+    if let Some(ovr_paths) = openvr_paths_file() {
         if ovr_paths.exists() {
             if let Ok(contents) = fs::read_to_string(&ovr_paths) {
                 if let Ok(json) = serde_json::from_str::<Value>(&contents) {
@@ -86,43 +174,86 @@ pub fn get_steamvr_dir() -> Option<PathBuf> {
         }
     }
 
-    // 3) Common SteamVR installation path
-    let steam_paths = vec![
-        // Steam default installation path on 64-bit Windows
-        "C:\\Program Files (x86)\\Steam\\steamapps\\common\\SteamVR",
-    ];
+    // 3) Common SteamVR installation paths
+    default_steamvr_dirs()
+        .into_iter()
+        .find(|path| path.join("bin").join(STEAMVR_BIN_DIR).exists())
+}
 
-    for path_str in steam_paths {
-        let path = Path::new(path_str);
-        if path.exists() && path.join("bin").join("win64").exists() {
-            return Some(path.to_path_buf());
-        }
-    }
+/// Path of the program that starts SteamVR, inside its installation directory
+fn vrstartup_path(steamvr_dir: &Path) -> PathBuf {
+    #[cfg(windows)]
+    return steamvr_dir.join("bin").join("win64").join("vrstartup.exe");
 
-    None
+    #[cfg(not(windows))]
+    return steamvr_dir.join("bin").join("vrstartup.sh");
 }
 
-/// Checks if the application is registered with SteamVR
-pub fn is_registered() -> Result<bool, Box<dyn Error>> {
-    let steamvr_dir = get_steamvr_dir().ok_or("SteamVR installation not found")?;
-    let vrpathreg_path = steamvr_dir.join("bin").join("win64").join("vrpathreg.exe");
+/// Checks whether SteamVR's server process is running
+pub fn is_steamvr_running() -> bool {
+    // tasklist always succeeds, so look for the process in its output
+    #[cfg(windows)]
+    return Command::new("tasklist")
+        .args(["/FI", "IMAGENAME eq vrserver.exe", "/NH"])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("vrserver.exe"));
+
+    #[cfg(not(windows))]
+    return Command::new("pgrep")
+        .args(["-x", "vrserver"])
+        .output()
+        .is_ok_and(|output| output.status.success());
+}
+
+/// Starts SteamVR from its installation directory without waiting for it.
+/// Returns the launcher that was started.
+pub fn launch_steamvr() -> Result<PathBuf, Box<dyn Error>> {
+    let steamvr_dir = get_steamvr_dir().ok_or(LighthouseError::SteamVrNotFound)?;
+    let vrstartup = vrstartup_path(&steamvr_dir);
+    if !vrstartup.exists() {
+        return Err(format!("SteamVR launcher not found at {}", vrstartup.display()).into());
+    }
+
+    Command::new(&vrstartup).current_dir(&steamvr_dir).spawn()?;
+    Ok(vrstartup)
+}
 
+/// Gets the path to SteamVR's vrpathreg tool, telling a missing SteamVR apart
+/// from an install that lacks the tool
+fn vrpathreg_path() -> Result<PathBuf, LighthouseError> {
+    let steamvr_dir = get_steamvr_dir().ok_or(LighthouseError::SteamVrNotFound)?;
+    let vrpathreg_path = steamvr_dir
+        .join("bin")
+        .join(STEAMVR_BIN_DIR)
+        .join(VRPATHREG_BINARY);
     if !vrpathreg_path.exists() {
-        return Err(format!(
-            "vrpathreg.exe not found at expected path: {}",
-            vrpathreg_path.display()
-        )
-        .into());
+        return Err(LighthouseError::VrPathRegMissing(vrpathreg_path));
     }
+    Ok(vrpathreg_path)
+}
+
+/// Builds a command running vrpathreg with `arg`, without a console window on Windows.
+/// On Linux vrpathreg loads `libopenvr_api.so` from its own directory.
+fn vrpathreg_command(vrpathreg_path: &Path, arg: &str) -> Command {
+    let mut command = Command::new(vrpathreg_path);
+    command.arg(arg);
 
     #[cfg(windows)]
-    let output = Command::new(&vrpathreg_path)
-        .arg("show")
-        .creation_flags(CREATE_NO_WINDOW)
-        .output()?;
-    
+    command.creation_flags(CREATE_NO_WINDOW);
+
     #[cfg(not(windows))]
-    let output = Command::new(&vrpathreg_path).arg("show").output()?;
+    if let Some(dir) = vrpathreg_path.parent() {
+        command.env("LD_LIBRARY_PATH", dir);
+    }
+
+    command
+}
+
+/// Checks if the application is registered with SteamVR
+pub fn is_registered() -> Result<bool, Box<dyn Error>> {
+    let vrpathreg_path = vrpathreg_path()?;
+    let output = vrpathreg_command(&vrpathreg_path, "show").output()?;
     let output_str = String::from_utf8_lossy(&output.stdout);
     Ok(output_str.contains("matty.lighthouse-rs"))
 }
@@ -130,17 +261,8 @@ pub fn is_registered() -> Result<bool, Box<dyn Error>> {
 /// Registers the application with SteamVR
 pub fn register_with_steamvr(force_register: bool) -> Result<(), Box<dyn Error>> {
     // Get the path to our manifest file
-    let manifest_path = get_manifest_path()?;
-
-    // Ensure steamvr directory exists and (re)generate manifest from embedded template
+    let mut manifest_path = get_manifest_path()?;
     let exe_path = env::current_exe()?;
-    let exe_dir = exe_path
-        .parent()
-        .ok_or("Failed to get executable directory")?;
-    let steamvr_dir = exe_dir.join("steamvr");
-    if !steamvr_dir.exists() {
-        fs::create_dir_all(&steamvr_dir)?;
-    }
 
     // Build manifest from embedded template and set absolute binary path
     let mut manifest_json: Value = serde_json::from_str(MANIFEST_TEMPLATE)
@@ -151,8 +273,9 @@ pub fn register_with_steamvr(force_register: bool) -> Result<(), Box<dyn Error>>
     {
         if let Some(first) = apps.get_mut(0) {
             if let Some(obj) = first.as_object_mut() {
+                obj.remove("binary_path_windows");
                 obj.insert(
-                    "binary_path_windows".to_string(),
+                    MANIFEST_BINARY_PATH_KEY.to_string(),
                     Value::String(exe_path.to_string_lossy().to_string()),
                 );
                 // Ensure auto_launch so SteamVR starts this helper automatically
@@ -161,33 +284,30 @@ pub fn register_with_steamvr(force_register: bool) -> Result<(), Box<dyn Error>>
         }
     }
     let manifest_contents = serde_json::to_string_pretty(&manifest_json)?;
-    fs::write(&manifest_path, manifest_contents)?;
-    println!("Wrote SteamVR manifest to: {}", manifest_path.display());
 
-    // Get the SteamVR directory
-    let steamvr_dir = get_steamvr_dir().ok_or("SteamVR installation not found")?;
+    // Installs under Program Files aren't writable without admin rights,
+    // so fall back to the app-data directory and register that copy instead
+    if let Err(e) = write_manifest(&manifest_path, &manifest_contents) {
+        let fallback_path = app_data_manifest_path()?;
+        if fallback_path == manifest_path {
+            return Err(e.into());
+        }
+        println!(
+            "Could not write manifest to {} ({}), using the app-data directory instead",
+            manifest_path.display(),
+            e
+        );
+        write_manifest(&fallback_path, &manifest_contents)?;
+        manifest_path = fallback_path;
+    }
+    println!("Wrote SteamVR manifest to: {}", manifest_path.display());
 
     // Path to vrpathreg tool
-    let vrpathreg_path = steamvr_dir.join("bin").join("win64").join("vrpathreg.exe");
-
-    if !vrpathreg_path.exists() {
-        return Err(format!(
-            "vrpathreg.exe not found at expected path: {}",
-            vrpathreg_path.display()
-        )
-        .into());
-    }
+    let vrpathreg_path = vrpathreg_path()?;
 
     // Check if already registered (unless force register is enabled)
     if !force_register {
-        #[cfg(windows)]
-        let output = Command::new(&vrpathreg_path)
-            .arg("show")
-            .creation_flags(CREATE_NO_WINDOW)
-            .output()?;
-        
-        #[cfg(not(windows))]
-        let output = Command::new(&vrpathreg_path).arg("show").output()?;
+        let output = vrpathreg_command(&vrpathreg_path, "show").output()?;
 
         let output_str = String::from_utf8_lossy(&output.stdout);
         if output_str.contains("matty.lighthouse-rs") {
@@ -199,16 +319,7 @@ pub fn register_with_steamvr(force_register: bool) -> Result<(), Box<dyn Error>>
     // Register the manifest with SteamVR
     println!("Registering lighthouse-rs with SteamVR...");
 
-    #[cfg(windows)]
-    let output = Command::new(&vrpathreg_path)
-        .arg("addmanifest")
-        .arg(&manifest_path)
-        .creation_flags(CREATE_NO_WINDOW)
-        .output()?;
-    
-    #[cfg(not(windows))]
-    let output = Command::new(&vrpathreg_path)
-        .arg("addmanifest")
+    let output = vrpathreg_command(&vrpathreg_path, "addmanifest")
         .arg(&manifest_path)
         .output()?;
 
@@ -222,21 +333,50 @@ pub fn register_with_steamvr(force_register: bool) -> Result<(), Box<dyn Error>>
     Ok(())
 }
 
+/// Registers with SteamVR like `register_with_steamvr`, waiting for SteamVR to be
+/// installed first. Until its vrpathreg tool is found, checks again every `interval`
+/// for up to `timeout`, so the app and SteamVR can be installed in either order.
+pub async fn register_when_steamvr_available(
+    force_register: bool,
+    interval: Duration,
+    timeout: Duration,
+    json_output: bool,
+) -> Result<(), Box<dyn Error>> {
+    let deadline = Instant::now() + timeout;
+    let mut attempt = 1;
+    loop {
+        match vrpathreg_path() {
+            Ok(_) => break,
+            Err(e) if Instant::now() + interval <= deadline => {
+                log(
+                    &format!(
+                        "Attempt {}: {} Checking again in {}s...",
+                        attempt,
+                        e,
+                        interval.as_secs()
+                    ),
+                    json_output,
+                );
+                attempt += 1;
+                tokio::time::sleep(interval).await;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    if attempt > 1 {
+        log(
+            &format!("SteamVR found after {} attempts", attempt),
+            json_output,
+        );
+    }
+    register_with_steamvr(force_register)
+}
+
 /// Unregisters the application from SteamVR
 pub fn unregister_from_steamvr() -> Result<(), Box<dyn Error>> {
-    // Get the SteamVR directory
-    let steamvr_dir = get_steamvr_dir().ok_or("SteamVR installation not found")?;
-
     // Path to vrpathreg tool
-    let vrpathreg_path = steamvr_dir.join("bin").join("win64").join("vrpathreg.exe");
-
-    if !vrpathreg_path.exists() {
-        return Err(format!(
-            "vrpathreg.exe not found at expected path: {}",
-            vrpathreg_path.display()
-        )
-        .into());
-    }
+    let vrpathreg_path = vrpathreg_path()?;
 
     // Get the path to our manifest file
     let manifest_path = get_manifest_path()?;
@@ -244,16 +384,7 @@ pub fn unregister_from_steamvr() -> Result<(), Box<dyn Error>> {
     // Unregister the manifest from SteamVR
     println!("Unregistering lighthouse-rs from SteamVR...");
 
-    #[cfg(windows)]
-    let output = Command::new(&vrpathreg_path)
-        .arg("removemanifest")
-        .arg(&manifest_path)
-        .creation_flags(CREATE_NO_WINDOW)
-        .output()?;
-    
-    #[cfg(not(windows))]
-    let output = Command::new(&vrpathreg_path)
-        .arg("removemanifest")
+    let output = vrpathreg_command(&vrpathreg_path, "removemanifest")
         .arg(&manifest_path)
         .output()?;
 
@@ -266,3 +397,191 @@ pub fn unregister_from_steamvr() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+/// Gets the path to SteamVR's lighthouse database (`<Steam>/config/lighthouse/lighthousedb.json`)
+pub fn get_lighthouse_db_path() -> Option<PathBuf> {
+    let steamvr_dir = get_steamvr_dir()?;
+    // SteamVR is installed under <Steam>/steamapps/common/SteamVR
+    let steam_dir = steamvr_dir.ancestors().nth(3)?;
+    let path = steam_dir
+        .join("config")
+        .join("lighthouse")
+        .join(LIGHTHOUSE_DB_FILENAME);
+    path.exists().then_some(path)
+}
+
+/// Loads the serial numbers of all base stations SteamVR knows about, across all universes
+pub fn load_steamvr_base_serials() -> Result<Vec<u32>, Box<dyn Error>> {
+    let path = get_lighthouse_db_path().ok_or("SteamVR lighthouse database not found")?;
+    let json: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+    let mut serials = Vec::new();
+    let universes = json.get("known_universes").and_then(|v| v.as_array());
+    for universe in universes.into_iter().flatten() {
+        let stations = universe.get("base_stations").and_then(|v| v.as_array());
+        for station in stations.into_iter().flatten() {
+            if let Some(serial) = station
+                .get("base_serial_number")
+                .and_then(|v| v.as_u64())
+                .and_then(|v| u32::try_from(v).ok())
+            {
+                if !serials.contains(&serial) {
+                    serials.push(serial);
+                }
+            }
+        }
+    }
+
+    Ok(serials)
+}
+
+/// A SteamVR tracking universe (one physical playspace) and the base stations it uses
+#[derive(Serialize, Debug, Clone)]
+pub struct Playspace {
+    pub universe_id: String,
+    pub base_serials: Vec<u32>,
+}
+
+impl Playspace {
+    /// Name of the device group this playspace is imported as
+    pub fn group_name(&self) -> String {
+        format!("playspace-{}", self.universe_id)
+    }
+}
+
+/// Reads the playspaces SteamVR knows about from its lighthouse database.
+/// Returns an empty list when SteamVR or its database isn't installed.
+pub fn import_playspaces() -> Result<Vec<Playspace>, Box<dyn Error>> {
+    let Some(path) = get_lighthouse_db_path() else {
+        return Ok(Vec::new());
+    };
+    let json: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+    let mut playspaces = Vec::new();
+    let universes = json.get("known_universes").and_then(|v| v.as_array());
+    for universe in universes.into_iter().flatten() {
+        // Universe IDs are written as either strings or numbers
+        let universe_id = match universe.get("id") {
+            Some(Value::String(id)) => id.clone(),
+            Some(Value::Number(id)) => id.to_string(),
+            _ => continue,
+        };
+        let base_serials = universe
+            .get("base_stations")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|station| station.get("base_serial_number")?.as_u64())
+            .filter_map(|serial| u32::try_from(serial).ok())
+            .collect();
+
+        playspaces.push(Playspace {
+            universe_id,
+            base_serials,
+        });
+    }
+
+    Ok(playspaces)
+}
+
+/// Groups matched base stations by playspace, keyed by group name.
+/// Base stations that aren't in any playspace are left out.
+pub fn assign_playspaces(
+    playspaces: &[Playspace],
+    matches: &[SteamVrMatch],
+) -> BTreeMap<String, Vec<String>> {
+    let mut groups = BTreeMap::new();
+    for playspace in playspaces {
+        let addresses: Vec<String> = matches
+            .iter()
+            .filter(|m| {
+                m.steamvr_serial
+                    .is_some_and(|serial| playspace.base_serials.contains(&serial))
+            })
+            .map(|m| m.address.clone())
+            .collect();
+        if !addresses.is_empty() {
+            groups.insert(playspace.group_name(), addresses);
+        }
+    }
+    groups
+}
+
+/// A base station found over Bluetooth, matched against SteamVR's lighthouse database
+#[derive(Serialize, Debug, Clone)]
+pub struct SteamVrMatch {
+    pub name: String,
+    pub address: String,
+    /// Serial number read from the device over GATT, if it could be read
+    pub serial: Option<String>,
+    /// The matching serial number from SteamVR's lighthouse database, if any
+    pub steamvr_serial: Option<u32>,
+}
+
+/// Possible numeric identities for a base station, from its GATT serial and its `LHB-XXXXXXXX` name
+fn identity_candidates(name: &str, serial: Option<&str>) -> Vec<u32> {
+    let mut candidates = Vec::new();
+    if let Some(serial) = serial {
+        candidates.extend(serial.parse::<u32>().ok());
+        candidates.extend(u32::from_str_radix(serial, 16).ok());
+    }
+    if let Some(suffix) = name
+        .strip_prefix(LHB_PREFIX)
+        .map(|s| s.trim_start_matches('-'))
+    {
+        candidates.extend(u32::from_str_radix(suffix, 16).ok());
+    }
+    candidates
+}
+
+/// Reads each base station's identity over GATT and matches it against SteamVR's lighthouse
+/// database. A failed read or an unavailable database leaves the corresponding fields `None`
+/// rather than aborting.
+pub async fn correlate_with_steamvr(
+    peripherals: &[Peripheral],
+    json_output: bool,
+) -> Vec<SteamVrMatch> {
+    let steamvr_serials = match load_steamvr_base_serials() {
+        Ok(serials) => serials,
+        Err(e) => {
+            log(
+                &format!("SteamVR lighthouse database unavailable: {}", e),
+                json_output,
+            );
+            Vec::new()
+        }
+    };
+
+    let mut matches = Vec::new();
+    for peripheral in peripherals {
+        let address = peripheral.address().to_string();
+        let name = read_properties(peripheral, json_output)
+            .await
+            .and_then(|props| props.local_name)
+            .unwrap_or_else(|| UNKNOWN_DEVICE_NAME.to_string());
+
+        let serial = match read_serial_number(peripheral).await {
+            Ok(serial) => serial,
+            Err(e) => {
+                log(
+                    &format!("Failed to read serial number from {}: {}", name, e),
+                    json_output,
+                );
+                None
+            }
+        };
+
+        let steamvr_serial = identity_candidates(&name, serial.as_deref())
+            .into_iter()
+            .find(|candidate| steamvr_serials.contains(candidate));
+
+        matches.push(SteamVrMatch {
+            name,
+            address,
+            serial,
+            steamvr_serial,
+        });
+    }
+
+    matches
+}